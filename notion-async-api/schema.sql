@@ -96,3 +96,16 @@ CREATE TABLE IF NOT EXISTS comments (
     -- array of rich text objects
     rich_text TEXT not null
 );
+
+-- Database views referenced by links. The public API doesn't expose views, so
+-- only the view IDs found in links are recorded here.
+CREATE TABLE IF NOT EXISTS views (
+    id TEXT not null,
+    database_id TEXT not null,
+
+    -- the object in which the view link is found
+    source_id TEXT not null,
+    url TEXT not null,
+
+    primary key (id, source_id)
+);
@@ -1,10 +1,12 @@
 use std::{
+    collections::HashSet,
     fmt::{Debug, Display},
-    sync::LazyLock,
+    sync::{Arc, LazyLock, Mutex},
 };
 
-use reqwest::{header, Client, Method, Response, StatusCode, Url};
-use serde::de::DeserializeOwned;
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{header, Client, Method, Request, RequestBuilder, Response, StatusCode, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
     block::Block,
@@ -14,25 +16,135 @@ use crate::{
     fetcher::AnyObject,
     object::{NextCursor, ObjectList},
     page::Page,
+    rich_text::RichText,
     user::User,
 };
 
 const NOTION_API_VERSION: &str = "2022-06-28";
+const NOTION_VERSION_HEADER: &str = "Notion-Version";
 
 /// Low-level notion Api.
 #[derive(Clone)]
 pub struct Api {
     client: Client,
+    executor: Arc<dyn HttpExecutor>,
+    // the `Authorization` header, marked sensitive
+    auth: header::HeaderValue,
+    base_url: Url,
+    // the requested `Notion-Version`
+    version: String,
+    version_check: Arc<Mutex<VersionCheck>>,
+}
+
+/// The `Notion-Version`s echoed in the responses which differ from the
+/// requested one.
+#[derive(Default)]
+struct VersionCheck {
+    seen: HashSet<String>,
+    // not taken yet
+    new: Vec<String>,
+}
+
+/// Executes the HTTP requests built by [`Api`].
+///
+/// [`ReqwestExecutor`] is used by default. Tests can provide their own
+/// implementation to return scripted responses without network access.
+pub trait HttpExecutor: Send + Sync {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>>;
+}
+
+/// The default [`HttpExecutor`], which sends requests with a
+/// [`reqwest::Client`].
+#[derive(Clone)]
+pub struct ReqwestExecutor {
+    client: Client,
+}
+
+impl ReqwestExecutor {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpExecutor for ReqwestExecutor {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+        self.client.execute(request).boxed()
+    }
 }
 
 #[derive(Debug)]
 pub enum RequestError {
     InvalidRequest(String),
     InvalidResponse(String),
+    /// An error response of the API, e.g. a 404 with the code
+    /// `object_not_found`.
+    NotionApiError {
+        status: u16,
+        code: ApiErrorCode,
+        message: String,
+    },
     RetryAfter(u64), // seconds
     Other(reqwest::Error),
 }
 
+/// The `code` of an error response of the API.
+///
+/// Refer to: [Status codes](https://developers.notion.com/reference/status-codes)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorCode {
+    InvalidJson,
+    InvalidRequestUrl,
+    InvalidRequest,
+    InvalidGrant,
+    ValidationError,
+    MissingVersion,
+    Unauthorized,
+    RestrictedResource,
+    ObjectNotFound,
+    ConflictError,
+    RateLimited,
+    InternalServerError,
+    BadGateway,
+    ServiceUnavailable,
+    DatabaseConnectionUnavailable,
+    GatewayTimeout,
+    /// A code unknown to this crate.
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl ApiErrorCode {
+    /// The raw value, e.g. `object_not_found`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ApiErrorCode::InvalidJson => "invalid_json",
+            ApiErrorCode::InvalidRequestUrl => "invalid_request_url",
+            ApiErrorCode::InvalidRequest => "invalid_request",
+            ApiErrorCode::InvalidGrant => "invalid_grant",
+            ApiErrorCode::ValidationError => "validation_error",
+            ApiErrorCode::MissingVersion => "missing_version",
+            ApiErrorCode::Unauthorized => "unauthorized",
+            ApiErrorCode::RestrictedResource => "restricted_resource",
+            ApiErrorCode::ObjectNotFound => "object_not_found",
+            ApiErrorCode::ConflictError => "conflict_error",
+            ApiErrorCode::RateLimited => "rate_limited",
+            ApiErrorCode::InternalServerError => "internal_server_error",
+            ApiErrorCode::BadGateway => "bad_gateway",
+            ApiErrorCode::ServiceUnavailable => "service_unavailable",
+            ApiErrorCode::DatabaseConnectionUnavailable => "database_connection_unavailable",
+            ApiErrorCode::GatewayTimeout => "gateway_timeout",
+            ApiErrorCode::Other(x) => x,
+        }
+    }
+}
+
+impl Display for ApiErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
 impl RequestError {
     pub fn invalid_response(s: impl Into<String>) -> Self {
         Self::InvalidResponse(s.into())
@@ -44,6 +156,11 @@ impl Display for RequestError {
         let s = match self {
             RequestError::InvalidRequest(s) => format!("invalid request: {s}"),
             RequestError::InvalidResponse(s) => format!("invalid response: {s}"),
+            RequestError::NotionApiError {
+                status,
+                code,
+                message,
+            } => format!("notion api error: status: {status}, code: {code}, message: {message}"),
             RequestError::RetryAfter(s) => format!("retry after: {s}"),
             RequestError::Other(e) => format!("request error: {e:?}"),
         };
@@ -51,49 +168,321 @@ impl Display for RequestError {
     }
 }
 
-impl std::error::Error for RequestError {}
+impl std::error::Error for RequestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RequestError::Other(e) => Some(e),
+            RequestError::InvalidRequest(_)
+            | RequestError::InvalidResponse(_)
+            | RequestError::NotionApiError { .. }
+            | RequestError::RetryAfter(_) => None,
+        }
+    }
+}
 
 impl Api {
     pub fn new(token: &str) -> Self {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "Notion-Version",
-            header::HeaderValue::from_static(NOTION_API_VERSION),
-        );
-        let bearer = format!("Bearer {}", token);
-        let mut auth_value = header::HeaderValue::from_str(&bearer)
-            .expect("token: only visible ASCII characters (32-127) are permitted");
-        auth_value.set_sensitive(true);
-        headers.insert(header::AUTHORIZATION, auth_value);
+        Self::with_client(token, Client::new())
+    }
 
+    /// Create an [`Api`] which sends all requests with `client`, e.g. one
+    /// with timeouts or a proxy. The auth & version headers are set on every
+    /// request, on top of the default headers of `client`.
+    pub fn with_client(token: &str, client: Client) -> Self {
         Api {
-            client: Client::builder().default_headers(headers).build().unwrap(),
+            executor: Arc::new(ReqwestExecutor::new(client.clone())),
+            client,
+            auth: auth_header(token),
+            base_url: BASE_URL.clone(),
+            version: NOTION_API_VERSION.to_owned(),
+            version_check: Default::default(),
         }
     }
 
+    /// Create an [`Api`] which sends all requests through `executor`.
+    pub fn with_executor(token: &str, executor: impl HttpExecutor + 'static) -> Self {
+        Api {
+            client: Client::new(),
+            executor: Arc::new(executor),
+            auth: auth_header(token),
+            base_url: BASE_URL.clone(),
+            version: NOTION_API_VERSION.to_owned(),
+            version_check: Default::default(),
+        }
+    }
+
+    /// Request the API `version` (the `Notion-Version` header, e.g.
+    /// `2025-09-03`) instead of the one this crate is written for, which is
+    /// the default. Fails if `version` isn't a valid header value, i.e. not
+    /// visible ASCII.
+    pub fn with_version(mut self, version: impl Into<String>) -> Result<Self, NotionError> {
+        let version = version.into();
+        if !version.is_ascii() || header::HeaderValue::from_str(&version).is_err() {
+            return Err(NotionError::invalid_request(format!(
+                "invalid Notion-Version: {version:?}"
+            )));
+        }
+        self.version = version;
+        Ok(self)
+    }
+
+    /// Send the requests to `base_url` (e.g. `http://localhost:8080/v1/`)
+    /// instead of `https://api.notion.com/v1/`, e.g. to test against a mock
+    /// server.
+    pub fn with_base_url(mut self, mut base_url: Url) -> Self {
+        // so that the paths are joined under it
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        self.base_url = base_url;
+        self
+    }
+
+    /// The base URL of the API, which the request URLs are relative to.
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// The requested API version.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The `Notion-Version`s echoed in the responses which differ from the
+    /// requested one, e.g. after the API auto-upgrades an account. Each
+    /// version is returned only once.
+    pub fn take_version_mismatches(&self) -> Vec<String> {
+        std::mem::take(&mut self.version_check.lock().unwrap().new)
+    }
+
+    fn check_version(&self, res: &Response) {
+        let Some(version) = res
+            .headers()
+            .get(NOTION_VERSION_HEADER)
+            .and_then(|x| x.to_str().ok())
+        else {
+            return;
+        };
+        if version != self.version {
+            let mut check = self.version_check.lock().unwrap();
+            if check.seen.insert(version.to_owned()) {
+                check.new.push(version.to_owned());
+            }
+        }
+    }
+
+    /// Build the request, send it by the executor, and check the response
+    /// status.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, NotionError> {
+        let request = request
+            .header(header::AUTHORIZATION, self.auth.clone())
+            .header(NOTION_VERSION_HEADER, &self.version);
+        let res = self.executor.execute(request.build()?).await?;
+        self.check_version(&res);
+        check_retry_after(&res)?;
+        check_status_code(res).await
+    }
+
     pub async fn get_object<T>(&self, id: &str) -> Result<T, NotionError>
     where
         T: DeserializeOwned + Requestable,
     {
-        let res = self.client.get(T::url(id)).send().await?;
-        check_retry_after(&res)?;
-        let res = check_status_code(res).await?;
+        self.get_url(T::url(&self.base_url, id)).await
+    }
 
-        res.json::<T>().await.map_err(|e| {
-            NotionError::RequestFailed(RequestError::InvalidResponse(format!(
-                "decode failed: {e:?}, {}",
-                T::url(id),
-            )))
+    /// The bot user of the token, e.g. to check that the token is valid
+    /// before a long sync. An invalid token fails with the status & body of
+    /// the response, i.e. 401 Unauthorized.
+    ///
+    /// Refer to: [Retrieve your token's bot user](https://developers.notion.com/reference/get-self)
+    pub async fn me(&self) -> Result<User, NotionError> {
+        self.get_object::<User>("me").await
+    }
+
+    /// The first page of the users of the workspace, including the bots. The
+    /// integration needs the "read user information" capability.
+    ///
+    /// The results paginate like [`Api::list`].
+    ///
+    /// Refer to: [List all users](https://developers.notion.com/reference/get-users)
+    pub async fn list_users(&self) -> Result<PaginationResult<User>, NotionError> {
+        self.list(&PaginationInfo::new::<ObjectList<User>>(&self.base_url, ""))
+            .await
+    }
+
+    /// Get a page with only the properties of `filter_props` (property IDs,
+    /// e.g. `title`), to cut the payload of the pages with many large
+    /// properties.
+    ///
+    /// Refer to: [Retrieve a page](https://developers.notion.com/reference/retrieve-a-page)
+    pub async fn get_page_with_properties(
+        &self,
+        id: &str,
+        filter_props: &[&str],
+    ) -> Result<Page, NotionError> {
+        self.get_url(filter_properties(
+            Page::url(&self.base_url, id),
+            filter_props,
+        ))
+        .await
+    }
+
+    /// Whether the block has children, without fetching them all, e.g. for a
+    /// lazy-loading UI to show "N+ items". Only the first child is fetched.
+    pub async fn children_summary(&self, block_id: &str) -> Result<ChildrenSummary, NotionError> {
+        let pagination =
+            PaginationInfo::new::<ObjectList<Block>>(&self.base_url, block_id).page_size(1);
+        let res: PaginationResult<serde_json::Value> = self.list(&pagination).await?;
+        Ok(ChildrenSummary {
+            first_page_count: res.result.results.len(),
+            has_more: res.pagination.is_some(),
         })
     }
 
+    /// Search the pages & databases shared with the integration by title,
+    /// optionally only of one object type. An empty `query` matches all of
+    /// them, e.g. to discover the top-level shared pages.
+    ///
+    /// The results paginate like [`Api::list`].
+    ///
+    /// Refer to: [Search by title](https://developers.notion.com/reference/post-search)
+    pub async fn search(
+        &self,
+        query: &str,
+        filter: Option<SearchFilter>,
+    ) -> Result<PaginationResult<AnyObject>, NotionError> {
+        let mut body = serde_json::json!({ "query": query });
+        if let Some(filter) = filter {
+            body["filter"] = filter.to_json();
+        }
+        let pagination =
+            PaginationInfo::build(self.base_url.join("search").unwrap(), Method::POST).body(body);
+        self.list(&pagination).await
+    }
+
+    /// Query the rows of a database, filtered & sorted by `body`.
+    ///
+    /// The results paginate like [`Api::list`], the cursor is sent in the
+    /// body along with the filter & sorts.
+    ///
+    /// Refer to: [Query a database](https://developers.notion.com/reference/post-database-query)
+    pub async fn query_database(
+        &self,
+        id: &str,
+        body: QueryBody,
+    ) -> Result<PaginationResult<AnyObject>, NotionError> {
+        let pagination = PaginationInfo::new::<ObjectList<AnyObject>>(&self.base_url, id)
+            .body(serde_json::to_value(body).unwrap());
+        self.list(&pagination).await
+    }
+
+    /// Search the pages & databases shared with the integration, the most
+    /// recently edited first.
+    ///
+    /// The results paginate like [`Api::list`].
+    pub(crate) async fn search_recently_edited(
+        &self,
+    ) -> Result<PaginationResult<AnyObject>, NotionError> {
+        let body = serde_json::json!({
+            "sort": { "direction": "descending", "timestamp": "last_edited_time" },
+        });
+        let pagination =
+            PaginationInfo::build(self.base_url.join("search").unwrap(), Method::POST).body(body);
+        self.list(&pagination).await
+    }
+
+    /// Comment on a page, or reply to an existing discussion, e.g. to leave
+    /// a review note. The integration needs the "insert comments"
+    /// capability.
+    ///
+    /// Refer to: [Create comment](https://developers.notion.com/reference/create-a-comment)
+    pub async fn create_comment(
+        &self,
+        parent: CommentTarget,
+        rich_text: Vec<RichText>,
+    ) -> Result<Comment, NotionError> {
+        let mut body = parent.to_json();
+        body["rich_text"] = serde_json::to_value(rich_text).unwrap();
+        self.send_json(Method::POST, self.base_url.join("comments").unwrap(), &body)
+            .await
+    }
+
+    /// Append `children` (block objects, e.g. `{"type": "paragraph",
+    /// "paragraph": {"rich_text": [...]}}`) to the block or page, at most
+    /// 100 of them. Return the appended blocks, without their own children.
+    ///
+    /// Refer to: [Append block children](https://developers.notion.com/reference/patch-block-children)
+    pub async fn append_block_children(
+        &self,
+        block_id: &str,
+        children: Vec<serde_json::Value>,
+    ) -> Result<Vec<Block>, NotionError> {
+        let url = ObjectList::<Block>::url(&self.base_url, block_id);
+        let body = serde_json::json!({ "children": children });
+        let res: ObjectList<Block> = self.send_json(Method::PATCH, url, &body).await?;
+        Ok(res.results)
+    }
+
+    /// Update the properties, archive state, icon or cover of a page, only
+    /// the fields set in `patch` are changed. Return the updated page.
+    ///
+    /// Refer to: [Update page properties](https://developers.notion.com/reference/patch-page)
+    pub async fn update_page(&self, id: &str, patch: PagePatch) -> Result<Page, NotionError> {
+        let body = serde_json::to_value(patch).unwrap();
+        self.send_json(Method::PATCH, Page::url(&self.base_url, id), &body)
+            .await
+    }
+
+    async fn get_url<T: DeserializeOwned>(&self, url: Url) -> Result<T, NotionError> {
+        let res = self.send(self.client.get(url.clone())).await?;
+        decode(res, &url).await
+    }
+
+    async fn send_json<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: Url,
+        body: &serde_json::Value,
+    ) -> Result<T, NotionError> {
+        let res = self
+            .send(self.client.request(method, url.clone()).json(body))
+            .await?;
+        decode(res, &url).await
+    }
+
     pub async fn list<T, P>(&self, pagination: &P) -> Result<PaginationResult<T>, NotionError>
     where
         T: DeserializeOwned,
         P: Pagination<T> + NextCursor,
     {
-        pagination.next_page(&self.client).await
+        pagination.next_page(self).await
+    }
+}
+
+async fn decode<T: DeserializeOwned>(res: Response, url: &Url) -> Result<T, NotionError> {
+    res.json::<T>().await.map_err(|e| {
+        NotionError::RequestFailed(RequestError::InvalidResponse(format!(
+            "decode failed: {e:?}, {url}",
+        )))
+    })
+}
+
+fn auth_header(token: &str) -> header::HeaderValue {
+    let mut value = header::HeaderValue::from_str(&format!("Bearer {token}"))
+        .expect("token: only visible ASCII characters (32-127) are permitted");
+    value.set_sensitive(true);
+    value
+}
+
+/// Append a `filter_properties` query param to `url` for each property.
+fn filter_properties(mut url: Url, filter_props: &[&str]) -> Url {
+    if !filter_props.is_empty() {
+        let mut pairs = url.query_pairs_mut();
+        for prop in filter_props {
+            pairs.append_pair("filter_properties", prop);
+        }
     }
+    url
 }
 
 fn check_retry_after(res: &Response) -> Result<(), NotionError> {
@@ -116,51 +505,210 @@ fn check_retry_after(res: &Response) -> Result<(), NotionError> {
     Ok(())
 }
 
+/// Fails with the error response parsed as [`RequestError::NotionApiError`],
+/// or with the raw body if it isn't one, e.g. from a proxy.
 async fn check_status_code(res: Response) -> Result<Response, NotionError> {
-    if !res.status().is_success() {
-        let url = res.url().clone();
-        Err(NotionError::invalid_response(format!(
-            "status: {}, body: {}, url: {url}",
-            res.status(),
-            res.text().await?,
-        )))
-    } else {
-        Ok(res)
+    // {"object": "error", "status": 404, "code": "object_not_found", "message": "..."}
+    #[derive(Deserialize)]
+    struct ErrorBody {
+        code: ApiErrorCode,
+        #[serde(default)]
+        message: String,
+    }
+
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
     }
+    let url = res.url().clone();
+    let body = res.text().await?;
+    Err(match serde_json::from_str::<ErrorBody>(&body) {
+        Ok(ErrorBody { code, message }) => {
+            NotionError::RequestFailed(RequestError::NotionApiError {
+                status: status.as_u16(),
+                code,
+                message,
+            })
+        }
+        Err(_) => {
+            NotionError::invalid_response(format!("status: {status}, body: {body}, url: {url}"))
+        }
+    })
 }
 
 pub trait Pagination<Item>: Debug {
     fn next_page(
         &self,
-        client: &Client,
+        api: &Api,
     ) -> impl std::future::Future<Output = Result<PaginationResult<Item>, NotionError>> + Send;
 }
 
+/// The body of a database query, see [`Api::query_database`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueryBody {
+    /// A filter object, e.g. `{"property": "Done", "checkbox": {"equals":
+    /// false}}`, see [Filter database entries].
+    ///
+    /// [Filter database entries]: https://developers.notion.com/reference/post-database-query-filter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<serde_json::Value>,
+    /// The sorts in order of precedence.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sorts: Vec<QuerySort>,
+}
+
+/// The changes of a page, see [`Api::update_page`]. Only the fields set are
+/// sent, e.g. `PagePatch::new().property("Processed", json!({"checkbox":
+/// true}))` marks a row as processed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PagePatch {
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    properties: serde_json::Map<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archived: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cover: Option<serde_json::Value>,
+}
+
+impl PagePatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the property of `name` (or ID) to a property value, e.g.
+    /// `{"checkbox": true}`, see [Page properties].
+    ///
+    /// [Page properties]: https://developers.notion.com/reference/page-property-values
+    pub fn property(mut self, name: impl Into<String>, value: serde_json::Value) -> Self {
+        self.properties.insert(name.into(), value);
+        self
+    }
+
+    /// Archive (i.e. move to the trash) or restore the page.
+    pub fn archived(mut self, archived: bool) -> Self {
+        self.archived = Some(archived);
+        self
+    }
+
+    /// Set the icon, an emoji or file object, e.g. `{"emoji": "✅"}`.
+    pub fn icon(mut self, icon: serde_json::Value) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Set the cover, a file object, e.g. `{"external": {"url": "..."}}`.
+    pub fn cover(mut self, cover: serde_json::Value) -> Self {
+        self.cover = Some(cover);
+        self
+    }
+}
+
+/// A sort of a database query.
+///
+/// Refer to: [Sort database entries](https://developers.notion.com/reference/post-database-query-sort)
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum QuerySort {
+    /// By a property, by its name or ID.
+    Property {
+        property: String,
+        direction: SortDirection,
+    },
+    /// By `created_time` or `last_edited_time`.
+    Timestamp {
+        timestamp: String,
+        direction: SortDirection,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// The max number of items per page of a list, which is also the default of
+/// Notion.
+pub const MAX_PAGE_SIZE: usize = 100;
+
 #[derive(Clone)]
 pub struct PaginationInfo {
     cursor: Option<String>,
     url: Url,
     method: Method,
+    // the JSON body of a POST request, which carries the cursor & page size
+    // instead of the query string
+    body: Option<serde_json::Value>,
+    page_size: usize,
+    // parse each element of the results on its own
+    lenient: bool,
     start_index: usize,
 }
 
 impl PaginationInfo {
-    pub fn new<R>(id: &str) -> Self
+    /// The first page of the list `R` of `id`, under the API `base` (see
+    /// [`Api::base_url`]).
+    pub fn new<R>(base: &Url, id: &str) -> Self
     where
         R: Requestable,
     {
-        Self::build(R::url(id), R::method())
+        Self::build(R::url(base, id), R::method())
+    }
+
+    #[cfg(any(feature = "otel", feature = "tracing"))]
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    #[cfg(any(feature = "otel", feature = "tracing"))]
+    pub(crate) fn current_cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    /// Only request the properties of `filter_props`, see
+    /// [`Api::get_page_with_properties`].
+    pub(crate) fn filter_properties(mut self, filter_props: &[&str]) -> Self {
+        self.url = filter_properties(self.url, filter_props);
+        self
+    }
+
+    /// Request at most `n` (clamped to `1..=`[`MAX_PAGE_SIZE`]) items per
+    /// page, [`MAX_PAGE_SIZE`] by default. A smaller page arrives sooner,
+    /// e.g. for a preview, while a larger one saves round trips.
+    pub(crate) fn page_size(mut self, n: usize) -> Self {
+        self.page_size = n.clamp(1, MAX_PAGE_SIZE);
+        self
+    }
+
+    /// Parse each element of the pages on its own if `lenient`, so that a
+    /// malformed element is skipped (see [`ObjectList::invalid`]) rather
+    /// than failing the whole page.
+    pub(crate) fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
     }
 
     fn build(url: Url, method: Method) -> Self {
         Self {
             cursor: None,
+            // the cursor of a POST request goes into the body
+            body: (method == Method::POST).then(|| serde_json::json!({})),
             url,
             method,
+            page_size: MAX_PAGE_SIZE,
+            lenient: false,
             start_index: 0,
         }
     }
 
+    fn body(mut self, body: serde_json::Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
     fn cursor(mut self, cursor: String) -> Self {
         self.cursor = Some(cursor);
         self
@@ -191,29 +739,59 @@ impl<T> Pagination<T> for PaginationInfo
 where
     T: DeserializeOwned + Send,
 {
-    async fn next_page(&self, client: &Client) -> Result<PaginationResult<T>, NotionError> {
+    async fn next_page(&self, api: &Api) -> Result<PaginationResult<T>, NotionError> {
         let mut url = self.url.clone();
+        let mut body = self.body.clone();
 
         if let Some(ref next_cursor) = self.cursor {
-            // set start_cursor
-            let q = self.url.query_pairs().filter(|(k, _)| k != "start_cursor");
-            url.query_pairs_mut()
-                .clear()
-                .extend_pairs(q)
-                .append_pair("start_cursor", next_cursor)
-                .finish();
+            if self.method == Method::POST {
+                body.get_or_insert_with(|| serde_json::json!({}))["start_cursor"] =
+                    next_cursor.as_str().into();
+            } else {
+                // set start_cursor
+                let q = self.url.query_pairs().filter(|(k, _)| k != "start_cursor");
+                url.query_pairs_mut()
+                    .clear()
+                    .extend_pairs(q)
+                    .append_pair("start_cursor", next_cursor)
+                    .finish();
+            }
         };
 
-        let res = client.request(self.method.clone(), url).send().await?;
-        check_retry_after(&res)?;
-        let res = check_status_code(res).await?;
+        // the default page size of Notion is left out
+        if self.page_size != MAX_PAGE_SIZE {
+            if self.method == Method::POST {
+                body.get_or_insert_with(|| serde_json::json!({}))["page_size"] =
+                    self.page_size.into();
+            } else {
+                url.query_pairs_mut()
+                    .append_pair("page_size", &self.page_size.to_string());
+            }
+        }
+
+        let mut request = api.client.request(self.method.clone(), url);
+        if let Some(body) = body.as_ref() {
+            request = request.json(body);
+        }
+        let res = api.send(request).await?;
 
-        let mut res: ObjectList<T> = res.json().await?;
+        let mut res: ObjectList<T> = if self.lenient {
+            res.json::<ObjectList<serde_json::Value>>()
+                .await?
+                .parse_each()
+        } else {
+            res.json().await?
+        };
         res.start_index = self.start_index;
         let next_page = res.next_cursor().map(|x| {
-            PaginationInfo::build(self.url.clone(), self.method.clone())
-                .cursor(x.to_owned())
-                .start_index(self.start_index + res.results.len())
+            PaginationInfo {
+                body: self.body.clone(),
+                page_size: self.page_size,
+                lenient: self.lenient,
+                ..PaginationInfo::build(self.url.clone(), self.method.clone())
+            }
+            .cursor(x.to_owned())
+            .start_index(self.start_index + res.results.len() + res.invalid.len())
         });
 
         Ok(PaginationResult::<T> {
@@ -223,6 +801,52 @@ where
     }
 }
 
+/// Limits [`Api::search`] to one object type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFilter {
+    Page,
+    Database,
+}
+
+impl SearchFilter {
+    fn to_json(self) -> serde_json::Value {
+        let value = match self {
+            SearchFilter::Page => "page",
+            SearchFilter::Database => "database",
+        };
+        serde_json::json!({ "property": "object", "value": value })
+    }
+}
+
+/// Where [`Api::create_comment`] puts a comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentTarget {
+    /// Start a new discussion on the page of this ID.
+    Page(String),
+    /// Reply to the discussion of this ID, see [`Comment::discussion_id`].
+    Discussion(String),
+}
+
+impl CommentTarget {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            CommentTarget::Page(id) => serde_json::json!({ "parent": { "page_id": id } }),
+            CommentTarget::Discussion(id) => serde_json::json!({ "discussion_id": id }),
+        }
+    }
+}
+
+/// A cheap summary of the children of a block, see
+/// [`Api::children_summary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChildrenSummary {
+    /// The number of the children in the first page, a lower bound of the
+    /// number of all children.
+    pub first_page_count: usize,
+    /// Whether there are more children than the first page.
+    pub has_more: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct PaginationResult<T> {
     pub result: ObjectList<T>,
@@ -230,7 +854,9 @@ pub struct PaginationResult<T> {
 }
 
 pub trait Requestable {
-    fn url(id: &str) -> Url;
+    /// The URL of the object/list `id`, relative to the API `base` (see
+    /// [`Api::base_url`]).
+    fn url(base: &Url, id: &str) -> Url;
     fn method() -> Method {
         Method::GET
     }
@@ -240,32 +866,45 @@ static BASE_URL: LazyLock<Url> =
     LazyLock::new(|| Url::parse("https://api.notion.com/v1/").unwrap());
 
 impl Requestable for Block {
-    fn url(id: &str) -> Url {
-        BASE_URL.join(&format!("blocks/{id}")).unwrap()
+    fn url(base: &Url, id: &str) -> Url {
+        base.join(&format!("blocks/{id}")).unwrap()
     }
 }
 
 impl Requestable for Page {
-    fn url(id: &str) -> Url {
-        BASE_URL.join(&format!("pages/{id}")).unwrap()
+    fn url(base: &Url, id: &str) -> Url {
+        base.join(&format!("pages/{id}")).unwrap()
     }
 }
 
 impl Requestable for Database {
-    fn url(id: &str) -> Url {
-        BASE_URL.join(&format!("databases/{id}")).unwrap()
+    fn url(base: &Url, id: &str) -> Url {
+        base.join(&format!("databases/{id}")).unwrap()
     }
 }
 
 impl Requestable for ObjectList<Block> {
-    fn url(id: &str) -> Url {
-        BASE_URL.join(&format!("blocks/{id}/children")).unwrap()
+    fn url(base: &Url, id: &str) -> Url {
+        base.join(&format!("blocks/{id}/children")).unwrap()
     }
 }
 
 impl Requestable for ObjectList<AnyObject> {
-    fn url(id: &str) -> Url {
-        BASE_URL.join(&format!("databases/{id}/query")).unwrap()
+    fn url(base: &Url, id: &str) -> Url {
+        base.join(&format!("databases/{id}/query")).unwrap()
+    }
+
+    fn method() -> Method {
+        Method::POST
+    }
+}
+
+/// Query the rows of a data source.
+pub(crate) struct DataSourceQuery;
+
+impl Requestable for DataSourceQuery {
+    fn url(base: &Url, id: &str) -> Url {
+        base.join(&format!("data_sources/{id}/query")).unwrap()
     }
 
     fn method() -> Method {
@@ -274,21 +913,396 @@ impl Requestable for ObjectList<AnyObject> {
 }
 
 impl Requestable for ObjectList<Comment> {
-    fn url(id: &str) -> Url {
-        let mut url = BASE_URL.join("comments").unwrap();
+    fn url(base: &Url, id: &str) -> Url {
+        let mut url = base.join("comments").unwrap();
         url.query_pairs_mut().append_pair("block_id", id).finish();
         url
     }
 }
 
 impl Requestable for User {
-    fn url(id: &str) -> Url {
-        BASE_URL.join(&format!("users/{id}")).unwrap()
+    fn url(base: &Url, id: &str) -> Url {
+        base.join(&format!("users/{id}")).unwrap()
     }
 }
 
 impl Requestable for ObjectList<User> {
-    fn url(_: &str) -> Url {
-        BASE_URL.join("users").unwrap()
+    fn url(base: &Url, _: &str) -> Url {
+        base.join("users").unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::sync::{Arc, Mutex};
+
+    use futures::future::BoxFuture;
+    use reqwest::{header, Request, Response};
+
+    use crate::{
+        tests::{block_json, list_json, page_json, response, user_json, RecordingExecutor},
+        AnyObject, HttpExecutor, NotionError, Object, RichText, User,
+    };
+
+    use super::{
+        Api, ApiErrorCode, ChildrenSummary, CommentTarget, PagePatch, QueryBody, QuerySort,
+        RequestError, SearchFilter, SortDirection,
+    };
+
+    #[tokio::test]
+    async fn get_page_with_properties() {
+        let executor = RecordingExecutor::default().route(
+            "GET pages/p1?filter_properties=title&filter_properties=a%3Bb",
+            page_json("p1", "workspace"),
+        );
+        let api = Api::with_executor("token", executor.clone());
+        let page = api
+            .get_page_with_properties("p1", &["title", "a;b"])
+            .await
+            .unwrap();
+        assert_eq!(page.obj.id, "p1");
+
+        api.get_page_with_properties("p1", &[]).await.unwrap_err();
+        assert_eq!(
+            executor.requests(),
+            [
+                "GET pages/p1",
+                "GET pages/p1?filter_properties=title&filter_properties=a%3Bb"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn children_summary() {
+        let executor = RecordingExecutor::default()
+            .route(
+                "GET blocks/b1/children?page_size=1",
+                list_json(vec![block_json("c1", "b1", "paragraph")], Some("c2")),
+            )
+            .route(
+                "GET blocks/b2/children?page_size=1",
+                list_json(vec![], None),
+            );
+        let api = Api::with_executor("token", executor);
+
+        assert_eq!(
+            api.children_summary("b1").await.unwrap(),
+            ChildrenSummary {
+                first_page_count: 1,
+                has_more: true,
+            }
+        );
+        assert_eq!(
+            api.children_summary("b2").await.unwrap(),
+            ChildrenSummary {
+                first_page_count: 0,
+                has_more: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn search() {
+        let executor = RecordingExecutor::default().route(
+            "POST search",
+            list_json(vec![page_json("p1", "workspace")], Some("c2")),
+        );
+        let api = Api::with_executor("token", executor.clone());
+
+        let res = api.search("notes", Some(SearchFilter::Page)).await.unwrap();
+        assert_eq!(res.result.results.len(), 1);
+        let pagination = res.pagination.unwrap();
+        let res = api.list::<AnyObject, _>(&pagination).await.unwrap();
+        assert_eq!(res.result.start_index, 1);
+
+        // the cursor is sent in the body, along with the query & filter
+        let filter = json!({ "property": "object", "value": "page" });
+        assert_eq!(
+            executor.bodies(),
+            [
+                json!({ "query": "notes", "filter": filter }),
+                json!({ "query": "notes", "filter": filter, "start_cursor": "c2" }),
+            ]
+        );
+        assert_eq!(executor.requests(), ["POST search", "POST search"]);
+    }
+
+    #[tokio::test]
+    async fn create_comment() {
+        let comment = json!({
+            "object": "comment",
+            "id": "c1",
+            "parent": { "type": "page_id", "page_id": "p1" },
+            "created_time": "2024-08-01T00:00:00.000Z",
+            "created_by": user_json("u1"),
+            "last_edited_time": "2024-08-01T00:00:00.000Z",
+            "discussion_id": "dis1",
+            "rich_text": [],
+        });
+        let executor = RecordingExecutor::default().route("POST comments", comment);
+        let api = Api::with_executor("token", executor.clone());
+
+        let res = api
+            .create_comment(
+                CommentTarget::Page("p1".to_owned()),
+                vec![RichText::text("LGTM")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.discussion_id, "dis1");
+        api.create_comment(CommentTarget::Discussion("dis1".to_owned()), vec![])
+            .await
+            .unwrap();
+
+        let bodies = executor.bodies();
+        assert_eq!(bodies[0]["parent"], json!({ "page_id": "p1" }));
+        assert_eq!(bodies[0]["rich_text"][0]["text"]["content"], "LGTM");
+        assert_eq!(
+            bodies[1],
+            json!({ "discussion_id": "dis1", "rich_text": [] })
+        );
+    }
+
+    #[tokio::test]
+    async fn append_block_children() {
+        let executor = RecordingExecutor::default().route(
+            "PATCH blocks/p1/children",
+            list_json(vec![block_json("b1", "p1", "paragraph")], None),
+        );
+        let api = Api::with_executor("token", executor.clone());
+
+        let paragraph = json!({
+            "type": "paragraph",
+            "paragraph": { "rich_text": [{ "text": { "content": "hi" } }] },
+        });
+        let blocks = api
+            .append_block_children("p1", vec![paragraph.clone()])
+            .await
+            .unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].obj.id, "b1");
+        assert_eq!(executor.bodies(), [json!({ "children": [paragraph] })]);
+    }
+
+    #[tokio::test]
+    async fn update_page() {
+        let executor = RecordingExecutor::default().route("PATCH pages/p1", page_json("p1", "d1"));
+        let api = Api::with_executor("token", executor.clone());
+
+        let page = api
+            .update_page(
+                "p1",
+                PagePatch::new().property("Processed", json!({ "checkbox": true })),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.id(), "p1");
+        api.update_page("p1", PagePatch::new().archived(true))
+            .await
+            .unwrap();
+
+        // only the fields set are sent
+        assert_eq!(
+            executor.bodies(),
+            [
+                json!({ "properties": { "Processed": { "checkbox": true } } }),
+                json!({ "archived": true }),
+            ]
+        );
+    }
+
+    #[test]
+    fn error_source() {
+        use std::error::Error;
+
+        let e: NotionError = reqwest::Client::new()
+            .get("not a url")
+            .build()
+            .unwrap_err()
+            .into();
+        let source = e.source().unwrap();
+        assert!(source.downcast_ref::<RequestError>().is_some());
+        assert!(source.source().unwrap().is::<reqwest::Error>());
+
+        let e = NotionError::invalid_response("oops");
+        assert!(e.source().unwrap().source().is_none());
+        assert!(NotionError::BufferFull.source().is_none());
+    }
+
+    #[tokio::test]
+    async fn me() {
+        let executor = RecordingExecutor::default().route("GET users/me", user_json("bot"));
+        let api = Api::with_executor("token", executor);
+        assert_eq!(api.me().await.unwrap().id(), "bot");
+
+        let executor = RecordingExecutor::default().route_status(
+            "GET users/me",
+            401,
+            json!({ "object": "error", "status": 401, "code": "unauthorized" }),
+        );
+        let api = Api::with_executor("token", executor);
+        let Err(NotionError::RequestFailed(RequestError::NotionApiError { status, code, .. })) =
+            api.me().await
+        else {
+            panic!("unexpected success");
+        };
+        assert_eq!((status, code), (401, ApiErrorCode::Unauthorized));
+
+        // not an error response of the API
+        let executor =
+            RecordingExecutor::default().route_status("GET users/me", 502, json!("bad gateway"));
+        let api = Api::with_executor("token", executor);
+        let Err(NotionError::RequestFailed(RequestError::InvalidResponse(msg))) = api.me().await
+        else {
+            panic!("unexpected success");
+        };
+        assert!(msg.contains("502") && msg.contains("bad gateway"), "{msg}");
+    }
+
+    #[tokio::test]
+    async fn check_status_code() {
+        let api_error = |body: &str| {
+            let res = super::check_status_code(response(404, body).into());
+            async move {
+                match res.await {
+                    Err(NotionError::RequestFailed(RequestError::NotionApiError {
+                        status,
+                        code,
+                        message,
+                    })) => (status, code, message),
+                    x => panic!("unexpected {x:?}"),
+                }
+            }
+        };
+        assert_eq!(
+            api_error(r#"{"object": "error", "code": "object_not_found", "message": "gone"}"#)
+                .await,
+            (404, ApiErrorCode::ObjectNotFound, "gone".to_owned())
+        );
+        // a code added to the API later
+        assert_eq!(
+            api_error(r#"{"object": "error", "code": "new_code"}"#).await,
+            (
+                404,
+                ApiErrorCode::Other("new_code".to_owned()),
+                String::new()
+            )
+        );
+
+        // the raw body, e.g. from a proxy
+        let res = super::check_status_code(response(502, "<html>Bad Gateway</html>").into()).await;
+        let Err(NotionError::RequestFailed(RequestError::InvalidResponse(msg))) = res else {
+            panic!("unexpected {res:?}");
+        };
+        assert!(
+            msg.contains("502") && msg.contains("<html>Bad Gateway</html>"),
+            "{msg}"
+        );
+
+        let res = super::check_status_code(response(200, "{}").into()).await;
+        assert_eq!(res.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn list_users() {
+        let executor = RecordingExecutor::default()
+            .route("GET users", list_json(vec![user_json("u1")], Some("c2")))
+            .route(
+                "GET users?start_cursor=c2",
+                list_json(vec![user_json("u2")], None),
+            );
+        let api = Api::with_executor("token", executor.clone());
+
+        let res = api.list_users().await.unwrap();
+        assert_eq!(res.result.results[0].id(), "u1");
+        let res = api.list::<User, _>(&res.pagination.unwrap()).await.unwrap();
+        assert_eq!(res.result.results[0].id(), "u2");
+        assert!(res.pagination.is_none());
+    }
+
+    #[tokio::test]
+    async fn query_database() {
+        let executor = RecordingExecutor::default().route(
+            "POST databases/d1/query",
+            list_json(vec![page_json("p1", "d1")], Some("c2")),
+        );
+        let api = Api::with_executor("token", executor.clone());
+
+        let filter = json!({ "property": "Done", "checkbox": { "equals": false } });
+        let body = QueryBody {
+            filter: Some(filter.clone()),
+            sorts: vec![
+                QuerySort::Property {
+                    property: "Due".to_owned(),
+                    direction: SortDirection::Ascending,
+                },
+                QuerySort::Timestamp {
+                    timestamp: "created_time".to_owned(),
+                    direction: SortDirection::Descending,
+                },
+            ],
+        };
+        let res = api.query_database("d1", body).await.unwrap();
+        assert_eq!(res.result.results.len(), 1);
+        let pagination = res.pagination.unwrap();
+        api.list::<AnyObject, _>(&pagination).await.unwrap();
+
+        // the cursor is sent in the body, along with the filter & sorts
+        let sorts = json!([
+            { "property": "Due", "direction": "ascending" },
+            { "timestamp": "created_time", "direction": "descending" },
+        ]);
+        assert_eq!(
+            executor.bodies(),
+            [
+                json!({ "filter": filter, "sorts": sorts }),
+                json!({ "filter": filter, "sorts": sorts, "start_cursor": "c2" }),
+            ]
+        );
+        assert_eq!(
+            executor.requests(),
+            ["POST databases/d1/query", "POST databases/d1/query"]
+        );
+
+        // without a filter or sorts
+        api.query_database("d1", QueryBody::default())
+            .await
+            .unwrap();
+        assert_eq!(executor.bodies().last(), Some(&json!({})));
+    }
+
+    #[test]
+    fn with_version() {
+        let api = Api::new("token").with_version("2025-09-03").unwrap();
+        assert_eq!(api.version(), "2025-09-03");
+
+        assert!(Api::new("token").with_version("2025-09-03\n").is_err());
+        assert!(Api::new("token").with_version("2025年").is_err());
+    }
+
+    // records the headers of the requests
+    #[derive(Clone, Default)]
+    struct HeaderExecutor(Arc<Mutex<Vec<header::HeaderMap>>>);
+
+    impl HttpExecutor for HeaderExecutor {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+            self.0.lock().unwrap().push(request.headers().clone());
+            Box::pin(async { Ok(response(200, page_json("p1", "workspace")).into()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn auth_header() {
+        let executor = HeaderExecutor::default();
+        let api = Api::with_executor("secret", executor.clone());
+        api.get_object::<crate::Page>("p1").await.unwrap();
+
+        let headers = executor.0.lock().unwrap()[0].clone();
+        let auth = &headers[header::AUTHORIZATION];
+        assert_eq!(auth, "Bearer secret");
+        assert!(auth.is_sensitive());
+        assert_eq!(headers["Notion-Version"], api.version());
     }
 }
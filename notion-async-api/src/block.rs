@@ -1,14 +1,19 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
+use std::str::FromStr;
 
+use chrono::{DateTime, Utc};
 use monostate::MustBe;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::serde_as;
+use thiserror::Error;
 
 use crate::{
-    misc::Unquotes,
+    fetcher::AnyObject,
+    misc::{NotionFile, Unquotes},
     object::{Object, ObjectCommon},
+    rich_text::{to_markdown, to_markdown_resolved, to_plain_text, MentionResolver, RichText},
 };
 
 /// Refer to:
@@ -76,6 +81,9 @@ pub enum BlockType {
     Toggle,
     Video,
     Unsupported,
+    /// A block type unknown to this crate, e.g. one newly added by Notion.
+    #[serde(untagged)]
+    Unknown(String),
 }
 
 impl Display for BlockType {
@@ -85,6 +93,20 @@ impl Display for BlockType {
     }
 }
 
+impl FromStr for BlockType {
+    type Err = UnsupportBlockTypeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match serde_json::from_value(Value::String(s.to_owned())) {
+            Ok(BlockType::Unknown(_)) | Err(_) => Err(UnsupportBlockTypeError(s.to_owned())),
+            Ok(x) => Ok(x),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("UnsupportBlockTypeError({0})")]
+pub struct UnsupportBlockTypeError(String);
+
 /// Refer to: [Block type](https://developers.notion.com/reference/block)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -131,6 +153,273 @@ pub enum BlockTypeData {
     Toggle(BTreeMap<String, Value>),
     Video(BTreeMap<String, Value>),
     Unsupported(BTreeMap<String, Value>),
+    /// The data of a [`BlockType::Unknown`] block, keyed by the block type,
+    /// or of a known block whose data isn't in the expected shape, so that
+    /// such a block never fails the list of blocks it's in.
+    #[serde(untagged)]
+    Unknown(BTreeMap<String, Value>),
+}
+
+impl Block {
+    /// The plain text of the block, i.e. the concatenated `plain_text` of its
+    /// rich text (see [`Block::rich_text`]), followed by the caption of a
+    /// code block on a new line, or the title of a child page/database.
+    /// Empty if the block has no text, e.g. an image.
+    pub fn plain_text(&self) -> String {
+        match &self.type_data {
+            BlockTypeData::ChildPage { title } | BlockTypeData::ChildDatabase { title } => {
+                title.clone()
+            }
+            BlockTypeData::Code(_) => {
+                let mut text = to_plain_text(&self.rich_text().unwrap_or_default());
+                let caption = self
+                    .type_data
+                    .parse::<CaptionData>()
+                    .map(|x| to_plain_text(&x.caption))
+                    .unwrap_or_default();
+                if !caption.is_empty() {
+                    text.push('\n');
+                    text.push_str(&caption);
+                }
+                text
+            }
+            _ => self
+                .rich_text()
+                .map(|x| to_plain_text(&x))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The rich text content of a block with text, i.e. a paragraph,
+    /// heading, quote, callout, to-do, list item, toggle or code block.
+    /// `None` for the other blocks, e.g. images and child pages.
+    pub fn rich_text(&self) -> Option<Vec<RichText>> {
+        match &self.type_data {
+            BlockTypeData::Paragraph(_)
+            | BlockTypeData::Heading1(_)
+            | BlockTypeData::Heading2(_)
+            | BlockTypeData::Heading3(_)
+            | BlockTypeData::Quote(_)
+            | BlockTypeData::Callout(_)
+            | BlockTypeData::ToDo(_)
+            | BlockTypeData::BulletedListItem(_)
+            | BlockTypeData::NumberedListItem(_)
+            | BlockTypeData::Toggle(_)
+            | BlockTypeData::Code(_) => self.type_data.parse::<RichTextData>().map(|x| x.rich_text),
+            _ => None,
+        }
+    }
+
+    /// The title embedded in a child page/database block, which is available
+    /// before the page/database itself is fetched, but may be stale after it
+    /// is renamed (see [`SyncEvent::TitleMismatch`]).
+    ///
+    /// [`SyncEvent::TitleMismatch`]: crate::SyncEvent::TitleMismatch
+    pub fn child_title(&self) -> Option<&str> {
+        match &self.type_data {
+            BlockTypeData::ChildPage { title } | BlockTypeData::ChildDatabase { title } => {
+                Some(title)
+            }
+            _ => None,
+        }
+    }
+
+    /// The key to order the blocks under the same parent, i.e. the
+    /// `child_index`, with the ties (e.g. a stale block left in db after the
+    /// blocks are moved) broken by `created_time` then ID, so that the order
+    /// is deterministic.
+    pub fn sibling_order(&self) -> (usize, DateTime<Utc>, &str) {
+        (self.child_index, self.obj.created_time, &self.obj.id)
+    }
+
+    /// Rewrite the URL of a file/image/video/pdf block with `f`.
+    pub fn map_file_url(&mut self, f: &dyn Fn(&str) -> String) {
+        let data = match &mut self.type_data {
+            BlockTypeData::File(x)
+            | BlockTypeData::Image(x)
+            | BlockTypeData::Video(x)
+            | BlockTypeData::Pdf(x) => x,
+            _ => return,
+        };
+        for key in ["file", "external"] {
+            if let Some(Value::String(url)) = data.get_mut(key).and_then(|x| x.get_mut("url")) {
+                *url = f(url);
+            }
+        }
+    }
+
+    /// The file of a file/image/video/pdf block, whose URL expires at its
+    /// `expiry_time` if it's hosted by Notion.
+    pub fn file(&self) -> Option<NotionFile> {
+        match self.block_type {
+            BlockType::File | BlockType::Image | BlockType::Video | BlockType::Pdf => {
+                self.type_data.parse()
+            }
+            _ => None,
+        }
+    }
+
+    /// The data of a bookmark or embed block.
+    pub fn as_bookmark(&self) -> Option<BookmarkData> {
+        match self.block_type {
+            BlockType::Bookmark | BlockType::Embed => self.type_data.parse(),
+            _ => None,
+        }
+    }
+
+    /// The data of a code block.
+    pub fn as_code(&self) -> Option<CodeBlock> {
+        match self.block_type {
+            BlockType::Code => self.type_data.parse(),
+            _ => None,
+        }
+    }
+
+    /// The data of a to-do block.
+    pub fn as_todo(&self) -> Option<ToDoData> {
+        match self.block_type {
+            BlockType::ToDo => self.type_data.parse(),
+            _ => None,
+        }
+    }
+}
+
+// The common part of the blocks with rich text content.
+#[derive(Deserialize)]
+struct RichTextData {
+    rich_text: Vec<RichText>,
+}
+
+// The caption of code blocks.
+#[derive(Deserialize)]
+struct CaptionData {
+    #[serde(default)]
+    caption: Vec<RichText>,
+}
+
+/// The data of bookmark & embed blocks.
+///
+/// Refer to: [Bookmark](https://developers.notion.com/reference/block#bookmark)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookmarkData {
+    pub url: String,
+    #[serde(default)]
+    pub caption: Vec<RichText>,
+}
+
+impl BookmarkData {
+    /// The caption as plain text.
+    pub fn plain_caption(&self) -> String {
+        to_plain_text(&self.caption)
+    }
+
+    /// A markdown link to the url, with the caption as text (or the url if
+    /// there's no caption).
+    pub fn to_markdown(&self) -> String {
+        let caption = self.plain_caption();
+        if caption.is_empty() {
+            format!("<{}>", self.url)
+        } else {
+            format!("[{caption}]({})", self.url)
+        }
+    }
+}
+
+/// The data of code blocks.
+///
+/// Refer to: [Code](https://developers.notion.com/reference/block#code)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeBlock {
+    /// The language of the code, e.g. `rust`, `c++` or `plain text`.
+    pub language: String,
+    pub rich_text: Vec<RichText>,
+    #[serde(default)]
+    pub caption: Vec<RichText>,
+}
+
+impl CodeBlock {
+    /// The code as plain text.
+    pub fn plain_code(&self) -> String {
+        to_plain_text(&self.rich_text)
+    }
+
+    /// A markdown code fence, tagged with the language (except for plain
+    /// text). The fence is longer than any run of backticks in the code.
+    pub fn to_markdown(&self) -> String {
+        let code = self.plain_code();
+        let mut longest = 0;
+        let mut run = 0;
+        for c in code.chars() {
+            run = if c == '`' { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        let fence = "`".repeat(longest.max(2) + 1);
+        let language = match self.language.as_str() {
+            "plain text" => "",
+            x => x,
+        };
+        format!("{fence}{language}\n{code}\n{fence}")
+    }
+}
+
+/// The data of to-do blocks.
+///
+/// Refer to: [To do](https://developers.notion.com/reference/block#to-do)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToDoData {
+    pub rich_text: Vec<RichText>,
+    #[serde(default)]
+    pub checked: bool,
+}
+
+impl BlockTypeData {
+    /// Deserialize the raw type data into `T`.
+    fn parse<T: DeserializeOwned>(&self) -> Option<T> {
+        let data = self.data()?.clone().into_iter().collect();
+        serde_json::from_value(Value::Object(data)).ok()
+    }
+
+    /// The raw type data, `None` for child pages/databases and unknown
+    /// blocks.
+    fn data(&self) -> Option<&BTreeMap<String, Value>> {
+        let data = match self {
+            BlockTypeData::ChildPage { .. }
+            | BlockTypeData::ChildDatabase { .. }
+            | BlockTypeData::Unknown(_) => return None,
+            BlockTypeData::Bookmark(x)
+            | BlockTypeData::Breadcrumb(x)
+            | BlockTypeData::BulletedListItem(x)
+            | BlockTypeData::Callout(x)
+            | BlockTypeData::Code(x)
+            | BlockTypeData::Column(x)
+            | BlockTypeData::ColumnList(x)
+            | BlockTypeData::Divider(x)
+            | BlockTypeData::Embed(x)
+            | BlockTypeData::Equation(x)
+            | BlockTypeData::File(x)
+            | BlockTypeData::Heading1(x)
+            | BlockTypeData::Heading2(x)
+            | BlockTypeData::Heading3(x)
+            | BlockTypeData::Image(x)
+            | BlockTypeData::LinkPreview(x)
+            | BlockTypeData::LinkToPreview(x)
+            | BlockTypeData::Mention(x)
+            | BlockTypeData::NumberedListItem(x)
+            | BlockTypeData::Paragraph(x)
+            | BlockTypeData::Pdf(x)
+            | BlockTypeData::Quote(x)
+            | BlockTypeData::SyncedBlock(x)
+            | BlockTypeData::Table(x)
+            | BlockTypeData::TableRow(x)
+            | BlockTypeData::TableOfContents(x)
+            | BlockTypeData::Template(x)
+            | BlockTypeData::ToDo(x)
+            | BlockTypeData::Toggle(x)
+            | BlockTypeData::Video(x)
+            | BlockTypeData::Unsupported(x) => x,
+        };
+        Some(data)
+    }
 }
 
 impl Object for Block {
@@ -142,3 +431,553 @@ impl Object for Block {
         crate::object::ObjectType::Block
     }
 }
+
+/// The blocks of a fetch (see [`Fetcher`]) as trees, which are emitted flat,
+/// each with its parent & `child_index`.
+///
+/// The roots are the parents which aren't blocks in the tree, i.e. usually
+/// pages (or databases for the rows), or the parents of the top level blocks
+/// if only a subtree of blocks is fetched.
+///
+/// [`Fetcher`]: crate::Fetcher
+#[derive(Debug, Clone, Default)]
+pub struct BlockTree {
+    // the blocks by parent ID, ordered by `Block::sibling_order`
+    children: HashMap<String, Vec<Block>>,
+    // in the order of their first children in the input
+    roots: Vec<String>,
+}
+
+impl BlockTree {
+    /// Build the trees of the blocks in `objects`, in any order. The other
+    /// objects are skipped.
+    pub fn from_objects(objects: Vec<AnyObject>) -> Self {
+        Self::from_blocks(objects.into_iter().filter_map(|x| match x {
+            AnyObject::Block(x) => Some(x),
+            _ => None,
+        }))
+    }
+
+    /// Build the trees of `blocks`, in any order.
+    pub fn from_blocks(blocks: impl IntoIterator<Item = Block>) -> Self {
+        let blocks: Vec<Block> = blocks.into_iter().collect();
+        let ids: HashSet<&str> = blocks.iter().map(|x| x.obj.id.as_str()).collect();
+        let mut roots = Vec::new();
+        for block in &blocks {
+            let parent = block.obj.parent.id();
+            if !ids.contains(parent) && !roots.iter().any(|x| x == parent) {
+                roots.push(parent.to_owned());
+            }
+        }
+
+        let mut children: HashMap<String, Vec<Block>> = HashMap::new();
+        for block in blocks {
+            children
+                .entry(block.obj.parent.id().to_owned())
+                .or_default()
+                .push(block);
+        }
+        for x in children.values_mut() {
+            x.sort_by(|a, b| a.sibling_order().cmp(&b.sibling_order()));
+        }
+        Self { children, roots }
+    }
+
+    /// The IDs of the roots, e.g. pages.
+    pub fn roots(&self) -> &[String] {
+        &self.roots
+    }
+
+    /// The children of a page/block in order, empty if it has none in the
+    /// tree.
+    pub fn children(&self, parent_id: &str) -> &[Block] {
+        self.children.get(parent_id).map_or(&[], |x| x.as_slice())
+    }
+
+    /// Render the trees as markdown, one root after another, see
+    /// [`blocks_to_markdown`].
+    pub fn to_markdown(&self) -> String {
+        self.write_markdown(None)
+    }
+
+    /// Like [`BlockTree::to_markdown`], but the mentions are rendered as
+    /// their labels resolved by `resolver`, e.g. the titles of the mentioned
+    /// pages, see [`RichText::mention_label`].
+    pub fn to_markdown_resolved(&self, resolver: &dyn MentionResolver) -> String {
+        self.write_markdown(Some(resolver))
+    }
+
+    fn write_markdown(&self, resolver: Option<&dyn MentionResolver>) -> String {
+        let mut writer = MarkdownWriter {
+            tree: self,
+            resolver,
+            out: String::new(),
+            last_is_list_item: false,
+        };
+        for root in &self.roots {
+            writer.write_blocks(self.children(root), "");
+        }
+        writer.out
+    }
+}
+
+/// Render the blocks as markdown, e.g. the blocks of a page as emitted by
+/// [`Fetcher`], in any order. The blocks are grouped into trees by
+/// [`BlockTree`], i.e. the blocks whose parents aren't in `blocks` are the
+/// top level ones.
+///
+/// Paragraphs, headings, lists, to-dos, quotes (callouts are rendered as
+/// quotes), code and dividers are rendered, the children of list items &
+/// to-dos are nested under them. The other blocks are rendered as HTML
+/// comments, e.g. `<!-- image -->`, followed by their children.
+///
+/// [`Fetcher`]: crate::Fetcher
+pub fn blocks_to_markdown(blocks: &[Block]) -> String {
+    BlockTree::from_blocks(blocks.iter().cloned()).to_markdown()
+}
+
+struct MarkdownWriter<'a> {
+    tree: &'a BlockTree,
+    resolver: Option<&'a dyn MentionResolver>,
+    out: String,
+    // whether the last written block is a list item, which is followed by
+    // the next list item without a blank line
+    last_is_list_item: bool,
+}
+
+impl MarkdownWriter<'_> {
+    fn write_blocks(&mut self, blocks: &[Block], indent: &str) {
+        let mut number = 0;
+        for block in blocks {
+            number = match block.block_type {
+                BlockType::NumberedListItem => number + 1,
+                _ => 0,
+            };
+            let is_list_item = matches!(
+                block.block_type,
+                BlockType::BulletedListItem | BlockType::NumberedListItem | BlockType::ToDo
+            );
+            if !self.out.is_empty() {
+                self.out
+                    .push_str(if is_list_item && self.last_is_list_item {
+                        "\n"
+                    } else {
+                        "\n\n"
+                    });
+            }
+            for (i, line) in render_markdown(block, number, self.resolver)
+                .lines()
+                .enumerate()
+            {
+                if i > 0 {
+                    self.out.push('\n');
+                }
+                if !line.is_empty() {
+                    self.out.push_str(indent);
+                    self.out.push_str(line);
+                }
+            }
+            self.last_is_list_item = is_list_item;
+
+            let children = self.tree.children(&block.obj.id);
+            if !children.is_empty() {
+                let indent = if is_list_item {
+                    format!("{indent}    ")
+                } else {
+                    indent.to_owned()
+                };
+                self.write_blocks(children, &indent);
+            }
+        }
+    }
+}
+
+/// Render a block itself (without its children) as markdown, `number` is
+/// the number of a numbered list item.
+fn render_markdown(block: &Block, number: usize, resolver: Option<&dyn MentionResolver>) -> String {
+    let rich_text = block.rich_text().unwrap_or_default();
+    let text = match resolver {
+        Some(resolver) => to_markdown_resolved(&rich_text, resolver),
+        None => to_markdown(&rich_text),
+    };
+    let quote = |text: &str| {
+        text.split('\n')
+            .map(|x| format!("> {x}").trim_end().to_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    match block.block_type {
+        BlockType::Paragraph | BlockType::Toggle => text,
+        BlockType::Heading1 => format!("# {text}"),
+        BlockType::Heading2 => format!("## {text}"),
+        BlockType::Heading3 => format!("### {text}"),
+        BlockType::BulletedListItem => format!("- {text}"),
+        BlockType::NumberedListItem => format!("{number}. {text}"),
+        BlockType::ToDo => {
+            let checked = block.as_todo().is_some_and(|x| x.checked);
+            format!("- [{}] {text}", if checked { "x" } else { " " })
+        }
+        BlockType::Quote | BlockType::Callout => quote(&text),
+        BlockType::Code => block.as_code().map(|x| x.to_markdown()).unwrap_or_default(),
+        BlockType::Divider => "---".to_owned(),
+        _ => {
+            let title = block.plain_text().replace("--", "- -");
+            if title.is_empty() {
+                format!("<!-- {} -->", block.block_type)
+            } else {
+                format!("<!-- {}: {title} -->", block.block_type)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::{
+        object::ObjectList,
+        tests::{block_json, list_json, page_json, rich_text_json as text},
+        AnyObject, NotionFile,
+    };
+
+    use super::{Block, BlockTree, BlockType, BlockTypeData};
+
+    #[test]
+    fn bookmark_caption() {
+        let mut js = block_json("b1", "p1", "bookmark");
+        js["bookmark"] = json!({
+            "caption": [text("Notion API")],
+            "url": "https://developers.notion.com/",
+        });
+        let block: Block = serde_json::from_value(js).unwrap();
+        let bookmark = block.as_bookmark().unwrap();
+        assert_eq!(bookmark.url, "https://developers.notion.com/");
+        assert_eq!(bookmark.plain_caption(), "Notion API");
+        assert_eq!(
+            bookmark.to_markdown(),
+            "[Notion API](https://developers.notion.com/)"
+        );
+
+        let block: Block = serde_json::from_value(block_json("b2", "p1", "paragraph")).unwrap();
+        assert!(block.as_bookmark().is_none());
+    }
+
+    #[test]
+    fn file() {
+        let mut js = block_json("i1", "p1", "image");
+        js["image"] = json!({
+            "caption": [],
+            "type": "file",
+            "file": {
+                "url": "https://files.notion.so/i1/photo.png?X-Amz-Expires=3600",
+                "expiry_time": "2024-08-01T01:00:00.000Z",
+            },
+        });
+        let block: Block = serde_json::from_value(js).unwrap();
+        let Some(NotionFile::File { file }) = block.file() else {
+            panic!("not a notion file: {:?}", block.file());
+        };
+        assert_eq!(file.expiry_time.to_rfc3339(), "2024-08-01T01:00:00+00:00");
+
+        let mut js = block_json("v1", "p1", "video");
+        js["video"] = json!({
+            "caption": [],
+            "type": "external",
+            "external": { "url": "https://youtu.be/xyz" },
+        });
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert_eq!(block.file().unwrap().url(), "https://youtu.be/xyz");
+
+        let block: Block = serde_json::from_value(block_json("b1", "p1", "paragraph")).unwrap();
+        assert!(block.file().is_none());
+    }
+
+    #[test]
+    fn rich_text() {
+        let mut js = block_json("b1", "p1", "heading_1");
+        let mut link = text("Notion");
+        link["text"]["link"] = json!({ "url": "https://notion.so" });
+        link["annotations"]["bold"] = json!(true);
+        link["href"] = json!("https://notion.so");
+        js["heading_1"]["rich_text"] = json!([link]);
+        let block: Block = serde_json::from_value(js).unwrap();
+        let rich_text = block.rich_text().unwrap();
+        assert_eq!(rich_text.len(), 1);
+        assert_eq!(rich_text[0].plain_text, "Notion");
+        assert_eq!(rich_text[0].href.as_deref(), Some("https://notion.so"));
+        assert!(rich_text[0].annotations.bold);
+
+        let block: Block = serde_json::from_value(block_json("p2", "p1", "child_page")).unwrap();
+        assert!(block.rich_text().is_none());
+
+        let mut js = block_json("i1", "p1", "image");
+        js["image"]["caption"] = json!([]);
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert!(block.rich_text().is_none());
+
+        let mut js = block_json("t1", "p1", "to_do");
+        js["to_do"] = json!({ "rich_text": [], "checked": true });
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert_eq!(block.rich_text().map(|x| x.len()), Some(0));
+    }
+
+    #[test]
+    fn plain_text() {
+        let mut js = block_json("b1", "p1", "quote");
+        js["quote"]["rich_text"] = json!([text("Hello, "), text("world")]);
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert_eq!(block.plain_text(), "Hello, world");
+
+        let mut js = block_json("c1", "p1", "code");
+        js["code"] = json!({
+            "rich_text": [text("fn main() {}")],
+            "caption": [text("main.rs")],
+            "language": "rust",
+        });
+        let block: Block = serde_json::from_value(js.clone()).unwrap();
+        assert_eq!(block.plain_text(), "fn main() {}\nmain.rs");
+        js["code"]["caption"] = json!([]);
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert_eq!(block.plain_text(), "fn main() {}");
+
+        let mut js = block_json("i1", "p1", "image");
+        js["image"]["caption"] = json!([text("A cat")]);
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert_eq!(block.plain_text(), "");
+    }
+
+    #[test]
+    fn code() {
+        let mut js = block_json("c1", "p1", "code");
+        js["code"] = json!({
+            "rich_text": [text("let s = \"```\";")],
+            "caption": [],
+            "language": "rust",
+        });
+        let block: Block = serde_json::from_value(js.clone()).unwrap();
+        let code = block.as_code().unwrap();
+        assert_eq!(code.language, "rust");
+        assert_eq!(code.plain_code(), "let s = \"```\";");
+        assert_eq!(code.to_markdown(), "````rust\nlet s = \"```\";\n````");
+
+        js["code"]["language"] = json!("plain text");
+        js["code"]["rich_text"] = json!([text("hello")]);
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert_eq!(block.as_code().unwrap().to_markdown(), "```\nhello\n```");
+
+        let block: Block = serde_json::from_value(block_json("b1", "p1", "paragraph")).unwrap();
+        assert!(block.as_code().is_none());
+    }
+
+    #[test]
+    fn todo() {
+        let mut js = block_json("t1", "p1", "to_do");
+        js["to_do"] = json!({ "rich_text": [text("Ship it")], "checked": true });
+        let block: Block = serde_json::from_value(js.clone()).unwrap();
+        let todo = block.as_todo().unwrap();
+        assert!(todo.checked);
+        assert_eq!(todo.rich_text[0].plain_text, "Ship it");
+
+        js["to_do"]["checked"] = json!(false);
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert!(!block.as_todo().unwrap().checked);
+
+        let block: Block = serde_json::from_value(block_json("b1", "p1", "paragraph")).unwrap();
+        assert!(block.as_todo().is_none());
+    }
+
+    #[test]
+    fn block_tree() {
+        let block = |id: &str, parent: &str, child_index: usize| {
+            let mut js = block_json(id, parent, "paragraph");
+            js["child_index"] = json!(child_index);
+            AnyObject::Block(serde_json::from_value(js).unwrap())
+        };
+        let page = page_json("p1", "w1");
+        let tree = BlockTree::from_objects(vec![
+            block("b2", "p1", 1),
+            AnyObject::Page(serde_json::from_value(page).unwrap()),
+            block("c1", "b1", 0),
+            block("b1", "p1", 0),
+            // a subtree whose parent isn't fetched
+            block("x1", "b9", 0),
+        ]);
+
+        assert_eq!(tree.roots(), ["p1", "b9"]);
+        let ids = |parent: &str| -> Vec<&str> {
+            tree.children(parent)
+                .iter()
+                .map(|x| x.obj.id.as_str())
+                .collect()
+        };
+        assert_eq!(ids("p1"), ["b1", "b2"]);
+        assert_eq!(ids("b1"), ["c1"]);
+        assert_eq!(ids("b9"), ["x1"]);
+        assert!(ids("c1").is_empty());
+    }
+
+    #[test]
+    fn markdown() {
+        let block = |id: &str, parent: &str, child_index: usize, block_type: &str, data: Value| {
+            let mut js = block_json(id, parent, block_type);
+            js["child_index"] = json!(child_index);
+            if !data.is_null() {
+                js[block_type] = data;
+            }
+            serde_json::from_value::<Block>(js).unwrap()
+        };
+        let rich_text = |s: &str| json!({ "rich_text": [text(s)] });
+        let mut bold = text("bold");
+        bold["annotations"]["bold"] = json!(true);
+        let mut link = text("link ");
+        link["href"] = json!("https://notion.so");
+
+        // in any order
+        let blocks = [
+            block("i1", "b1", 0, "bulleted_list_item", rich_text("nested")),
+            block("b1", "p1", 1, "bulleted_list_item", rich_text("one")),
+            block("h1", "p1", 0, "heading_1", rich_text("Title")),
+            block("b2", "p1", 2, "bulleted_list_item", rich_text("two")),
+            block(
+                "p2",
+                "p1",
+                3,
+                "paragraph",
+                json!({ "rich_text": [link, text("and "), bold] }),
+            ),
+            block("n1", "p1", 4, "numbered_list_item", rich_text("first")),
+            block("n2", "p1", 5, "numbered_list_item", rich_text("second")),
+            block(
+                "t1",
+                "p1",
+                6,
+                "to_do",
+                json!({ "rich_text": [text("done")], "checked": true }),
+            ),
+            block("t2", "p1", 7, "to_do", rich_text("todo")),
+            block("q1", "p1", 8, "quote", rich_text("a\nb")),
+            block(
+                "c1",
+                "p1",
+                9,
+                "code",
+                json!({ "rich_text": [text("x = 1")], "language": "python" }),
+            ),
+            block("d1", "p1", 10, "divider", json!({})),
+            block("m1", "p1", 11, "image", Value::Null),
+        ];
+
+        assert_eq!(
+            super::blocks_to_markdown(&blocks),
+            "# Title\n\n\
+             - one\n    \
+             - nested\n\
+             - two\n\n\
+             [link](https://notion.so) and **bold**\n\n\
+             1. first\n\
+             2. second\n\
+             - [x] done\n\
+             - [ ] todo\n\n\
+             > a\n> b\n\n\
+             ```python\nx = 1\n```\n\n\
+             ---\n\n\
+             <!-- image -->"
+        );
+    }
+
+    #[test]
+    fn child_title() {
+        let mut js = block_json("p2", "p1", "child_page");
+        js["child_page"]["title"] = json!("Meeting notes");
+        let block: Block = serde_json::from_value(js).unwrap();
+        assert_eq!(block.child_title(), Some("Meeting notes"));
+        assert_eq!(block.plain_text(), "Meeting notes");
+
+        let block: Block =
+            serde_json::from_value(block_json("d1", "p1", "child_database")).unwrap();
+        assert_eq!(block.child_title(), Some("d1"));
+
+        let block: Block = serde_json::from_value(block_json("b1", "p1", "paragraph")).unwrap();
+        assert_eq!(block.child_title(), None);
+    }
+
+    #[test]
+    fn sibling_order() {
+        let block = |id: &str, child_index: usize, created_time: &str| {
+            let mut js = block_json(id, "p1", "paragraph");
+            js["child_index"] = json!(child_index);
+            js["created_time"] = json!(created_time);
+            serde_json::from_value::<Block>(js).unwrap()
+        };
+        let mut blocks = [
+            block("b4", 1, "2024-08-01T00:00:00.000Z"),
+            block("b3", 0, "2024-08-02T00:00:00.000Z"),
+            block("b2", 0, "2024-08-01T00:00:00.000Z"),
+            block("b1", 0, "2024-08-02T00:00:00.000Z"),
+        ];
+        blocks.sort_by(|a, b| a.sibling_order().cmp(&b.sibling_order()));
+        let ids: Vec<&str> = blocks.iter().map(|x| x.obj.id.as_str()).collect();
+        assert_eq!(ids, ["b2", "b1", "b3", "b4"]);
+    }
+
+    #[test]
+    fn unknown_block_type() {
+        let mut js = block_json("b1", "p1", "paragraph");
+        js["type"] = json!("meeting_notes");
+        js["meeting_notes"] = json!({ "title": [] });
+        js.as_object_mut().unwrap().remove("paragraph");
+        let block: Block = serde_json::from_value(js.clone()).unwrap();
+        assert_eq!(
+            block.block_type,
+            BlockType::Unknown("meeting_notes".to_owned())
+        );
+        assert_eq!(block.block_type.to_string(), "meeting_notes");
+        assert_eq!(block.plain_text(), "");
+        assert_eq!(
+            serde_json::to_value(&block).unwrap()["meeting_notes"],
+            js["meeting_notes"]
+        );
+
+        assert!("meeting_notes".parse::<BlockType>().is_err());
+        assert_eq!(
+            "paragraph".parse::<BlockType>().unwrap(),
+            BlockType::Paragraph
+        );
+    }
+
+    #[test]
+    fn unknown_block_in_list() {
+        let mut unknown = block_json("b2", "p1", "paragraph");
+        unknown["type"] = json!("meeting_notes");
+        unknown["meeting_notes"] = json!(null);
+        // a known type with data of an unexpected shape
+        let mut malformed = block_json("b3", "p1", "child_page");
+        malformed["child_page"] = json!({});
+        let js = list_json(
+            vec![
+                block_json("b1", "p1", "paragraph"),
+                unknown,
+                malformed,
+                block_json("b4", "p1", "divider"),
+            ],
+            None,
+        );
+
+        let list: ObjectList<Block> = serde_json::from_value(js).unwrap();
+        let types: Vec<String> = list
+            .results
+            .iter()
+            .map(|x| x.block_type.to_string())
+            .collect();
+        assert_eq!(
+            types,
+            ["paragraph", "meeting_notes", "child_page", "divider"]
+        );
+        assert!(matches!(
+            list.results[2].type_data,
+            BlockTypeData::Unknown(_)
+        ));
+        assert_eq!(list.results[2].child_title(), None);
+    }
+}
@@ -20,7 +20,31 @@ pub struct Comment {
 
     pub discussion_id: String,
     pub rich_text: Vec<RichText>,
-    // pub rich_text: Vec<Value>,
+}
+
+impl Comment {
+    /// A comment of the discussion `discussion_id` on the page or block
+    /// `parent`, e.g. one stored by a sync.
+    pub fn new(
+        id: impl Into<String>,
+        parent: Parent,
+        created_time: DateTime<Utc>,
+        created_by: User,
+        last_edited_time: DateTime<Utc>,
+        discussion_id: impl Into<String>,
+        rich_text: Vec<RichText>,
+    ) -> Self {
+        Self {
+            object: Default::default(),
+            id: id.into(),
+            parent,
+            created_time,
+            created_by,
+            last_edited_time,
+            discussion_id: discussion_id.into(),
+            rich_text,
+        }
+    }
 }
 
 impl Object for Comment {
@@ -0,0 +1,96 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use tokio::sync::Notify;
+
+/// A handle to pause & resume the fetches of a [`Fetcher`], see
+/// [`Fetcher::control`].
+///
+/// While paused, the requests in flight complete, but no new request is
+/// started, e.g. when the user backgrounds an interactive app, or to
+/// cooperate with an external rate budget.
+///
+/// [`Fetcher`]: crate::Fetcher
+/// [`Fetcher::control`]: crate::Fetcher::control
+#[derive(Clone, Default)]
+pub struct FetchControl {
+    inner: Arc<ControlState>,
+}
+
+#[derive(Default)]
+struct ControlState {
+    paused: AtomicBool,
+    resumed: Notify,
+}
+
+impl FetchControl {
+    pub fn pause(&self) {
+        self.inner.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.inner.paused.store(false, Ordering::SeqCst);
+        self.inner.resumed.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the fetches are resumed, return immediately if they aren't
+    /// paused.
+    pub(crate) async fn wait_resumed(&self) {
+        loop {
+            // Created before the check, so that a `resume` in between isn't
+            // missed.
+            let resumed = self.inner.resumed.notified();
+            if !self.is_paused() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+}
+
+/// A handle to cancel a fetch, see [`Fetcher::fetch_cancellable`].
+///
+/// Once cancelled, no new request is started, the requests in flight are
+/// aborted, and the stream of the fetch ends, e.g. to stop a long sync
+/// cleanly. Unlike dropping the stream, the events already fetched can still
+/// be read.
+///
+/// [`Fetcher::fetch_cancellable`]: crate::Fetcher::fetch_cancellable
+#[derive(Clone, Default)]
+pub struct CancelHandle {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the fetch is cancelled.
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::misc::{Icon, NotionFile, Property};
 use crate::object::{Object, ObjectCommon};
-use crate::rich_text::RichText;
+use crate::rich_text::{to_plain_text, RichText};
 
 /// Refer to:
 /// - [database](https://developers.notion.com/reference/database)
@@ -26,6 +26,31 @@ pub struct Database {
     pub is_inline: bool,
     pub title: Vec<RichText>,
     pub description: Vec<RichText>,
+
+    /// The data sources of the database, which are queried for the rows
+    /// instead of the database itself if present. Only returned by the newer
+    /// API versions.
+    #[serde(default)]
+    pub data_sources: Vec<DataSourceRef>,
+}
+
+/// Refer to: [Data source](https://developers.notion.com/reference/data-source)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataSourceRef {
+    pub id: String,
+    pub name: String,
+}
+
+impl Database {
+    /// The title as plain text.
+    pub fn plain_title(&self) -> String {
+        to_plain_text(&self.title)
+    }
+
+    /// The description as plain text, empty if there's no description.
+    pub fn plain_description(&self) -> String {
+        to_plain_text(&self.description)
+    }
 }
 
 impl Object for Database {
@@ -37,3 +62,52 @@ impl Object for Database {
         crate::object::ObjectType::Database
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::tests::{database_json, rich_text_json as text};
+
+    use super::{DataSourceRef, Database};
+
+    #[test]
+    fn plain_title_and_description() {
+        let mut js = database_json("d1", "p1");
+        js["title"] = json!([text("Tasks "), text("2024")]);
+        js["description"] = json!([text("What to do")]);
+        let database: Database = serde_json::from_value(js.clone()).unwrap();
+        assert_eq!(database.plain_title(), "Tasks 2024");
+        assert_eq!(database.plain_description(), "What to do");
+
+        js["description"] = json!([]);
+        let database: Database = serde_json::from_value(js).unwrap();
+        assert_eq!(database.plain_description(), "");
+    }
+
+    #[test]
+    fn data_sources() {
+        let mut js = database_json("d1", "p1");
+        let database: Database = serde_json::from_value(js.clone()).unwrap();
+        assert!(database.data_sources.is_empty());
+
+        js["data_sources"] = json!([
+            { "id": "ds1", "name": "Tasks" },
+            { "id": "ds2", "name": "Archive" },
+        ]);
+        let database: Database = serde_json::from_value(js).unwrap();
+        assert_eq!(
+            database.data_sources,
+            [
+                DataSourceRef {
+                    id: "ds1".to_owned(),
+                    name: "Tasks".to_owned(),
+                },
+                DataSourceRef {
+                    id: "ds2".to_owned(),
+                    name: "Archive".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -1,11 +1,14 @@
 use std::fmt::Display;
 
-use crate::api::RequestError;
+use crate::api::{ApiErrorCode, RequestError};
 
 #[derive(Debug)]
 pub enum NotionError {
     InvalidObject(String),
     RequestFailed(RequestError),
+    /// The result buffer of a fetch is full, see
+    /// [`OverflowPolicy::Error`](crate::OverflowPolicy::Error).
+    BufferFull,
 }
 
 impl NotionError {
@@ -28,6 +31,16 @@ impl NotionError {
     pub fn retry_after(secs: u64) -> Self {
         Self::RequestFailed(RequestError::RetryAfter(secs))
     }
+
+    /// The code of the error response of the API, e.g. to tell a missing
+    /// object ([`ApiErrorCode::ObjectNotFound`]) from a rejected token
+    /// ([`ApiErrorCode::Unauthorized`]). `None` for the other errors.
+    pub fn api_error_code(&self) -> Option<&ApiErrorCode> {
+        match self {
+            Self::RequestFailed(RequestError::NotionApiError { code, .. }) => Some(code),
+            _ => None,
+        }
+    }
 }
 
 impl Display for NotionError {
@@ -35,6 +48,7 @@ impl Display for NotionError {
         match self {
             NotionError::InvalidObject(s) => format!("invalid notion object: {s}").fmt(f),
             NotionError::RequestFailed(e) => e.fmt(f),
+            NotionError::BufferFull => "result buffer is full".fmt(f),
         }
     }
 }
@@ -60,4 +74,11 @@ impl serde::de::Error for NotionError {
     }
 }
 
-impl std::error::Error for NotionError {}
+impl std::error::Error for NotionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NotionError::RequestFailed(e) => Some(e),
+            NotionError::InvalidObject(_) | NotionError::BufferFull => None,
+        }
+    }
+}
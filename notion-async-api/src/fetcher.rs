@@ -1,33 +1,313 @@
-use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use async_rate_limiter::RateLimiter;
+use chrono::{DateTime, Utc};
 use futures::{
     channel::mpsc::{channel, Sender},
     future::BoxFuture,
-    FutureExt, SinkExt, Stream, StreamExt,
+    Future, FutureExt, SinkExt, Stream, StreamExt,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use reqwest::Url;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use tokio::{
+    spawn,
+    sync::{watch, Semaphore, SemaphorePermit},
 };
-use serde::{Deserialize, Serialize};
-use tokio::spawn;
 
+#[cfg(feature = "otel")]
+use crate::otel::TaskSpan;
 use crate::{
-    api::{PaginationInfo, PaginationResult},
+    api::{ApiErrorCode, DataSourceQuery, PaginationInfo, PaginationResult, MAX_PAGE_SIZE},
     block::Block,
     comment::Comment,
+    control::{CancelHandle, FetchControl},
     database::Database,
     error::NotionError,
-    object::{Object, ObjectList, ObjectType},
+    misc::{Icon, NotionFile},
+    object::{Object, ObjectKey, ObjectList, ObjectType, Parent},
     page::Page,
+    result_channel::{result_channel, OverflowPolicy, ResultSender, SendError},
+    rich_text::{to_plain_text, RichTextType},
     user::User,
     Api,
 };
 
+const RESULT_BUFFER_SIZE: usize = 10;
+const TASK_BUFFER_SIZE: usize = 10;
+
+// max length of the title part of a slug, in chars
+const SLUG_TITLE_LEN: usize = 50;
+// length of the ID suffix of a slug
+const SLUG_ID_LEN: usize = 8;
+
 #[derive(Clone)]
 pub struct Fetcher {
     api: Api,
     rate_limiter: RateLimiter,
+    options: FetcherOptions,
+    file_url_hook: Option<Arc<FileUrlHook>>,
+    descend_hook: Option<Arc<DescendHook>>,
+    // the children deeper than it aren't fetched
+    max_depth: Option<usize>,
+    // the max number of retries of a request responded with `Retry-After`,
+    // unlimited if None
+    max_retries: Option<usize>,
+    // the max wait before each retry, unlimited if None
+    max_backoff: Option<Duration>,
+    error_mode: ErrorMode,
+    // the max number of items per page of the lists
+    page_size: usize,
+    // the capacity of the results of a fetch, & of the queued child tasks
+    // of each task
+    result_buffer: usize,
+    task_buffer: usize,
+    control: FetchControl,
+    // bounds the requests in flight across all the fetches, unlimited if
+    // None
+    concurrency: Option<Arc<Semaphore>>,
+    // source of the retry jitter
+    rng: Arc<Mutex<StdRng>>,
+    // number of the tasks queued or running in the current fetch
+    pending: Arc<AtomicUsize>,
+    // the objects emitted in the current fetch, or in all the fetches if
+    // `dedup_across_fetches`
+    seen: Arc<Mutex<HashSet<ObjectKey>>>,
+    // the mentioned objects queued in the current fetch, or in all the
+    // fetches like `seen`
+    followed: Arc<Mutex<HashSet<ObjectKey>>>,
+    // the running counts of the current fetch
+    stats: Arc<watch::Sender<SyncStats>>,
+    // cancels the current fetch
+    cancel: CancelHandle,
+}
+
+type FileUrlHook = dyn Fn(&str) -> String + Send + Sync;
+type DescendHook = dyn Fn(&AnyObject) -> bool + Send + Sync;
+
+/// Options of [`Fetcher`].
+#[derive(Debug, Clone)]
+pub struct FetcherOptions {
+    /// What to do when the result buffer is full, defaults to
+    /// [`OverflowPolicy::Block`].
+    pub overflow: OverflowPolicy,
+
+    /// Whether to fetch the rows of databases, defaults to true.
+    pub descend_into_databases: bool,
+
+    /// Only emit the objects of these types, other objects are still
+    /// traversed (except comments, which aren't fetched at all if excluded).
+    /// Emit all objects if empty, which is the default.
+    pub object_types: Vec<ObjectType>,
+
+    /// Only fetch these properties (by property ID, e.g. `title`) of pages,
+    /// including the rows of databases, see
+    /// [`Api::get_page_with_properties`]. Fetch all properties if empty,
+    /// which is the default.
+    pub filter_properties: Vec<String>,
+
+    /// Emit a [`SyncEvent::Heartbeat`] at this interval while the fetch is
+    /// in progress, see [`Fetcher::fetch_events`]. Disabled by default.
+    pub heartbeat: Option<Duration>,
+
+    /// Max random delay added to each wait for `Retry-After`, so that the
+    /// concurrent requests don't retry all at once. Defaults to 500ms.
+    pub max_retry_jitter: Duration,
+
+    /// Seed of the retry jitter, which makes the retry timing deterministic,
+    /// e.g. in tests. Seeded from the OS if `None`, which is the default.
+    pub jitter_seed: Option<u64>,
+
+    /// Parse the elements of the lists (children of blocks, rows of
+    /// databases & comments) one by one, so that a malformed element is
+    /// skipped with a [`SyncEvent::InvalidElement`], rather than failing the
+    /// whole page of the list. Defaults to false.
+    pub lenient_lists: bool,
+
+    /// Also fetch the pages/databases mentioned in the rich text of the
+    /// blocks, with their children, as if they were child pages/databases
+    /// of the blocks. They may be outside the roots, e.g. a linked page,
+    /// which is otherwise missed. Defaults to false.
+    pub follow_mentions: bool,
+
+    /// Emit an object only once across all the fetches of the fetcher (and
+    /// its clones), rather than once per fetch, e.g. for the roots of one
+    /// sync, which may be nested or overlap. An object is fetched by
+    /// whichever fetch reaches it first. Defaults to false.
+    pub dedup_across_fetches: bool,
+}
+
+impl Default for FetcherOptions {
+    fn default() -> Self {
+        Self {
+            overflow: OverflowPolicy::default(),
+            descend_into_databases: true,
+            object_types: Vec::new(),
+            filter_properties: Vec::new(),
+            heartbeat: None,
+            max_retry_jitter: Duration::from_millis(500),
+            jitter_seed: None,
+            lenient_lists: false,
+            follow_mentions: false,
+            dedup_across_fetches: false,
+        }
+    }
+}
+
+impl FetcherOptions {
+    fn emits(&self, object_type: ObjectType) -> bool {
+        self.object_types.is_empty() || self.object_types.contains(&object_type)
+    }
+
+    fn filter_properties(&self) -> Vec<&str> {
+        self.filter_properties.iter().map(|x| x.as_str()).collect()
+    }
+}
+
+/// What a fetch does when a request fails, see [`Fetcher::with_error_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorMode {
+    /// Emit the error as a [`SyncEvent::Error`], and go on with the other
+    /// objects, i.e. only the subtree of the failed object is missing. The
+    /// default.
+    #[default]
+    Continue,
+    /// Like [`ErrorMode::Continue`], but retry a failed request once first,
+    /// e.g. for transient network errors. Invalid requests aren't retried.
+    RetryOnce,
+    /// Emit the first error and stop the fetch, as if it's cancelled (see
+    /// [`Fetcher::fetch_cancellable`]).
+    Abort,
+}
+
+type FetchResult = Result<AnyObject, NotionError>;
+
+/// An event of a fetch, see [`Fetcher::fetch_events`].
+// Not boxed, to be consistent with `FetchResult`.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum SyncEvent {
+    /// An object is fetched.
+    Object(FetchedObject),
+    /// A request failed, the fetch goes on with the other objects.
+    Error(NotionError),
+    /// The fetch is still in progress, emitted at the interval of
+    /// [`FetcherOptions::heartbeat`].
+    Heartbeat {
+        /// The number of the requests queued or running.
+        pending_tasks: usize,
+    },
+    /// A response is for another API version than the requested one (see
+    /// [`Api::take_version_mismatches`]), which may explain parse errors.
+    /// Advisory only, emitted once per version.
+    VersionMismatch {
+        requested: String,
+        responded: String,
+    },
+    /// An object is skipped, e.g. a block in the results of a database
+    /// query, or an object below the roots which is deleted or not shared
+    /// with the integration.
+    Skipped { key: ObjectKey, reason: String },
+    /// The title of a page/database differs from the one embedded in its
+    /// child page/database block (see [`Block::child_title`]), i.e. the
+    /// block's title is stale.
+    TitleMismatch {
+        /// The ID of the page/database, which is also the ID of the block.
+        id: String,
+        block_title: String,
+        title: String,
+    },
+    /// An element of a list failed to be parsed and is skipped, see
+    /// [`FetcherOptions::lenient_lists`].
+    InvalidElement {
+        /// The ID of the object the list belongs to, e.g. the parent block of
+        /// the children.
+        parent_id: String,
+        /// The index of the element in the list.
+        index: usize,
+        error: String,
+    },
+}
+
+/// A fetched object, with where it's found in the traversal, e.g. to build a
+/// tree view, or to find out why an object is pulled in.
+#[derive(Debug, Clone)]
+pub struct FetchedObject {
+    pub object: AnyObject,
+    /// 0 for the root object, 1 for its children (the blocks/comments of a
+    /// page, or the rows of a database), etc. The page/database of a child
+    /// page/database block is at the same depth as the block.
+    pub depth: usize,
+    /// The ID of the object whose children include this one, `None` for the
+    /// root object.
+    pub parent_id: Option<String>,
+}
+
+impl SyncEvent {
+    /// The fetched object or the error, `None` for the other events.
+    pub fn into_result(self) -> Option<FetchResult> {
+        match self {
+            SyncEvent::Object(x) => Some(Ok(x.object)),
+            SyncEvent::Error(e) => Some(Err(e)),
+            SyncEvent::Heartbeat { .. }
+            | SyncEvent::VersionMismatch { .. }
+            | SyncEvent::TitleMismatch { .. }
+            | SyncEvent::Skipped { .. }
+            | SyncEvent::InvalidElement { .. } => None,
+        }
+    }
+}
+
+/// The running counts of a fetch, see [`Fetcher::fetch_with_stats`]. The
+/// objects are counted as they're emitted, i.e. once each, and only the
+/// types in [`FetcherOptions::object_types`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncStats {
+    pub pages: usize,
+    pub databases: usize,
+    pub blocks: usize,
+    pub comments: usize,
+    pub users: usize,
+    /// The requests sent, including the retries.
+    pub requests: usize,
+    /// The retries after being rate limited.
+    pub retries: usize,
+    /// The [`SyncEvent::Error`]s emitted.
+    pub errors: usize,
+}
+
+impl SyncStats {
+    /// The objects emitted.
+    pub fn objects(&self) -> usize {
+        self.pages + self.databases + self.blocks + self.comments + self.users
+    }
+
+    fn add_object(&mut self, object_type: ObjectType) {
+        match object_type {
+            ObjectType::Page => self.pages += 1,
+            ObjectType::Database => self.databases += 1,
+            ObjectType::Block => self.blocks += 1,
+            ObjectType::Comment => self.comments += 1,
+            ObjectType::User => self.users += 1,
+            ObjectType::List | ObjectType::Unknown => (),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Any notion object.
+///
+/// Serialized in the externally tagged form, e.g. `{"Page": {...}}`.
+/// Deserialized from either that form, or a raw notion object, whose type is
+/// detected by its `object` field, e.g. the results of a database query.
+#[derive(Serialize, Debug, Clone)]
 pub enum AnyObject {
     Block(Block),
     Page(Page),
@@ -36,6 +316,45 @@ pub enum AnyObject {
     Comment(Comment),
 }
 
+impl<'de> Deserialize<'de> for AnyObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        enum Tagged {
+            Block(Block),
+            Page(Page),
+            Database(Database),
+            User(User),
+            Comment(Comment),
+        }
+
+        let value = Value::deserialize(deserializer)?;
+        let Some(object) = value.get("object").and_then(|x| x.as_str()) else {
+            let obj = match Tagged::deserialize(value).map_err(de::Error::custom)? {
+                Tagged::Block(x) => AnyObject::Block(x),
+                Tagged::Page(x) => AnyObject::Page(x),
+                Tagged::Database(x) => AnyObject::Database(x),
+                Tagged::User(x) => AnyObject::User(x),
+                Tagged::Comment(x) => AnyObject::Comment(x),
+            };
+            return Ok(obj);
+        };
+
+        let obj = match object {
+            "block" => AnyObject::Block(Block::deserialize(value).map_err(de::Error::custom)?),
+            "page" => AnyObject::Page(Page::deserialize(value).map_err(de::Error::custom)?),
+            "database" => {
+                AnyObject::Database(Database::deserialize(value).map_err(de::Error::custom)?)
+            }
+            "user" => AnyObject::User(User::deserialize(value).map_err(de::Error::custom)?),
+            "comment" => {
+                AnyObject::Comment(Comment::deserialize(value).map_err(de::Error::custom)?)
+            }
+            x => return Err(de::Error::custom(format!("unsupported object: {x}"))),
+        };
+        Ok(obj)
+    }
+}
+
 impl Object for AnyObject {
     fn id(&self) -> &str {
         match self {
@@ -58,9 +377,217 @@ impl Object for AnyObject {
     }
 }
 
+impl AnyObject {
+    /// A filesystem-safe name of the object, i.e. its title (or the object
+    /// type if there's no title) in lowercase, with the characters other than
+    /// letters and digits replaced by `-`, followed by a short ID suffix to
+    /// avoid collisions, e.g. `meeting-notes-2024-1a2b3c4d`.
+    pub fn slug(&self) -> String {
+        let title = match self {
+            AnyObject::Block(x) => x.plain_text(),
+            AnyObject::Page(x) => x.title(),
+            AnyObject::Database(x) => x.plain_title(),
+            AnyObject::User(x) => x.name.clone().unwrap_or_default(),
+            AnyObject::Comment(x) => to_plain_text(&x.rich_text),
+        };
+
+        let mut slug = String::new();
+        for c in title.chars().flat_map(char::to_lowercase) {
+            if c.is_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+            if slug.chars().count() >= SLUG_TITLE_LEN {
+                break;
+            }
+        }
+        let slug = slug.trim_end_matches('-');
+        let slug = if slug.is_empty() {
+            self.object_type().to_string()
+        } else {
+            slug.to_owned()
+        };
+
+        let id: String = self
+            .id()
+            .chars()
+            .filter(|x| x.is_ascii_alphanumeric())
+            .take(SLUG_ID_LEN)
+            .collect();
+        format!("{slug}-{id}")
+    }
+
+    /// The titles of the pages & databases in `objects`, e.g. the synced
+    /// set, to resolve mentions with [`resolve_mentions`].
+    ///
+    /// [`resolve_mentions`]: crate::resolve_mentions
+    pub fn titles<'a>(
+        objects: impl IntoIterator<Item = &'a AnyObject>,
+    ) -> HashMap<ObjectKey, String> {
+        objects
+            .into_iter()
+            .filter_map(|obj| match obj {
+                AnyObject::Page(x) => Some((obj.key(), x.title())),
+                AnyObject::Database(x) => Some((obj.key(), x.plain_title())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The parent of the object, `None` for users.
+    pub fn parent(&self) -> Option<&Parent> {
+        match self {
+            AnyObject::Block(x) => Some(&x.obj.parent),
+            AnyObject::Page(x) => Some(&x.obj.parent),
+            AnyObject::Database(x) => Some(&x.obj.parent),
+            AnyObject::Comment(x) => Some(&x.parent),
+            AnyObject::User(_) => None,
+        }
+    }
+
+    /// The users referenced by `created_by`/`last_edited_by` of the object,
+    /// which are usually partial, i.e. only with IDs.
+    pub fn users(&self) -> Vec<&User> {
+        match self {
+            AnyObject::Block(x) => vec![&x.obj.created_by, &x.obj.last_edited_by],
+            AnyObject::Page(x) => vec![&x.obj.created_by, &x.obj.last_edited_by],
+            AnyObject::Database(x) => vec![&x.obj.created_by, &x.obj.last_edited_by],
+            AnyObject::Comment(x) => vec![&x.created_by],
+            AnyObject::User(_) => vec![],
+        }
+    }
+
+    /// Rewrite the URLs of the files in the object with `f`, i.e. the icon &
+    /// cover of pages/databases, and file/image/video/pdf blocks.
+    pub fn map_file_urls(&mut self, f: &dyn Fn(&str) -> String) {
+        let (icon, cover) = match self {
+            AnyObject::Block(block) => return block.map_file_url(f),
+            AnyObject::Page(page) => (&mut page.icon, &mut page.cover),
+            AnyObject::Database(database) => (&mut database.icon, &mut database.cover),
+            AnyObject::User(_) | AnyObject::Comment(_) => return,
+        };
+        if let Some(icon) = icon {
+            icon.map_url(f);
+        }
+        if let Some(cover) = cover {
+            cover.map_url(f);
+        }
+    }
+
+    /// The files in the object, named by the field they're in, i.e. `icon`
+    /// & `cover` of pages/databases, and `file` of file/image/video/pdf
+    /// blocks.
+    pub fn files(&self) -> Vec<(&'static str, NotionFile)> {
+        let (icon, cover) = match self {
+            AnyObject::Block(block) => {
+                return block.file().map(|x| ("file", x)).into_iter().collect()
+            }
+            AnyObject::Page(page) => (&page.icon, &page.cover),
+            AnyObject::Database(database) => (&database.icon, &database.cover),
+            AnyObject::User(_) | AnyObject::Comment(_) => return Vec::new(),
+        };
+        let icon = match icon {
+            Some(Icon::File(file)) => Some(("icon", file.clone())),
+            _ => None,
+        };
+        let cover = cover.as_ref().map(|x| ("cover", x.clone()));
+        icon.into_iter().chain(cover).collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Task {
     req_type: ReqType,
+    // whether to fetch the children of the object
+    descend: bool,
+    // the depth of the objects emitted by the task, i.e. 0 for the root
+    // object, 1 for its children, etc.
+    depth: usize,
+    // the ID of the object whose children the task fetches, `None` for the
+    // roots
+    parent_id: Option<String>,
+    // the title embedded in the child page/database block which the task
+    // fetches the page/database of
+    block_title: Option<String>,
+    // whether the object is the root given to `fetch`, whose ID may be a
+    // user ID by mistake
+    user_check: bool,
+    // the context of the parent span before the task is started, then the
+    // context of its own span
+    #[cfg(feature = "otel")]
+    otel_cx: opentelemetry::Context,
+}
+
+impl Task {
+    fn new(req_type: ReqType) -> Self {
+        Self {
+            req_type,
+            descend: true,
+            depth: 0,
+            parent_id: None,
+            block_title: None,
+            user_check: false,
+            #[cfg(feature = "otel")]
+            otel_cx: opentelemetry::Context::current(),
+        }
+    }
+
+    #[cfg(feature = "otel")]
+    fn start_span(mut self) -> (Self, TaskSpan) {
+        let span = TaskSpan::start(
+            &self.otel_cx,
+            self.req_type.name(),
+            self.req_type.otel_attributes(),
+        );
+        self.otel_cx = span.context().clone();
+        (self, span)
+    }
+
+    /// A task derived from this one to fetch the children of `parent_id`,
+    /// an object emitted by this task.
+    fn child(&self, parent_id: &str, req_type: ReqType) -> Self {
+        Self {
+            req_type,
+            descend: self.descend,
+            depth: self.depth + 1,
+            parent_id: Some(parent_id.to_owned()),
+            block_title: None,
+            user_check: false,
+            #[cfg(feature = "otel")]
+            otel_cx: self.otel_cx.clone(),
+        }
+    }
+
+    /// The object requested, or the one whose list is requested. The parent
+    /// of comments may be a page or a block, which is taken as a block.
+    fn key(&self) -> Option<ObjectKey> {
+        let (object_type, id) = match &self.req_type {
+            ReqType::Block(id) => (ObjectType::Block, id),
+            ReqType::Page(id) => (ObjectType::Page, id),
+            ReqType::Database(id) => (ObjectType::Database, id),
+            ReqType::BlockChildren(_) | ReqType::Comments(_) => {
+                (ObjectType::Block, self.parent_id.as_ref()?)
+            }
+            ReqType::DatabaseQuery(_) => (ObjectType::Database, self.parent_id.as_ref()?),
+        };
+        Some(ObjectKey::new(object_type, id))
+    }
+
+    /// A task continuing this one at the same depth, e.g. to fetch the next
+    /// page of a list, or the page of a child page block.
+    fn sibling(&self, req_type: ReqType) -> Self {
+        Self {
+            req_type,
+            descend: self.descend,
+            depth: self.depth,
+            parent_id: self.parent_id.clone(),
+            block_title: None,
+            user_check: false,
+            #[cfg(feature = "otel")]
+            otel_cx: self.otel_cx.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -74,6 +601,60 @@ enum ReqType {
     Comments(PaginationInfo),
 }
 
+#[cfg(any(feature = "otel", feature = "tracing"))]
+impl ReqType {
+    fn name(&self) -> &'static str {
+        match self {
+            ReqType::Block(_) => "block",
+            ReqType::Page(_) => "page",
+            ReqType::Database(_) => "database",
+            ReqType::BlockChildren(_) => "block_children",
+            ReqType::DatabaseQuery(_) => "database_query",
+            ReqType::Comments(_) => "comments",
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl ReqType {
+    /// The ID of the object requested, or the URL (& cursor) of the list.
+    fn target(&self) -> String {
+        match self {
+            ReqType::Block(id) | ReqType::Page(id) | ReqType::Database(id) => id.clone(),
+            ReqType::BlockChildren(pagination)
+            | ReqType::DatabaseQuery(pagination)
+            | ReqType::Comments(pagination) => match pagination.current_cursor() {
+                Some(cursor) => format!("{} (cursor {cursor})", pagination.url()),
+                None => pagination.url().to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+impl ReqType {
+    fn otel_attributes(&self) -> Vec<opentelemetry::KeyValue> {
+        use opentelemetry::KeyValue;
+
+        let mut attributes = vec![KeyValue::new("notion.request", self.name())];
+        match self {
+            ReqType::Block(id) | ReqType::Page(id) | ReqType::Database(id) => {
+                attributes.push(KeyValue::new("notion.object_type", self.name()));
+                attributes.push(KeyValue::new("notion.object_id", id.clone()));
+            }
+            ReqType::BlockChildren(pagination)
+            | ReqType::DatabaseQuery(pagination)
+            | ReqType::Comments(pagination) => {
+                attributes.push(KeyValue::new("notion.url", pagination.url().to_string()));
+                if let Some(cursor) = pagination.current_cursor() {
+                    attributes.push(KeyValue::new("notion.cursor", cursor.to_owned()));
+                }
+            }
+        }
+        attributes
+    }
+}
+
 enum TaskOutput {
     Block(Block),
     Page(Page),
@@ -114,43 +695,561 @@ impl<E> TryFrom<Result<Block, E>> for TaskOutput {
     }
 }
 
+// the default rate limit, i.e. Notion's average rate limit
+const RATE_LIMIT: usize = 3;
+const RATE_LIMIT_BURST: usize = 5;
+
 impl Fetcher {
     pub fn new(token: &str) -> Fetcher {
+        Self::with_api(Api::new(token))
+    }
+
+    /// Create a fetcher which sends at most `per_second` requests per second
+    /// on average, with bursts of up to `burst` requests. The default is 3/5.
+    ///
+    /// Notion limits the requests of an integration to an average of 3 per
+    /// second, exceeding it causes `429 Too Many Requests` responses, which
+    /// are retried after the `Retry-After` delay. Higher limits may be
+    /// granted to some API tiers, lower ones make a gentler sync.
+    ///
+    /// Panics if `per_second` or `burst` is 0.
+    pub fn with_rate_limit(token: &str, per_second: usize, burst: usize) -> Fetcher {
+        Self::build(Api::new(token), per_second, burst)
+    }
+
+    /// Create a fetcher on top of an existing [`Api`], e.g. one created by
+    /// [`Api::with_executor`].
+    pub fn with_api(api: Api) -> Fetcher {
+        Self::build(api, RATE_LIMIT, RATE_LIMIT_BURST)
+    }
+
+    fn build(api: Api, per_second: usize, burst: usize) -> Fetcher {
         Fetcher {
-            api: Api::new(token),
+            api,
             rate_limiter: {
-                let rl = RateLimiter::new(3);
-                rl.burst(5);
+                let rl = RateLimiter::new(per_second);
+                rl.burst(burst);
                 rl
             },
+            options: FetcherOptions::default(),
+            file_url_hook: None,
+            descend_hook: None,
+            max_depth: None,
+            max_retries: None,
+            max_backoff: None,
+            error_mode: ErrorMode::default(),
+            page_size: MAX_PAGE_SIZE,
+            result_buffer: RESULT_BUFFER_SIZE,
+            task_buffer: TASK_BUFFER_SIZE,
+            control: FetchControl::default(),
+            concurrency: None,
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+            pending: Default::default(),
+            seen: Default::default(),
+            followed: Default::default(),
+            stats: Default::default(),
+            cancel: CancelHandle::default(),
         }
     }
 
-    pub async fn fetch(&self, id: &str) -> impl Stream<Item = Result<AnyObject, NotionError>> {
-        let (res_tx, res_rx) = channel::<Result<AnyObject, NotionError>>(10);
+    pub fn with_options(mut self, options: FetcherOptions) -> Self {
+        if let Some(seed) = options.jitter_seed {
+            self.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed)));
+        }
+        self.options = options;
+        self
+    }
 
-        // Initial task
-        let task = Task {
-            req_type: ReqType::Block(id.to_owned()),
+    /// Retry a request responded with `Retry-After` at most `max_retries`
+    /// times, waiting at most `max_backoff` before each retry, rather than
+    /// whatever the response asks for. Once the retries are exhausted, the
+    /// request fails with [`RequestError::RetryAfter`]. Retries forever by
+    /// default.
+    ///
+    /// [`RequestError::RetryAfter`]: crate::api::RequestError::RetryAfter
+    pub fn with_retry_policy(mut self, max_retries: usize, max_backoff: Duration) -> Self {
+        self.max_retries = Some(max_retries);
+        self.max_backoff = Some(max_backoff);
+        self
+    }
+
+    /// Set what a fetch does when a request fails, defaults to
+    /// [`ErrorMode::Continue`], e.g. [`ErrorMode::RetryOnce`] for an overnight
+    /// sync of a whole workspace, or [`ErrorMode::Abort`] to fail fast.
+    pub fn with_error_mode(mut self, mode: ErrorMode) -> Self {
+        self.error_mode = mode;
+        self
+    }
+
+    /// How long to wait before retrying a request which is responded with
+    /// `Retry-After: secs`, `None` if it isn't retried anymore, given the
+    /// number of the previous retries.
+    fn retry_delay(&self, secs: u64, retries: usize) -> Option<Duration> {
+        if self.max_retries.is_some_and(|x| retries >= x) {
+            return None;
+        }
+        let jitter = self
+            .rng
+            .lock()
+            .unwrap()
+            .gen_range(Duration::ZERO..=self.options.max_retry_jitter);
+        let delay = Duration::from_secs(secs) + jitter;
+        Some(self.max_backoff.map_or(delay, |x| delay.min(x)))
+    }
+
+    /// Rewrite the URL of every file (see [`AnyObject::map_file_urls`])
+    /// with `f` before the object is emitted, e.g. to point at a mirror/CDN
+    /// of the files. The URLs are kept as is by default.
+    pub fn on_file_url(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.file_url_hook = Some(Arc::new(f));
+        self
+    }
+
+    /// Decide per object whether to fetch its children, e.g. to stop at the
+    /// pages tagged "Archive". `f` is called before the children are
+    /// queued, returning false skips the children (for a child page/database
+    /// block, the page/database itself), but the object is still emitted.
+    /// Every object is descended into by default.
+    pub fn should_descend(
+        mut self,
+        f: impl Fn(&AnyObject) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.descend_hook = Some(Arc::new(f));
+        self
+    }
+
+    /// Only fetch the objects up to `depth` levels below the root, e.g. 0
+    /// for only the root object, 1 for the root and its children (the
+    /// blocks/comments of a page, or the rows of a database), for a shallow
+    /// preview. The objects at the max depth are still emitted, but their
+    /// children aren't fetched. Unlimited by default.
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Request at most `n` (clamped to `1..=`[`MAX_PAGE_SIZE`]) items per page
+    /// of the block children, database rows & comments, [`MAX_PAGE_SIZE`] by
+    /// default. A smaller page arrives sooner, e.g. for a latency-sensitive
+    /// preview, while a larger one saves round trips.
+    pub fn with_page_size(mut self, n: usize) -> Self {
+        self.page_size = n.clamp(1, MAX_PAGE_SIZE);
+        self
+    }
+
+    /// Buffer up to `results` fetched results until the consumer reads them,
+    /// and up to `tasks` queued child requests of each request, both 10 by
+    /// default (and at least 1).
+    ///
+    /// It's backpressure: once the result buffer is full, a slow consumer
+    /// throttles the fetch (unless [`FetcherOptions::overflow`] says
+    /// otherwise), which resumes as the stream is read, so the fetch never
+    /// runs far ahead of the consumer. Larger buffers let a wide tree be
+    /// fetched ahead with fewer waits, at the cost of memory.
+    pub fn with_buffer_sizes(mut self, results: usize, tasks: usize) -> Self {
+        self.result_buffer = results.max(1);
+        self.task_buffer = tasks.max(1);
+        self
+    }
+
+    /// Send at most `n` (clamped to `1..=`[`Semaphore::MAX_PERMITS`])
+    /// requests at once, across all the fetches of this fetcher (and its
+    /// clones), independent of the rate limit. The tasks beyond it wait
+    /// before sending, which bounds the connections & the responses in
+    /// memory for a huge workspace. Unlimited by default.
+    pub fn with_concurrency(mut self, n: usize) -> Self {
+        let n = n.clamp(1, Semaphore::MAX_PERMITS);
+        self.concurrency = Some(Arc::new(Semaphore::new(n)));
+        self
+    }
+
+    /// A handle to pause & resume the fetches of this fetcher (and its
+    /// clones), including the ones in progress.
+    pub fn control(&self) -> FetchControl {
+        self.control.clone()
+    }
+
+    /// The API the requests are sent with, e.g. to send a request of its own
+    /// to the same endpoint.
+    pub fn api(&self) -> &Api {
+        &self.api
+    }
+
+    fn descends(&self, obj: &AnyObject) -> bool {
+        self.descend_hook.as_ref().is_none_or(|f| f(obj))
+    }
+
+    /// Emit a fetched object.
+    async fn send_object(
+        &self,
+        task: &Task,
+        res_tx: &ResultSender<SyncEvent>,
+        mut obj: AnyObject,
+    ) -> Result<(), SendError> {
+        if !self.options.emits(obj.object_type()) {
+            return Ok(());
+        }
+        if let Some(f) = &self.file_url_hook {
+            obj.map_file_urls(f.as_ref());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::info!(object = %obj.key(), depth = task.depth, "object emitted");
+        self.stats.send_modify(|x| x.add_object(obj.object_type()));
+        res_tx
+            .send(SyncEvent::Object(FetchedObject {
+                object: obj,
+                depth: task.depth,
+                parent_id: task.parent_id.clone(),
+            }))
+            .await
+    }
+
+    fn event_channel(&self) -> (ResultSender<SyncEvent>, impl Stream<Item = SyncEvent>) {
+        let (tx, rx) = result_channel(self.result_buffer, self.options.overflow, || {
+            SyncEvent::Error(NotionError::BufferFull)
+        });
+        (tx, rx.into_stream())
+    }
+
+    /// A clone of the fetcher for a new fetch, which counts its own pending
+    /// tasks, and is cancelled on its own.
+    fn new_fetch(&self) -> Self {
+        let (seen, followed) = if self.options.dedup_across_fetches {
+            (self.seen.clone(), self.followed.clone())
+        } else {
+            Default::default()
         };
+        Self {
+            pending: Default::default(),
+            seen,
+            followed,
+            stats: Default::default(),
+            cancel: CancelHandle::default(),
+            ..self.clone()
+        }
+    }
 
+    /// Run `task` and the tasks derived from it in the background.
+    fn spawn_task(&self, task: Task, res_tx: ResultSender<SyncEvent>) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
         let this = self.clone();
         spawn(async move {
             this.do_task_recurs(task, res_tx).await;
         });
+    }
+
+    /// Whether `obj` isn't emitted yet in the current fetch, e.g. a page
+    /// may be reached through both a link and a child page block. An
+    /// object is emitted, and its children fetched, only the first time.
+    fn first_seen(&self, obj: &AnyObject) -> bool {
+        self.seen.lock().unwrap().insert(obj.key())
+    }
+
+    /// Queue the fetches of the pages/databases mentioned in `block`, see
+    /// [`FetcherOptions::follow_mentions`]. A mentioned object is queued
+    /// once, and only if it isn't emitted yet.
+    async fn follow_mentions(
+        &self,
+        task_tx: &mut Sender<Task>,
+        task: &Task,
+        block: &AnyObject,
+    ) -> Result<(), SendError> {
+        let AnyObject::Block(block) = block else {
+            return Ok(());
+        };
+        if !self.options.follow_mentions || !task.descend {
+            return Ok(());
+        }
+        let targets = block
+            .rich_text()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|x| match x.rich_text_type {
+                RichTextType::Mention { mention } => mention.target(),
+                _ => None,
+            });
+        for target in targets {
+            if self.seen.lock().unwrap().contains(&target)
+                || !self.followed.lock().unwrap().insert(target.clone())
+            {
+                continue;
+            }
+            let req_type = match target.object_type {
+                ObjectType::Database => ReqType::Database(target.id),
+                _ => ReqType::Page(target.id),
+            };
+            self.enqueue(task_tx, task.child(block.id(), req_type))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Queue a task derived from the running one, fails if the queue is
+    /// closed, e.g. the runtime is shutting down.
+    async fn enqueue(&self, task_tx: &mut Sender<Task>, task: Task) -> Result<(), SendError> {
+        if self.max_depth.is_some_and(|x| task.depth > x) || self.cancel.is_cancelled() {
+            return Ok(());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            request = task.req_type.name(),
+            target = %task.req_type.target(),
+            depth = task.depth,
+            "task enqueued"
+        );
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        if task_tx.send(task).await.is_err() {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            return Err(SendError);
+        }
+        Ok(())
+    }
+
+    /// Emit a heartbeat at the interval of [`FetcherOptions::heartbeat`],
+    /// until the fetch is done or the stream is dropped.
+    fn spawn_heartbeat(&self, res_tx: &ResultSender<SyncEvent>) {
+        let Some(interval) = self.options.heartbeat else {
+            return;
+        };
+        let res_tx = res_tx.downgrade();
+        let pending = self.pending.clone();
+        spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // the first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let pending_tasks = pending.load(Ordering::SeqCst);
+                if res_tx
+                    .send(SyncEvent::Heartbeat { pending_tasks })
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+    }
+
+    pub async fn fetch(&self, id: &str) -> impl Stream<Item = FetchResult> {
+        self.fetch_events(id)
+            .await
+            .filter_map(|x| futures::future::ready(x.into_result()))
+    }
+
+    /// Like [`Fetcher::fetch`], but also emits the other [`SyncEvent`]s,
+    /// e.g. heartbeats.
+    pub async fn fetch_events(&self, id: &str) -> impl Stream<Item = SyncEvent> {
+        self.fetch_cancellable(id).await.0
+    }
+
+    /// Like [`Fetcher::fetch_events`], with a handle to cancel the fetch.
+    pub async fn fetch_cancellable(
+        &self,
+        id: &str,
+    ) -> (impl Stream<Item = SyncEvent>, CancelHandle) {
+        let this = self.new_fetch();
+        (this.start_fetch(id), this.cancel)
+    }
+
+    /// Like [`Fetcher::fetch`], with the running [`SyncStats`] of the fetch,
+    /// e.g. for a progress bar, which are updated as the requests complete &
+    /// the objects are emitted.
+    pub async fn fetch_with_stats(
+        &self,
+        id: &str,
+    ) -> (impl Stream<Item = FetchResult>, watch::Receiver<SyncStats>) {
+        let (events, stats) = self.fetch_events_with_stats(id).await;
+        (
+            events.filter_map(|x| futures::future::ready(x.into_result())),
+            stats,
+        )
+    }
+
+    /// Like [`Fetcher::fetch_with_stats`], but also emits the other
+    /// [`SyncEvent`]s.
+    pub async fn fetch_events_with_stats(
+        &self,
+        id: &str,
+    ) -> (impl Stream<Item = SyncEvent>, watch::Receiver<SyncStats>) {
+        let this = self.new_fetch();
+        let stats = this.stats.subscribe();
+        (this.start_fetch(id), stats)
+    }
+
+    /// Start fetching `id` with the fetcher of a new fetch, see
+    /// [`Fetcher::new_fetch`].
+    fn start_fetch(&self, id: &str) -> impl Stream<Item = SyncEvent> {
+        let (res_tx, res_rx) = self.event_channel();
+        self.spawn_heartbeat(&res_tx);
+
+        // Initial task
+        let task = Task {
+            user_check: true,
+            ..Task::new(ReqType::Block(id.to_owned()))
+        };
+        self.spawn_task(task, res_tx);
 
         res_rx
     }
 
+    /// Fetch `id` with its children like [`Fetcher::fetch`], collected into
+    /// a `Vec`, or the first error, which cancels the rest of the fetch. It
+    /// blocks on a runtime of its own, e.g. for a script which doesn't drive
+    /// a stream, so it panics in an async context, where
+    /// [`Fetcher::fetch_all_async`] is the equivalent.
+    pub fn fetch_all(&self, id: &str) -> Result<Vec<AnyObject>, NotionError> {
+        tokio::runtime::Runtime::new()
+            .expect("failed to start a tokio runtime")
+            .block_on(self.fetch_all_async(id))
+    }
+
+    /// Like [`Fetcher::fetch_all`], but awaited rather than blocking.
+    pub async fn fetch_all_async(&self, id: &str) -> Result<Vec<AnyObject>, NotionError> {
+        let (events, cancel) = self.fetch_cancellable(id).await;
+        let mut results =
+            std::pin::pin!(events.filter_map(|x| futures::future::ready(x.into_result())));
+        let mut objects = Vec::new();
+        while let Some(res) = results.next().await {
+            match res {
+                Ok(obj) => objects.push(obj),
+                Err(e) => {
+                    cancel.cancel();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Fetch the pages & databases edited since `since` with their children
+    /// recursively, which are found by the search endpoint. It's far cheaper
+    /// than crawling from a root if only a few pages change. An object in
+    /// more than one of the changed subtrees is emitted only once.
+    ///
+    /// Note that the search results are eventually consistent, i.e. the most
+    /// recent edits may not be found yet.
+    pub async fn fetch_changed(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<impl Stream<Item = FetchResult>, NotionError> {
+        let mut ids = Vec::new();
+        let mut pagination = None;
+        loop {
+            let current = pagination.take();
+            let res = self
+                .with_retries(|| async {
+                    match current {
+                        Some(ref x) => self.api.list(x).await,
+                        None => self.api.search_recently_edited().await,
+                    }
+                })
+                .await?;
+            let mut done = false;
+            for obj in res.result.results.iter() {
+                let last_edited_time = match obj {
+                    AnyObject::Page(x) => x.obj.last_edited_time,
+                    AnyObject::Database(x) => x.obj.last_edited_time,
+                    _ => continue,
+                };
+                if last_edited_time < since {
+                    done = true;
+                    break;
+                }
+                ids.push(obj.id().to_owned());
+            }
+            match res.pagination {
+                Some(next) if !done => pagination = Some(next),
+                _ => break,
+            }
+        }
+
+        // the objects are deduplicated across the subtrees by the fetch
+        Ok(self.fetch_ids(ids, true).await)
+    }
+
+    /// Fetch all the users of the workspace, including the bots, e.g. to
+    /// resolve the `created_by`/`last_edited_by` IDs of the objects to
+    /// names. The integration needs the "read user information" capability.
+    pub async fn fetch_users(&self) -> Result<Vec<User>, NotionError> {
+        let mut users = Vec::new();
+        let mut pagination = None;
+        loop {
+            let current = pagination.take();
+            let res = self
+                .with_retries(|| async {
+                    match current {
+                        Some(ref x) => self.api.list(x).await,
+                        None => self.api.list_users().await,
+                    }
+                })
+                .await?;
+            users.extend(res.result.results);
+            match res.pagination {
+                Some(next) => pagination = Some(next),
+                None => break,
+            }
+        }
+        Ok(users)
+    }
+
+    /// Send a request out of the fetch tasks, i.e. with the rate limit and
+    /// the retries of `Retry-After`.
+    async fn with_retries<T, F, Fut>(&self, mut request: F) -> Result<T, NotionError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, NotionError>>,
+    {
+        let mut retries = 0;
+        loop {
+            self.control.wait_resumed().await;
+            let permit = self.acquire_slot().await;
+            let res = request().await;
+            drop(permit);
+            let Err(NotionError::RequestFailed(crate::api::RequestError::RetryAfter(secs))) = res
+            else {
+                break res;
+            };
+            let Some(delay) = self.retry_delay(secs, retries) else {
+                break res;
+            };
+            tokio::time::sleep(delay).await;
+            retries += 1;
+        }
+    }
+
+    /// Fetch the objects of `ids` concurrently, the type of each object is
+    /// detected automatically. Children are fetched recursively only if
+    /// `descend` is true.
+    pub async fn fetch_ids(
+        &self,
+        ids: Vec<String>,
+        descend: bool,
+    ) -> impl Stream<Item = FetchResult> {
+        let this = self.new_fetch();
+        let (res_tx, res_rx) = this.event_channel();
+
+        let mut seen = HashSet::new();
+        for id in ids.into_iter().filter(|id| seen.insert(id.clone())) {
+            let task = Task {
+                descend,
+                ..Task::new(ReqType::Block(id))
+            };
+            this.spawn_task(task, res_tx.clone());
+        }
+
+        res_rx.filter_map(|x| futures::future::ready(x.into_result()))
+    }
+
     // Recursive async fn need to be boxed in BoxFuture
     fn do_task_recurs(
         &self,
         task: Task,
-        res_tx: Sender<Result<AnyObject, NotionError>>,
+        res_tx: ResultSender<SyncEvent>,
     ) -> BoxFuture<'static, ()> {
         let this = self.clone();
         async move {
-            let (task_tx, mut task_rx) = channel(10);
+            let (task_tx, mut task_rx) = channel(this.task_buffer);
 
             {
                 let this = this.clone();
@@ -162,7 +1261,19 @@ impl Fetcher {
                 });
             }
 
-            this.do_task(task, res_tx.clone(), task_tx).await;
+            #[cfg(feature = "otel")]
+            let (task, span) = task.start_span();
+
+            // Stop quietly if the result stream or the task queue is closed,
+            // e.g. the consumer stops reading early, or the fetch is
+            // cancelled, in which case the queued tasks are skipped too.
+            if !this.cancel.is_cancelled() {
+                let _ = this.do_task(task, res_tx.clone(), task_tx).await;
+            }
+
+            #[cfg(feature = "otel")]
+            span.end();
+            this.pending.fetch_sub(1, Ordering::SeqCst);
         }
         .boxed()
     }
@@ -170,135 +1281,355 @@ impl Fetcher {
     async fn do_task(
         &self,
         task: Task,
-        mut res_tx: Sender<Result<AnyObject, NotionError>>,
+        res_tx: ResultSender<SyncEvent>,
         mut task_tx: Sender<Task>,
-    ) {
-        let res = self.do_request(task).await;
+    ) -> Result<(), SendError> {
+        let mut retried = false;
+        let res = loop {
+            // abort the request if the fetch is cancelled
+            let res = tokio::select! {
+                res = self.do_request(&task) => res,
+                _ = self.cancel.cancelled() => return Ok(()),
+            };
+            match &res {
+                Err(NotionError::RequestFailed(crate::api::RequestError::InvalidRequest(_))) => {}
+                Err(_) if self.error_mode == ErrorMode::RetryOnce && !retried => {
+                    #[cfg(feature = "tracing")]
+                    if let Err(e) = &res {
+                        tracing::info!(
+                            request = task.req_type.name(),
+                            target = %task.req_type.target(),
+                            error = %e,
+                            "request failed, retrying once"
+                        );
+                    }
+                    retried = true;
+                    continue;
+                }
+                _ => {}
+            }
+            break res;
+        };
+        for responded in self.api.take_version_mismatches() {
+            let requested = self.api.version().to_owned();
+            res_tx
+                .send(SyncEvent::VersionMismatch {
+                    requested,
+                    responded,
+                })
+                .await?;
+        }
         match res {
             Ok(obj) => {
                 match obj {
                     TaskOutput::Page(page) => {
-                        // get children
-                        let task = Task {
-                            req_type: ReqType::BlockChildren(PaginationInfo::new::<
-                                ObjectList<Block>,
-                            >(
-                                page.id()
-                            )),
-                        };
-                        task_tx.send(task).await.unwrap();
-
-                        // get comments
-                        let task = Task {
-                            req_type: ReqType::Comments(
-                                PaginationInfo::new::<ObjectList<Comment>>(page.id()),
-                            ),
-                        };
-                        task_tx.send(task).await.unwrap();
-
-                        res_tx.send(Ok(AnyObject::Page(page))).await.unwrap();
+                        let page = AnyObject::Page(page);
+                        if !self.first_seen(&page) {
+                            return Ok(());
+                        }
+                        self.check_title(&task, &page, &res_tx).await?;
+                        if task.descend && self.descends(&page) {
+                            // get children
+                            let child = task.child(
+                                page.id(),
+                                ReqType::BlockChildren(PaginationInfo::new::<ObjectList<Block>>(
+                                    self.api.base_url(),
+                                    page.id(),
+                                )),
+                            );
+                            self.enqueue(&mut task_tx, child).await?;
+
+                            // get comments
+                            if self.options.emits(ObjectType::Comment) {
+                                let child = task.child(
+                                    page.id(),
+                                    ReqType::Comments(PaginationInfo::new::<ObjectList<Comment>>(
+                                        self.api.base_url(),
+                                        page.id(),
+                                    )),
+                                );
+                                self.enqueue(&mut task_tx, child).await?;
+                            }
+                        }
+
+                        self.send_object(&task, &res_tx, page).await?;
                     }
                     TaskOutput::Database(database) => {
-                        let task = Task {
-                            req_type: ReqType::DatabaseQuery(PaginationInfo::new::<
-                                ObjectList<Block>,
-                            >(
-                                database.id()
-                            )),
-                        };
-                        task_tx.send(task).await.unwrap();
-                        res_tx
-                            .send(Ok(AnyObject::Database(database)))
-                            .await
-                            .unwrap();
+                        let paginations = query_paginations(
+                            self.api.base_url(),
+                            &database,
+                            &self.options.filter_properties(),
+                        );
+                        let database = AnyObject::Database(database);
+                        if !self.first_seen(&database) {
+                            return Ok(());
+                        }
+                        self.check_title(&task, &database, &res_tx).await?;
+                        if task.descend
+                            && self.options.descend_into_databases
+                            && self.descends(&database)
+                        {
+                            for pagination in paginations {
+                                self.enqueue(
+                                    &mut task_tx,
+                                    task.child(database.id(), ReqType::DatabaseQuery(pagination)),
+                                )
+                                .await?;
+                            }
+                        }
+                        self.send_object(&task, &res_tx, database).await?;
                     }
                     TaskOutput::BlockChildren(result) => {
-                        for (idx, mut block) in result.result.results.into_iter().enumerate() {
-                            block.child_index = result.result.start_index + idx;
-                            if let Some(task) = get_task_for_block(&block) {
-                                task_tx.send(task).await.unwrap();
+                        self.report_invalid(&task, &res_tx, &result.result).await?;
+                        for (index, mut block) in result.result.into_indexed_results() {
+                            block.child_index = index;
+                            let child = get_task_for_block(self.api.base_url(), &task, &block);
+                            let block = AnyObject::Block(block);
+                            if !self.first_seen(&block) {
+                                continue;
+                            }
+                            if self.descends(&block) {
+                                if let Some(child) = child {
+                                    self.enqueue(&mut task_tx, child).await?;
+                                }
+                                self.follow_mentions(&mut task_tx, &task, &block).await?;
                             }
-                            res_tx.send(Ok(AnyObject::Block(block))).await.unwrap();
+                            self.send_object(&task, &res_tx, block).await?;
                         }
                         if let Some(pagination) = result.pagination {
-                            task_tx
-                                .send(Task {
-                                    req_type: ReqType::BlockChildren(pagination),
-                                })
-                                .await
-                                .unwrap();
+                            self.enqueue(
+                                &mut task_tx,
+                                task.sibling(ReqType::BlockChildren(pagination)),
+                            )
+                            .await?;
                         }
                     }
                     TaskOutput::QueryDatabase(result) => {
+                        self.report_invalid(&task, &res_tx, &result.result).await?;
                         for obj in result.result.results {
-                            let task = match obj {
-                                AnyObject::Database(_) => Task {
-                                    req_type: ReqType::DatabaseQuery(PaginationInfo::new::<
-                                        ObjectList<AnyObject>,
-                                    >(
-                                        obj.id()
-                                    )),
-                                },
-                                AnyObject::Page(_) => Task {
-                                    req_type: ReqType::BlockChildren(PaginationInfo::new::<
-                                        ObjectList<Block>,
-                                    >(
-                                        obj.id()
-                                    )),
-                                },
-                                AnyObject::Block(_) => unreachable!("shouldn't be a block"),
-                                AnyObject::User(_) => unreachable!("shouldn't be a user"),
-                                AnyObject::Comment(_) => unreachable!("shouldn't be a comment"),
+                            if !self.first_seen(&obj) {
+                                continue;
+                            }
+                            let child = match obj {
+                                AnyObject::Database(_) => task.child(
+                                    obj.id(),
+                                    ReqType::DatabaseQuery(
+                                        PaginationInfo::new::<ObjectList<AnyObject>>(
+                                            self.api.base_url(),
+                                            obj.id(),
+                                        )
+                                        .filter_properties(&self.options.filter_properties()),
+                                    ),
+                                ),
+                                AnyObject::Page(_) => task.child(
+                                    obj.id(),
+                                    ReqType::BlockChildren(
+                                        PaginationInfo::new::<ObjectList<Block>>(
+                                            self.api.base_url(),
+                                            obj.id(),
+                                        ),
+                                    ),
+                                ),
+                                // Shouldn't happen, skip them rather than guess
+                                // what they are.
+                                AnyObject::Block(_)
+                                | AnyObject::User(_)
+                                | AnyObject::Comment(_) => {
+                                    res_tx
+                                        .send(SyncEvent::Skipped {
+                                            key: obj.key(),
+                                            reason: "unexpected object in a database query"
+                                                .to_owned(),
+                                        })
+                                        .await?;
+                                    continue;
+                                }
                             };
-                            task_tx.send(task).await.unwrap();
-                            res_tx.send(Ok(obj)).await.unwrap();
+                            if self.descends(&obj) {
+                                self.enqueue(&mut task_tx, child).await?;
+                            }
+                            self.send_object(&task, &res_tx, obj).await?;
                         }
                         if let Some(pagination) = result.pagination {
-                            task_tx
-                                .send(Task {
-                                    req_type: ReqType::DatabaseQuery(pagination),
-                                })
-                                .await
-                                .unwrap();
+                            self.enqueue(
+                                &mut task_tx,
+                                task.sibling(ReqType::DatabaseQuery(pagination)),
+                            )
+                            .await?;
                         }
                     }
                     TaskOutput::Block(block) => {
-                        if let Some(task) = get_task_for_block(&block) {
-                            task_tx.send(task).await.unwrap();
+                        let child = get_task_for_block(self.api.base_url(), &task, &block);
+                        let block = AnyObject::Block(block);
+                        if !self.first_seen(&block) {
+                            return Ok(());
+                        }
+                        if self.descends(&block) {
+                            if let Some(child) = child {
+                                self.enqueue(&mut task_tx, child).await?;
+                            }
+                            self.follow_mentions(&mut task_tx, &task, &block).await?;
                         }
-                        res_tx.send(Ok(AnyObject::Block(block))).await.unwrap();
+                        self.send_object(&task, &res_tx, block).await?;
                     }
                     TaskOutput::Comments(comments) => {
+                        self.report_invalid(&task, &res_tx, &comments.result)
+                            .await?;
                         for obj in comments.result.results {
-                            res_tx.send(Ok(AnyObject::Comment(obj))).await.unwrap();
+                            let obj = AnyObject::Comment(obj);
+                            if self.first_seen(&obj) {
+                                self.send_object(&task, &res_tx, obj).await?;
+                            }
                         }
                         if let Some(pagination) = comments.pagination {
-                            task_tx
-                                .send(Task {
-                                    req_type: ReqType::Comments(pagination),
-                                })
-                                .await
-                                .unwrap();
+                            self.enqueue(&mut task_tx, task.sibling(ReqType::Comments(pagination)))
+                                .await?;
                         }
                     }
                 };
             }
-            Err(e) => res_tx.send(Err(e)).await.unwrap(),
+            Err(e) => {
+                // A missing root is still an error, e.g. a wrong ID.
+                if let (Some(_), Some(key), Some(code)) =
+                    (&task.parent_id, task.key(), e.api_error_code())
+                {
+                    if matches!(
+                        code,
+                        ApiErrorCode::ObjectNotFound | ApiErrorCode::RestrictedResource
+                    ) {
+                        let reason = format!("{code}, deleted or not shared with the integration");
+                        res_tx.send(SyncEvent::Skipped { key, reason }).await?;
+                        return Ok(());
+                    }
+                }
+                let e = match &task.req_type {
+                    ReqType::Block(id) if task.user_check => self.check_user_root(id, e).await,
+                    _ => e,
+                };
+                // every other request would be rejected too
+                let unauthorized = e.api_error_code() == Some(&ApiErrorCode::Unauthorized);
+                self.stats.send_modify(|x| x.errors += 1);
+                res_tx.send(SyncEvent::Error(e)).await?;
+                if self.error_mode == ErrorMode::Abort || unauthorized {
+                    self.cancel.cancel();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The page of a list to request, with the page size & the parsing mode
+    /// of the fetcher.
+    fn list_pagination(&self, pagination: &PaginationInfo) -> PaginationInfo {
+        pagination
+            .clone()
+            .page_size(self.page_size)
+            .lenient(self.options.lenient_lists)
+    }
+
+    /// Emit the elements of `list` failed to be parsed.
+    async fn report_invalid<T>(
+        &self,
+        task: &Task,
+        res_tx: &ResultSender<SyncEvent>,
+        list: &ObjectList<T>,
+    ) -> Result<(), SendError> {
+        for (i, error) in list.invalid.iter() {
+            res_tx
+                .send(SyncEvent::InvalidElement {
+                    parent_id: task.parent_id.clone().unwrap_or_default(),
+                    index: list.start_index + i,
+                    error: error.clone(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Flag the title of the page/database fetched for a child page/database
+    /// block, if it differs from the title embedded in the block.
+    async fn check_title(
+        &self,
+        task: &Task,
+        obj: &AnyObject,
+        res_tx: &ResultSender<SyncEvent>,
+    ) -> Result<(), SendError> {
+        let Some(block_title) = &task.block_title else {
+            return Ok(());
+        };
+        let title = match obj {
+            // the title may be filtered out
+            AnyObject::Page(_)
+                if !self.options.filter_properties.is_empty()
+                    && !self.options.filter_properties.iter().any(|x| x == "title") =>
+            {
+                return Ok(())
+            }
+            AnyObject::Page(x) => x.title(),
+            AnyObject::Database(x) => x.plain_title(),
+            _ => return Ok(()),
+        };
+        if title != *block_title {
+            res_tx
+                .send(SyncEvent::TitleMismatch {
+                    id: obj.id().to_owned(),
+                    block_title: block_title.clone(),
+                    title,
+                })
+                .await?;
         }
+        Ok(())
+    }
+
+    /// The root `id` failed to be fetched with `err`, replace the error with
+    /// a clear one if `id` turns out to be a user ID, which is a common
+    /// mistake.
+    async fn check_user_root(&self, id: &str, err: NotionError) -> NotionError {
+        let _permit = self.acquire_slot().await;
+        match self.api.get_object::<User>(id).await {
+            Ok(_) => NotionError::invalid_request(format!(
+                "`{id}` is the ID of a user, which can't be synced, \
+                 use the ID of a page or database instead"
+            )),
+            Err(_) => err,
+        }
+    }
+
+    /// Wait for a request to be allowed by the concurrency limit & the rate
+    /// limit, return the permit of the former to hold during the request.
+    async fn acquire_slot(&self) -> Option<SemaphorePermit<'_>> {
+        // before the rate limiter, so that no token is spent waiting;
+        // never closed
+        let permit = match &self.concurrency {
+            Some(x) => Some(x.acquire().await.unwrap()),
+            None => None,
+        };
+        self.rate_limiter.acquire().await;
+        permit
     }
 
-    async fn do_request(&self, task: Task) -> Result<TaskOutput, NotionError> {
-        // Repeatly send request if there is a RetryAfter error, otherwise send
-        // the result to the channel.
+    async fn do_request(&self, task: &Task) -> Result<TaskOutput, NotionError> {
+        // Repeatly send request if there is a RetryAfter error (until the
+        // retries are exhausted), otherwise send the result to the channel.
+        let mut retries = 0;
         loop {
-            self.rate_limiter.acquire().await;
+            self.control.wait_resumed().await;
+            let permit = self.acquire_slot().await;
 
+            #[cfg(feature = "tracing")]
+            let started = std::time::Instant::now();
+            self.stats.send_modify(|x| x.requests += 1);
             let res = match task.req_type {
                 ReqType::Block(ref id) => self
                     .api
                     .get_object::<Block>(id)
                     .await
                     .map(TaskOutput::Block),
+                ReqType::Page(ref id) if !self.options.filter_properties.is_empty() => self
+                    .api
+                    .get_page_with_properties(id, &self.options.filter_properties())
+                    .await
+                    .map(TaskOutput::Page),
                 ReqType::Page(ref id) => {
                     self.api.get_object::<Page>(id).await.map(TaskOutput::Page)
                 }
@@ -309,18 +1640,37 @@ impl Fetcher {
                     .map(TaskOutput::Database),
                 ReqType::BlockChildren(ref pagination) => self
                     .api
-                    .list(pagination)
+                    .list(&self.list_pagination(pagination))
                     .await
                     .map(TaskOutput::BlockChildren),
                 ReqType::DatabaseQuery(ref pagination) => self
                     .api
-                    .list(pagination)
+                    .list(&self.list_pagination(pagination))
                     .await
                     .map(TaskOutput::QueryDatabase),
-                ReqType::Comments(ref pagination) => {
-                    self.api.list(pagination).await.map(TaskOutput::Comments)
-                }
+                ReqType::Comments(ref pagination) => self
+                    .api
+                    .list(&self.list_pagination(pagination))
+                    .await
+                    .map(TaskOutput::Comments),
             };
+            drop(permit);
+            #[cfg(feature = "tracing")]
+            match &res {
+                Ok(_) => tracing::debug!(
+                    request = task.req_type.name(),
+                    target = %task.req_type.target(),
+                    latency_ms = started.elapsed().as_millis() as u64,
+                    "request done"
+                ),
+                Err(e) => tracing::warn!(
+                    request = task.req_type.name(),
+                    target = %task.req_type.target(),
+                    latency_ms = started.elapsed().as_millis() as u64,
+                    error = %e,
+                    "request failed"
+                ),
+            }
 
             let Err(err) = &res else {
                 break res;
@@ -334,30 +1684,1758 @@ impl Fetcher {
                 break res;
             };
 
-            tokio::time::sleep(Duration::from_secs(*secs)).await;
+            let Some(delay) = self.retry_delay(*secs, retries) else {
+                break res;
+            };
+
+            #[cfg(feature = "otel")]
+            crate::otel::record_retry(&task.otel_cx, *secs);
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                request = task.req_type.name(),
+                target = %task.req_type.target(),
+                retry_after = secs,
+                retries,
+                delay_ms = delay.as_millis() as u64,
+                "rate limited, retrying"
+            );
+
+            self.stats.send_modify(|x| x.retries += 1);
+            tokio::time::sleep(delay).await;
+            retries += 1;
             // should we reset the rate_limiter here?
         }
     }
 }
 
-fn get_task_for_block(block: &Block) -> Option<Task> {
+/// Fetch the root of each fetcher concurrently, and merge the results into
+/// one stream. Each fetcher (e.g. one per integration token) keeps its own
+/// rate limiter. An object reached by more than one fetcher is emitted only
+/// once.
+pub async fn merge_fetchers(fetchers: Vec<(Fetcher, String)>) -> impl Stream<Item = FetchResult> {
+    let mut streams = Vec::new();
+    for (fetcher, root) in fetchers {
+        streams.push(fetcher.fetch(&root).await);
+    }
+
+    let mut seen = HashSet::new();
+    futures::stream::select_all(streams).filter(move |res| {
+        let new = match res {
+            Ok(obj) => seen.insert(obj.key()),
+            Err(_) => true,
+        };
+        futures::future::ready(new)
+    })
+}
+
+/// The queries for the rows of `database`, i.e. one per data source if it has
+/// any, otherwise the database itself.
+fn query_paginations(
+    base: &Url,
+    database: &Database,
+    filter_props: &[&str],
+) -> Vec<PaginationInfo> {
+    let paginations = if database.data_sources.is_empty() {
+        vec![PaginationInfo::new::<ObjectList<AnyObject>>(
+            base,
+            database.id(),
+        )]
+    } else {
+        database
+            .data_sources
+            .iter()
+            .map(|x| PaginationInfo::new::<DataSourceQuery>(base, &x.id))
+            .collect()
+    };
+    paginations
+        .into_iter()
+        .map(|x| x.filter_properties(filter_props))
+        .collect()
+}
+
+fn get_task_for_block(base: &Url, task: &Task, block: &Block) -> Option<Task> {
     let block_type = &block.block_type;
     let id = block.id().to_owned();
     match block_type {
+        // The page/database is the block itself, rather than its children.
         crate::block::BlockType::ChildPage => Some(Task {
-            req_type: ReqType::Page(id),
+            block_title: block.child_title().map(ToOwned::to_owned),
+            ..task.sibling(ReqType::Page(id))
         }),
         crate::block::BlockType::ChildDatabase => Some(Task {
-            req_type: ReqType::Database(id),
+            block_title: block.child_title().map(ToOwned::to_owned),
+            ..task.sibling(ReqType::Database(id))
         }),
         _ => {
-            if block.has_children {
-                Some(Task {
-                    req_type: ReqType::BlockChildren(PaginationInfo::new::<ObjectList<Block>>(&id)),
-                })
+            if block.has_children && task.descend {
+                Some(task.child(
+                    &id,
+                    ReqType::BlockChildren(PaginationInfo::new::<ObjectList<Block>>(base, &id)),
+                ))
             } else {
                 None
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use futures::{future::ready, StreamExt};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use serde_json::json;
+
+    use crate::{
+        api::{ApiErrorCode, PaginationInfo, RequestError},
+        error::NotionError,
+        object::ObjectList,
+        tests::{
+            block_json, database_json, list_json, mention_json, page_json, response, retry_after,
+            rich_text_json, user_json, RecordingExecutor, ScriptedExecutor,
+        },
+        Api, Block, HttpExecutor, Object, ObjectType,
+    };
+
+    use super::{
+        merge_fetchers, AnyObject, ErrorMode, FetchResult, FetchedObject, Fetcher, FetcherOptions,
+        ReqType, SyncEvent, SyncStats, Task, TaskOutput,
+    };
+
+    fn fetcher(executor: &(impl HttpExecutor + Clone + 'static)) -> Fetcher {
+        Fetcher::with_api(Api::with_executor("token", executor.clone())).with_options(
+            FetcherOptions {
+                max_retry_jitter: Duration::ZERO,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Run `task` and the tasks derived from it, and collect the results.
+    async fn fetch_task(fetcher: &Fetcher, task: Task) -> Vec<FetchResult> {
+        let fetcher = fetcher.new_fetch();
+        let (res_tx, res_rx) = fetcher.event_channel();
+        fetcher.spawn_task(task, res_tx);
+        res_rx
+            .filter_map(|x| ready(x.into_result()))
+            .collect()
+            .await
+    }
+
+    fn block_task(id: &str) -> Task {
+        Task::new(ReqType::Block(id.to_owned()))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_then_success() {
+        let executor = ScriptedExecutor::new([
+            retry_after(0),
+            retry_after(0),
+            response(200, block_json("b1", "p1", "paragraph")),
+        ]);
+        let res = fetcher(&executor).do_request(&block_task("b1")).await;
+        assert!(matches!(res, Ok(TaskOutput::Block(block)) if block.id() == "b1"));
+        assert_eq!(executor.calls(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_policy() {
+        let executor = ScriptedExecutor::new([
+            retry_after(60),
+            retry_after(60),
+            retry_after(60),
+            response(200, block_json("b1", "p1", "paragraph")),
+        ]);
+        let fetcher = fetcher(&executor).with_retry_policy(2, Duration::from_millis(10));
+        let res = tokio::time::timeout(
+            Duration::from_secs(5),
+            fetcher.do_request(&block_task("b1")),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            res,
+            Err(NotionError::RequestFailed(RequestError::RetryAfter(60)))
+        ));
+        assert_eq!(executor.calls(), 3);
+
+        assert_eq!(fetcher.retry_delay(60, 1), Some(Duration::from_millis(10)));
+        assert_eq!(fetcher.retry_delay(60, 2), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn error_mode() {
+        let run = |mode, responses: Vec<_>| async move {
+            let executor = ScriptedExecutor::new(responses);
+            let fetcher = fetcher(&executor).with_error_mode(mode).new_fetch();
+            let (res_tx, res_rx) = fetcher.event_channel();
+            // not a root block, which is checked for a user on errors
+            let task = Task {
+                descend: false,
+                ..Task::new(ReqType::Page("p1".to_owned()))
+            };
+            fetcher.spawn_task(task, res_tx);
+            let results: Vec<_> = res_rx
+                .filter_map(|x| ready(x.into_result()))
+                .collect()
+                .await;
+            (results, executor.calls(), fetcher.cancel.is_cancelled())
+        };
+        let ok = || response(200, page_json("p1", "workspace"));
+
+        let (results, calls, cancelled) =
+            run(ErrorMode::Continue, vec![response(502, ""), ok()]).await;
+        assert!(matches!(&results[..], [Err(_)]));
+        assert_eq!((calls, cancelled), (1, false));
+
+        let (results, calls, cancelled) =
+            run(ErrorMode::RetryOnce, vec![response(502, ""), ok()]).await;
+        assert!(matches!(&results[..], [Ok(AnyObject::Page(_))]));
+        assert_eq!((calls, cancelled), (2, false));
+
+        let (results, calls, cancelled) =
+            run(ErrorMode::Abort, vec![response(502, ""), ok()]).await;
+        assert!(matches!(&results[..], [Err(_)]));
+        assert_eq!((calls, cancelled), (1, true));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limited_without_retry_after() {
+        let executor = ScriptedExecutor::new([response(429, "")]);
+        let res = fetcher(&executor).do_request(&block_task("b1")).await;
+        assert!(matches!(
+            res,
+            Err(NotionError::RequestFailed(RequestError::InvalidResponse(_)))
+        ));
+        assert_eq!(executor.calls(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn server_error_is_not_retried() {
+        let executor = ScriptedExecutor::new([
+            response(502, "bad gateway"),
+            response(200, block_json("b1", "p1", "paragraph")),
+        ]);
+        let res = fetcher(&executor).do_request(&block_task("b1")).await;
+        assert!(matches!(
+            res,
+            Err(NotionError::RequestFailed(RequestError::InvalidResponse(_)))
+        ));
+        assert_eq!(executor.calls(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn server_error_then_success() {
+        let executor = ScriptedExecutor::new([
+            response(503, "service unavailable"),
+            response(200, block_json("b1", "p1", "paragraph")),
+        ]);
+        let fetcher = fetcher(&executor).with_error_mode(ErrorMode::RetryOnce);
+        let results = fetch_task(&fetcher, block_task("b1")).await;
+        assert!(matches!(&results[..], [Ok(AnyObject::Block(block))] if block.id() == "b1"));
+        assert_eq!(executor.calls(), 2);
+    }
+
+    /// Records the level & message of each event.
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct CapturedEvents(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for CapturedEvents {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message(String);
+            impl tracing::field::Visit for Message {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    if field.name() == "message" {
+                        self.0 = format!("{value:?}");
+                    }
+                }
+            }
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            let level = event.metadata().level();
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{level} {}", message.0));
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test(start_paused = true)]
+    async fn tracing_events() {
+        let events = CapturedEvents::default();
+        // the current-thread runtime runs the spawned tasks on this thread
+        let _guard = tracing::subscriber::set_default(events.clone());
+
+        let executor = ScriptedExecutor::new([
+            retry_after(0),
+            response(503, "service unavailable"),
+            response(200, page_json("p1", "workspace")),
+        ]);
+        let fetcher = fetcher(&executor).with_error_mode(ErrorMode::RetryOnce);
+        let task = Task {
+            descend: false,
+            ..Task::new(ReqType::Page("p1".to_owned()))
+        };
+        let results = fetch_task(&fetcher, task).await;
+        assert!(matches!(&results[..], [Ok(AnyObject::Page(_))]));
+
+        assert_eq!(
+            *events.0.lock().unwrap(),
+            [
+                // the 429, then the 503
+                "WARN request failed",
+                "INFO rate limited, retrying",
+                "WARN request failed",
+                "INFO request failed, retrying once",
+                "DEBUG request done",
+                "INFO object emitted",
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn invalid_body_after_retry() {
+        let executor = ScriptedExecutor::new([retry_after(0), response(200, "{}")]);
+        let res = fetcher(&executor).do_request(&block_task("b1")).await;
+        assert!(res.is_err());
+        assert_eq!(executor.calls(), 2);
+    }
+
+    #[test]
+    fn any_object_roundtrip() {
+        let block = serde_json::from_value(block_json("b1", "p1", "paragraph")).unwrap();
+        let js = serde_json::to_string(&AnyObject::Block(block)).unwrap();
+        let obj: AnyObject = serde_json::from_str(&js).unwrap();
+        assert!(matches!(obj, AnyObject::Block(block) if block.id() == "b1"));
+
+        // a raw object, e.g. in the results of a database query
+        let obj: AnyObject = serde_json::from_value(page_json("p1", "d1")).unwrap();
+        assert!(matches!(obj, AnyObject::Page(page) if page.id() == "p1"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetch_ids_without_descending() {
+        let mut block = block_json("b1", "p1", "paragraph");
+        block["has_children"] = true.into();
+        let executor = ScriptedExecutor::new([response(200, block)]);
+        let objects: Vec<_> = fetcher(&executor)
+            .fetch_ids(vec!["b1".to_owned(), "b1".to_owned()], false)
+            .await
+            .collect()
+            .await;
+        assert!(matches!(&objects[..], [Ok(AnyObject::Block(block))] if block.id() == "b1"));
+        assert_eq!(executor.calls(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetch_requests() {
+        let mut root = block_json("root", "workspace", "paragraph");
+        root["has_children"] = true.into();
+        let executor = RecordingExecutor::default()
+            .route("GET blocks/root", root)
+            .route(
+                "GET blocks/root/children",
+                list_json(
+                    vec![
+                        block_json("b1", "root", "paragraph"),
+                        block_json("p1", "root", "child_page"),
+                    ],
+                    None,
+                ),
+            )
+            .route("GET pages/p1", page_json("p1", "root"))
+            .route("GET blocks/p1/children", list_json(vec![], None))
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+
+        let objects: Vec<_> = fetcher(&executor).fetch("root").await.collect().await;
+        assert_eq!(objects.len(), 4);
+        assert!(objects.iter().all(|x| x.is_ok()));
+        assert_eq!(
+            executor.requests(),
+            [
+                "GET blocks/p1/children",
+                "GET blocks/root",
+                "GET blocks/root/children",
+                "GET comments?block_id=p1",
+                "GET pages/p1",
+            ]
+        );
+    }
+
+    #[test]
+    fn fetch_all() {
+        let mut root = block_json("root", "workspace", "paragraph");
+        root["has_children"] = true.into();
+        let executor = RecordingExecutor::default()
+            .route("GET blocks/root", root)
+            .route(
+                "GET blocks/root/children",
+                list_json(
+                    vec![
+                        block_json("b1", "root", "paragraph"),
+                        block_json("p1", "root", "child_page"),
+                    ],
+                    None,
+                ),
+            )
+            .route("GET pages/p1", page_json("p1", "root"))
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+        // blocking on a runtime of its own, where the time can't be paused
+        let fetcher = |executor: &RecordingExecutor| {
+            Fetcher::build(Api::with_executor("token", executor.clone()), 100, 100)
+        };
+
+        // the children of p1 aren't routed, i.e. fail
+        let res = fetcher(&executor).fetch_all("root");
+        assert!(matches!(res, Err(NotionError::RequestFailed(_))), "{res:?}");
+
+        let executor = executor.route("GET blocks/p1/children", list_json(vec![], None));
+        let mut ids: Vec<_> = fetcher(&executor)
+            .fetch_all("root")
+            .unwrap()
+            .iter()
+            .map(|x| x.key().to_string())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["block-b1", "block-p1", "block-root", "page-p1"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrency() {
+        use std::sync::{atomic::AtomicUsize, Arc};
+
+        use futures::future::BoxFuture;
+        use reqwest::{Request, Response};
+
+        // serves each block slowly, recording the max requests in flight
+        #[derive(Clone, Default)]
+        struct SlowExecutor {
+            in_flight: Arc<AtomicUsize>,
+            max_in_flight: Arc<AtomicUsize>,
+        }
+
+        impl HttpExecutor for SlowExecutor {
+            fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+                let id = request.url().path().rsplit('/').next().unwrap().to_owned();
+                Box::pin(async move {
+                    let n = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    self.max_in_flight.fetch_max(n, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok(response(200, block_json(&id, "p1", "paragraph")).into())
+                })
+            }
+        }
+
+        let ids: Vec<_> = (0..8).map(|i| format!("b{i}")).collect();
+        let fetch = |fetcher: Fetcher| {
+            let ids = ids.clone();
+            async move {
+                let results: Vec<_> = fetcher.fetch_ids(ids, false).await.collect().await;
+                assert!(results.iter().all(|x| x.is_ok()), "{results:?}");
+                results.len()
+            }
+        };
+
+        // the rate limit allows all of them at once
+        let executor = SlowExecutor::default();
+        let fetcher = Fetcher::build(Api::with_executor("token", executor.clone()), 100, 100);
+        assert_eq!(fetch(fetcher).await, 8);
+        assert!(executor.max_in_flight.load(Ordering::SeqCst) > 2);
+
+        let executor = SlowExecutor::default();
+        let fetcher = Fetcher::build(Api::with_executor("token", executor.clone()), 100, 100)
+            .with_concurrency(2);
+        assert_eq!(fetch(fetcher).await, 8);
+        assert_eq!(executor.max_in_flight.load(Ordering::SeqCst), 2);
+
+        // clamped rather than panicking
+        let executor = SlowExecutor::default();
+        let fetcher = Fetcher::build(Api::with_executor("token", executor.clone()), 100, 100)
+            .with_concurrency(usize::MAX);
+        assert_eq!(fetch(fetcher).await, 8);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_consumer() {
+        // a root with 5 children, each with 3 children
+        let mut root = block_json("root", "workspace", "paragraph");
+        root["has_children"] = true.into();
+        let mut executor = RecordingExecutor::default().route("GET blocks/root", root);
+        let mut children = Vec::new();
+        for i in 0..5 {
+            let id = format!("b{i}");
+            let mut child = block_json(&id, "root", "paragraph");
+            child["has_children"] = true.into();
+            children.push(child);
+            let grandchildren = (0..3)
+                .map(|j| block_json(&format!("{id}-{j}"), &id, "paragraph"))
+                .collect();
+            executor = executor.route(
+                &format!("GET blocks/{id}/children"),
+                list_json(grandchildren, None),
+            );
+        }
+        let executor = executor.route("GET blocks/root/children", list_json(children, None));
+
+        let fetcher =
+            Fetcher::build(Api::with_executor("token", executor), 100, 100).with_buffer_sizes(1, 1);
+        let results = fetcher
+            .fetch("root")
+            .await
+            .then(|x| async {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                x
+            })
+            .collect::<Vec<_>>();
+        let results = tokio::time::timeout(Duration::from_secs(10), results)
+            .await
+            .expect("stalled");
+        assert!(results.iter().all(|x| x.is_ok()), "{results:?}");
+        assert_eq!(results.len(), 1 + 5 + 5 * 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetch_with_stats() {
+        let mut root = block_json("root", "workspace", "paragraph");
+        root["has_children"] = true.into();
+        let executor = RecordingExecutor::default()
+            .route("GET blocks/root", root)
+            .route(
+                "GET blocks/root/children",
+                list_json(
+                    vec![
+                        block_json("b1", "root", "paragraph"),
+                        block_json("p1", "root", "child_page"),
+                    ],
+                    None,
+                ),
+            )
+            .route("GET pages/p1", page_json("p1", "root"))
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+
+        // the children of p1 aren't routed, i.e. fail
+        let (objects, stats) = fetcher(&executor).fetch_with_stats("root").await;
+        let objects: Vec<_> = objects.collect().await;
+        assert_eq!(objects.len(), 5);
+        let stats = *stats.borrow();
+        assert_eq!(
+            stats,
+            SyncStats {
+                pages: 1,
+                blocks: 3,
+                requests: 5,
+                errors: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.objects(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn base_url() {
+        let executor = RecordingExecutor::default()
+            .route("GET /mock/v1/pages/p1", page_json("p1", "workspace"))
+            .route(
+                "GET /mock/v1/blocks/p1/children",
+                list_json(vec![block_json("b1", "p1", "paragraph")], None),
+            )
+            .route("GET /mock/v1/comments?block_id=p1", list_json(vec![], None));
+        let api = Api::with_executor("token", executor.clone())
+            .with_base_url("http://localhost:8080/mock/v1".parse().unwrap());
+        assert_eq!(api.base_url().as_str(), "http://localhost:8080/mock/v1/");
+
+        let fetcher = Fetcher::with_api(api);
+        let task = Task::new(ReqType::Page("p1".to_owned()));
+        let objects = fetch_task(&fetcher, task).await;
+        assert_eq!(objects.len(), 2);
+        assert!(objects.iter().all(|x| x.is_ok()));
+        assert_eq!(
+            executor.requests(),
+            [
+                "GET /mock/v1/blocks/p1/children",
+                "GET /mock/v1/comments?block_id=p1",
+                "GET /mock/v1/pages/p1",
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn on_file_url() {
+        let mut page = page_json("p1", "workspace");
+        page["cover"] = json!({ "type": "external", "external": { "url": "https://a.com/c.png" } });
+        let mut image = block_json("b1", "p1", "image");
+        image["image"] = json!({
+            "type": "file",
+            "file": { "url": "https://s3.com/i.png", "expiry_time": "2024-01-01T00:00:00.000Z" },
+        });
+        let executor = RecordingExecutor::default()
+            .route("GET pages/p1", page)
+            .route("GET blocks/p1/children", list_json(vec![image], None))
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+
+        let fetcher = fetcher(&executor).on_file_url(|url| url.replace("https://", "https://cdn/"));
+        let task = Task::new(ReqType::Page("p1".to_owned()));
+        let objects: Vec<_> = fetch_task(&fetcher, task)
+            .await
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect();
+
+        let urls: Vec<_> = objects
+            .iter()
+            .map(|obj| match obj {
+                AnyObject::Page(page) => page.cover.as_ref().unwrap().url().to_owned(),
+                AnyObject::Block(block) => serde_json::to_value(block).unwrap()["image"]["file"]
+                    ["url"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(urls.len(), 2);
+        assert!(urls.contains(&"https://cdn/a.com/c.png".to_owned()));
+        assert!(urls.contains(&"https://cdn/s3.com/i.png".to_owned()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn merge_fetchers_dedup() {
+        let mut root = block_json("root", "workspace", "paragraph");
+        root["has_children"] = true.into();
+        let team_a = RecordingExecutor::default()
+            .route("GET blocks/a", block_json("a", "root", "paragraph"))
+            .route("GET blocks/root", root.clone())
+            .route(
+                "GET blocks/root/children",
+                list_json(vec![block_json("a", "root", "paragraph")], None),
+            );
+        let team_b = RecordingExecutor::default()
+            .route("GET blocks/root", root)
+            .route(
+                "GET blocks/root/children",
+                list_json(
+                    vec![
+                        block_json("a", "root", "paragraph"),
+                        block_json("b", "root", "paragraph"),
+                    ],
+                    None,
+                ),
+            );
+
+        let objects: Vec<_> = merge_fetchers(vec![
+            (fetcher(&team_a), "root".to_owned()),
+            (fetcher(&team_a), "a".to_owned()),
+            (fetcher(&team_b), "root".to_owned()),
+        ])
+        .await
+        .collect()
+        .await;
+        let mut ids: Vec<_> = objects.iter().map(|x| x.as_ref().unwrap().id()).collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "b", "root"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn nested_roots() {
+        let mut root = block_json("root", "workspace", "paragraph");
+        root["has_children"] = true.into();
+        let executor = RecordingExecutor::default()
+            .route("GET blocks/root", root)
+            .route(
+                "GET blocks/root/children",
+                list_json(vec![block_json("p1", "root", "child_page")], None),
+            )
+            .route("GET blocks/p1", block_json("p1", "root", "child_page"))
+            .route("GET pages/p1", page_json("p1", "root"))
+            .route(
+                "GET blocks/p1/children",
+                list_json(vec![block_json("b1", "p1", "paragraph")], None),
+            )
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+        let fetch_roots = |fetcher: Fetcher| async move {
+            let streams = vec![fetcher.fetch("root").await, fetcher.fetch("p1").await];
+            let mut keys: Vec<_> = futures::stream::select_all(streams)
+                .map(|x| x.unwrap().key().to_string())
+                .collect()
+                .await;
+            keys.sort();
+            keys
+        };
+
+        let keys = fetch_roots(fetcher(&executor)).await;
+        assert!(keys.len() > 4, "{keys:?}");
+
+        let fetcher = fetcher(&executor).with_options(FetcherOptions {
+            dedup_across_fetches: true,
+            max_retry_jitter: Duration::ZERO,
+            ..Default::default()
+        });
+        assert_eq!(
+            fetch_roots(fetcher).await,
+            ["block-b1", "block-p1", "block-root", "page-p1"]
+        );
+    }
+
+    #[test]
+    fn slug() {
+        let page = |title: &str| {
+            let mut js = page_json("1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d", "workspace");
+            js["properties"] = json!({
+                "Name": {
+                    "id": "title",
+                    "type": "title",
+                    "title": [rich_text_json(title)],
+                },
+            });
+            AnyObject::Page(serde_json::from_value(js).unwrap())
+        };
+
+        assert_eq!(
+            page("Design / Specs: v2").slug(),
+            "design-specs-v2-1a2b3c4d"
+        );
+        assert_eq!(page("🚀 Launch Plan 🚀").slug(), "launch-plan-1a2b3c4d");
+        assert_eq!(page("Café Ünïcode").slug(), "café-ünïcode-1a2b3c4d");
+        assert_eq!(page("").slug(), "page-1a2b3c4d");
+        assert_eq!(page("🚀").slug(), "page-1a2b3c4d");
+        assert_eq!(page(&"a".repeat(100)).slug().len(), 50 + 9);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn databases_only() {
+        let executor = RecordingExecutor::default()
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route(
+                "GET blocks/p1/children",
+                list_json(
+                    vec![
+                        block_json("b1", "p1", "paragraph"),
+                        block_json("d1", "p1", "child_database"),
+                    ],
+                    None,
+                ),
+            )
+            .route("GET databases/d1", database_json("d1", "p1"));
+
+        let fetcher = fetcher(&executor).with_options(FetcherOptions {
+            descend_into_databases: false,
+            object_types: vec![ObjectType::Database],
+            ..Default::default()
+        });
+        let task = Task::new(ReqType::Page("p1".to_owned()));
+        let objects = fetch_task(&fetcher, task).await;
+
+        assert!(matches!(&objects[..], [Ok(AnyObject::Database(x))] if x.id() == "d1"));
+        assert_eq!(
+            executor.requests(),
+            ["GET blocks/p1/children", "GET databases/d1", "GET pages/p1"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_descend() {
+        let executor = RecordingExecutor::default()
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route(
+                "GET blocks/p1/children",
+                list_json(
+                    vec![
+                        block_json("p2", "p1", "child_page"),
+                        block_json("p3", "p1", "child_page"),
+                    ],
+                    None,
+                ),
+            )
+            .route("GET pages/p2", page_json("p2", "p1"))
+            .route("GET pages/p3", page_json("p3", "p1"))
+            .route("GET blocks/p3/children", list_json(vec![], None))
+            .route("GET comments?block_id=p1", list_json(vec![], None))
+            .route("GET comments?block_id=p3", list_json(vec![], None));
+
+        let fetcher = fetcher(&executor)
+            .should_descend(|obj| !matches!(obj, AnyObject::Page(x) if x.id() == "p2"));
+        let task = Task::new(ReqType::Page("p1".to_owned()));
+        let mut ids: Vec<_> = fetch_task(&fetcher, task)
+            .await
+            .into_iter()
+            .map(|x| x.unwrap().key().to_string())
+            .collect();
+        ids.sort();
+
+        // p2 itself is still emitted, but not its children
+        assert_eq!(
+            ids,
+            ["block-p2", "block-p3", "page-p1", "page-p2", "page-p3"]
+        );
+        assert!(!executor
+            .requests()
+            .iter()
+            .any(|x| x.contains("p2/children") || x.contains("block_id=p2")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_depth() {
+        let mut b1 = block_json("b1", "p1", "paragraph");
+        b1["has_children"] = json!(true);
+        let executor = RecordingExecutor::default()
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route(
+                "GET blocks/p1/children",
+                list_json(vec![b1, block_json("p2", "p1", "child_page")], None),
+            )
+            .route(
+                "GET blocks/b1/children",
+                list_json(vec![block_json("b2", "b1", "paragraph")], None),
+            )
+            .route("GET pages/p2", page_json("p2", "p1"))
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+
+        let fetch = |depth| {
+            let fetcher = fetcher(&executor).with_max_depth(depth);
+            async move {
+                let task = Task::new(ReqType::Page("p1".to_owned()));
+                let mut ids: Vec<_> = fetch_task(&fetcher, task)
+                    .await
+                    .into_iter()
+                    .map(|x| x.unwrap().key().to_string())
+                    .collect();
+                ids.sort();
+                ids
+            }
+        };
+
+        assert_eq!(fetch(0).await, ["page-p1"]);
+        assert_eq!(executor.requests(), ["GET pages/p1"]);
+
+        // the page of a child page block is at the same depth as the block
+        assert_eq!(
+            fetch(1).await,
+            ["block-b1", "block-p2", "page-p1", "page-p2"]
+        );
+        assert!(!executor
+            .requests()
+            .iter()
+            .any(|x| x.contains("b1/children") || x.contains("p2/children")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetched_object_depth() {
+        let mut b1 = block_json("b1", "p1", "paragraph");
+        b1["has_children"] = json!(true);
+        let executor = RecordingExecutor::default()
+            .route("GET blocks/p1", block_json("p1", "workspace", "child_page"))
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route(
+                "GET blocks/p1/children",
+                list_json(vec![b1, block_json("p2", "p1", "child_page")], None),
+            )
+            .route(
+                "GET blocks/b1/children",
+                list_json(vec![block_json("b2", "b1", "paragraph")], None),
+            )
+            .route("GET pages/p2", page_json("p2", "p1"))
+            .route("GET blocks/p2/children", list_json(vec![], None))
+            .route("GET comments?block_id=p1", list_json(vec![], None))
+            .route("GET comments?block_id=p2", list_json(vec![], None));
+
+        let mut objects: Vec<_> = fetcher(&executor)
+            .fetch_events("p1")
+            .await
+            .filter_map(|x| {
+                ready(match x {
+                    SyncEvent::Object(x) => {
+                        Some((x.object.key().to_string(), x.depth, x.parent_id))
+                    }
+                    _ => None,
+                })
+            })
+            .collect()
+            .await;
+        objects.sort();
+
+        let parent = |x: &str| Some(x.to_owned());
+        assert_eq!(
+            objects,
+            [
+                ("block-b1".to_owned(), 1, parent("p1")),
+                ("block-b2".to_owned(), 2, parent("b1")),
+                ("block-p1".to_owned(), 0, None),
+                ("block-p2".to_owned(), 1, parent("p1")),
+                ("page-p1".to_owned(), 0, None),
+                ("page-p2".to_owned(), 1, parent("p1")),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dedup() {
+        let mut b1 = block_json("b1", "p1", "paragraph");
+        b1["has_children"] = json!(true);
+        let executor = RecordingExecutor::default()
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route(
+                "GET blocks/p1/children",
+                list_json(vec![b1.clone(), b1], None),
+            )
+            .route(
+                "GET blocks/b1/children",
+                list_json(vec![block_json("b2", "b1", "paragraph")], None),
+            )
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+
+        let task = Task::new(ReqType::Page("p1".to_owned()));
+        let mut ids: Vec<_> = fetch_task(&fetcher(&executor), task)
+            .await
+            .into_iter()
+            .map(|x| x.unwrap().key().to_string())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["block-b1", "block-b2", "page-p1"]);
+        assert_eq!(
+            executor.requests(),
+            [
+                "GET blocks/b1/children",
+                "GET blocks/p1/children",
+                "GET comments?block_id=p1",
+                "GET pages/p1",
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn user_root() {
+        let executor = RecordingExecutor::default()
+            .route_not_found("GET blocks/u1")
+            .route("GET users/u1", user_json("u1"))
+            .route_not_found("GET blocks/x1")
+            .route_not_found("GET users/x1");
+        let results: Vec<_> = fetcher(&executor).fetch("u1").await.collect().await;
+
+        let [Err(NotionError::RequestFailed(RequestError::InvalidRequest(msg)))] = &results[..]
+        else {
+            panic!("unexpected {results:?}");
+        };
+        assert!(msg.contains("ID of a user"), "{msg}");
+        assert_eq!(executor.requests(), ["GET blocks/u1", "GET users/u1"]);
+
+        // not a user either
+        let results: Vec<_> = fetcher(&executor).fetch("x1").await.collect().await;
+        let [Err(e)] = &results[..] else {
+            panic!("unexpected {results:?}");
+        };
+        assert_eq!(e.api_error_code(), Some(&ApiErrorCode::ObjectNotFound));
+        assert!(e.to_string().contains("not found"), "{e}");
+
+        // the IDs of `fetch_ids` are not checked
+        let executor = RecordingExecutor::default()
+            .route_not_found("GET blocks/u1")
+            .route("GET users/u1", user_json("u1"));
+        let results: Vec<_> = fetcher(&executor)
+            .fetch_ids(vec!["u1".to_owned()], false)
+            .await
+            .collect()
+            .await;
+        let [Err(e)] = &results[..] else {
+            panic!("unexpected {results:?}");
+        };
+        assert_eq!(e.api_error_code(), Some(&ApiErrorCode::ObjectNotFound));
+        assert_eq!(executor.requests(), ["GET blocks/u1"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skip_inaccessible() {
+        let mut root = block_json("root", "workspace", "paragraph");
+        root["has_children"] = true.into();
+        let children = list_json(
+            vec![
+                block_json("p1", "root", "child_page"),
+                block_json("b1", "root", "paragraph"),
+                block_json("p2", "root", "child_page"),
+            ],
+            None,
+        );
+        let executor = RecordingExecutor::default()
+            .route("GET blocks/root", root)
+            .route("GET blocks/root/children", children.clone())
+            .route_not_found("GET pages/p2")
+            .route_status(
+                "GET pages/p1",
+                403,
+                json!({
+                    "object": "error",
+                    "status": 403,
+                    "code": "restricted_resource",
+                    "message": "no access",
+                }),
+            );
+        let events: Vec<_> = fetcher(&executor)
+            .with_options(FetcherOptions {
+                object_types: vec![ObjectType::Block],
+                ..Default::default()
+            })
+            .fetch_events("root")
+            .await
+            .collect()
+            .await;
+        let mut skipped: Vec<_> = events
+            .iter()
+            .filter_map(|x| match x {
+                SyncEvent::Skipped { key, .. } => Some(key.to_string()),
+                SyncEvent::Error(e) => panic!("unexpected {e}"),
+                _ => None,
+            })
+            .collect();
+        skipped.sort();
+        assert_eq!(skipped, ["page-p1", "page-p2"]);
+        let objects = events
+            .iter()
+            .filter(|x| matches!(x, SyncEvent::Object(_)))
+            .count();
+        assert_eq!(objects, 4);
+
+        // a rejected token aborts the fetch in any error mode
+        let executor = RecordingExecutor::default().route_status(
+            "GET blocks/root",
+            401,
+            json!({ "object": "error", "status": 401, "code": "unauthorized" }),
+        );
+        let (events, cancel) = fetcher(&executor).fetch_cancellable("root").await;
+        let results: Vec<_> = events.collect().await;
+        assert!(matches!(results[..], [SyncEvent::Error(_)]), "{results:?}");
+        assert!(cancel.is_cancelled());
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test(start_paused = true)]
+    async fn otel_spans() {
+        use opentelemetry::{trace::Status, Value};
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        opentelemetry::global::set_tracer_provider(provider);
+
+        let executor = ScriptedExecutor::new([
+            retry_after(0),
+            response(200, page_json("otel-p1", "workspace")),
+            response(200, list_json(vec![], None)),
+            response(200, list_json(vec![], None)),
+        ]);
+        let task = Task::new(ReqType::Page("otel-p1".to_owned()));
+        let objects = fetch_task(&fetcher(&executor), task).await;
+        assert_eq!(objects.len(), 1);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let attr = |span: &opentelemetry_sdk::trace::SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|x| x.key.as_str() == key)
+                .map(|x| x.value.clone())
+        };
+        let page = spans
+            .iter()
+            .find(|x| attr(x, "notion.object_id") == Some(Value::from("otel-p1")))
+            .unwrap();
+        assert_eq!(page.name, "page");
+        assert_eq!(page.status, Status::Unset);
+        assert_eq!(attr(page, "notion.retry_count"), Some(Value::I64(1)));
+        assert!(attr(page, "notion.duration_ms").is_some());
+        assert_eq!(page.events.len(), 1);
+
+        let page_cx = page.span_context.clone();
+        let mut children: Vec<_> = spans
+            .iter()
+            .filter(|x| x.parent_span_id == page_cx.span_id())
+            .map(|x| {
+                assert_eq!(x.span_context.trace_id(), page_cx.trace_id());
+                assert_eq!(attr(x, "notion.retry_count"), Some(Value::I64(0)));
+                x.name.to_string()
+            })
+            .collect();
+        children.sort();
+        assert_eq!(children, ["block_children", "comments"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn filter_properties() {
+        let executor = RecordingExecutor::default()
+            .route(
+                "GET pages/p1?filter_properties=title",
+                page_json("p1", "workspace"),
+            )
+            .route(
+                "GET blocks/p1/children",
+                list_json(vec![block_json("d1", "p1", "child_database")], None),
+            )
+            .route("GET databases/d1", database_json("d1", "p1"))
+            .route(
+                "POST databases/d1/query?filter_properties=title",
+                list_json(vec![], None),
+            )
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+
+        let fetcher = fetcher(&executor).with_options(FetcherOptions {
+            filter_properties: vec!["title".to_owned()],
+            ..Default::default()
+        });
+        let task = Task::new(ReqType::Page("p1".to_owned()));
+        let objects = fetch_task(&fetcher, task).await;
+
+        assert!(objects.iter().all(|x| x.is_ok()), "{objects:?}");
+        assert_eq!(objects.len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn version_mismatch() {
+        let with_version = |body: serde_json::Value| {
+            let mut res = response(200, body);
+            res.headers_mut()
+                .insert("Notion-Version", "2025-09-03".parse().unwrap());
+            res
+        };
+        let executor = ScriptedExecutor::new([
+            with_version(page_json("p1", "workspace")),
+            with_version(list_json(vec![], None)),
+        ]);
+        let fetcher = fetcher(&executor).with_options(FetcherOptions {
+            object_types: vec![ObjectType::Page, ObjectType::Block],
+            ..Default::default()
+        });
+        let (res_tx, res_rx) = fetcher.event_channel();
+        fetcher.spawn_task(Task::new(ReqType::Page("p1".to_owned())), res_tx);
+        let events: Vec<_> = res_rx.collect().await;
+
+        let mismatches: Vec<_> = events
+            .iter()
+            .filter_map(|x| match x {
+                SyncEvent::VersionMismatch {
+                    requested,
+                    responded,
+                } => Some((requested.as_str(), responded.as_str())),
+                _ => None,
+            })
+            .collect();
+        // reported once
+        assert_eq!(mismatches, [("2022-06-28", "2025-09-03")]);
+        assert!(events.iter().any(|x| matches!(
+            x,
+            SyncEvent::Object(FetchedObject {
+                object: AnyObject::Page(_),
+                ..
+            })
+        )));
+
+        // the same version as requested
+        let executor = ScriptedExecutor::new([with_version(page_json("p1", "workspace"))]);
+        let api = Api::with_executor("token", executor)
+            .with_version("2025-09-03")
+            .unwrap();
+        api.get_object::<crate::Page>("p1").await.unwrap();
+        assert!(api.take_version_mismatches().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pause_resume() {
+        let executor = ScriptedExecutor::new([response(200, block_json("b1", "p1", "paragraph"))]);
+        let fetcher = fetcher(&executor);
+        let control = fetcher.control();
+        control.pause();
+        assert!(control.is_paused());
+
+        let (res_tx, res_rx) = fetcher.event_channel();
+        fetcher.spawn_task(block_task("b1"), res_tx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(executor.calls(), 0);
+
+        control.resume();
+        let results: Vec<_> = res_rx
+            .filter_map(|x| ready(x.into_result()))
+            .collect()
+            .await;
+        assert!(matches!(&results[..], [Ok(AnyObject::Block(_))]));
+        assert_eq!(executor.calls(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel() {
+        let executor = ScriptedExecutor::new([response(200, block_json("b1", "p1", "paragraph"))]);
+        let fetcher = fetcher(&executor);
+        // keep the request waiting
+        fetcher.control().pause();
+
+        let (events, cancel) = fetcher.fetch_cancellable("b1").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel.cancel();
+        assert!(cancel.is_cancelled());
+
+        let events: Vec<_> = tokio::time::timeout(Duration::from_secs(5), events.collect())
+            .await
+            .unwrap();
+        assert!(events.is_empty(), "{events:?}");
+        assert_eq!(executor.calls(), 0);
+
+        // the other fetches aren't cancelled
+        fetcher.control().resume();
+        let results: Vec<_> = fetcher.fetch("b1").await.collect().await;
+        assert!(matches!(&results[..], [Ok(AnyObject::Block(_))]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn enqueue_closed() {
+        let executor = RecordingExecutor::default();
+        let fetcher = fetcher(&executor);
+        let (mut task_tx, task_rx) = futures::channel::mpsc::channel(1);
+        drop(task_rx);
+        assert!(fetcher
+            .enqueue(&mut task_tx, block_task("b1"))
+            .await
+            .is_err());
+        assert_eq!(fetcher.pending.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn query_unexpected_objects() {
+        let executor = RecordingExecutor::default()
+            .route(
+                "POST databases/d1/query",
+                list_json(
+                    vec![
+                        block_json("b1", "d1", "paragraph"),
+                        page_json("p1", "d1"),
+                        user_json("u1"),
+                    ],
+                    None,
+                ),
+            )
+            .route("GET blocks/p1/children", list_json(vec![], None))
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+        let fetcher = fetcher(&executor);
+        let task = Task {
+            descend: false,
+            ..Task::new(ReqType::DatabaseQuery(PaginationInfo::new::<
+                ObjectList<AnyObject>,
+            >(
+                fetcher.api.base_url(), "d1"
+            )))
+        };
+        let (res_tx, res_rx) = fetcher.event_channel();
+        fetcher.spawn_task(task, res_tx);
+        let events: Vec<_> = res_rx.collect().await;
+
+        let skipped: Vec<_> = events
+            .iter()
+            .filter_map(|x| match x {
+                SyncEvent::Skipped { key, .. } => Some(key.to_string()),
+                SyncEvent::Error(e) => panic!("unexpected {e}"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(skipped, ["block-b1", "user-u1"]);
+        assert!(events.iter().any(|x| matches!(
+            x,
+            SyncEvent::Object(FetchedObject {
+                object: AnyObject::Page(_),
+                ..
+            })
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn query_pagination() {
+        let executor = RecordingExecutor::default()
+            .route(
+                "POST databases/d1/query",
+                list_json(vec![page_json("p1", "d1")], Some("c2")),
+            )
+            .route(
+                "POST databases/d1/query start_cursor=c2",
+                list_json(vec![page_json("p2", "d1")], None),
+            );
+        let fetcher = fetcher(&executor).should_descend(|_| false);
+        let task = Task::new(ReqType::DatabaseQuery(PaginationInfo::new::<
+            ObjectList<AnyObject>,
+        >(
+            fetcher.api.base_url(), "d1"
+        )));
+        let mut ids: Vec<_> = fetch_task(&fetcher, task)
+            .await
+            .into_iter()
+            .map(|x| x.unwrap().key().to_string())
+            .collect();
+        ids.sort();
+
+        assert_eq!(ids, ["page-p1", "page-p2"]);
+        // the cursor is sent in the body rather than the query string
+        assert_eq!(
+            executor.requests(),
+            ["POST databases/d1/query", "POST databases/d1/query"]
+        );
+        assert_eq!(executor.bodies()[1]["start_cursor"], "c2");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn page_size() {
+        let executor = RecordingExecutor::default()
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route(
+                "GET blocks/p1/children?page_size=1",
+                list_json(vec![block_json("d1", "p1", "child_database")], None),
+            )
+            .route("GET databases/d1", database_json("d1", "p1"))
+            .route(
+                "POST databases/d1/query",
+                list_json(vec![page_json("p2", "d1")], Some("c2")),
+            )
+            .route(
+                "POST databases/d1/query start_cursor=c2",
+                list_json(vec![page_json("p3", "d1")], None),
+            )
+            .route(
+                "GET comments?block_id=p1&page_size=1",
+                list_json(vec![], None),
+            );
+
+        let fetcher = fetcher(&executor)
+            .with_page_size(1)
+            .should_descend(|obj| !matches!(obj, AnyObject::Page(x) if x.id() != "p1"));
+        let task = Task::new(ReqType::Page("p1".to_owned()));
+        let mut ids: Vec<_> = fetch_task(&fetcher, task)
+            .await
+            .into_iter()
+            .map(|x| x.unwrap().key().to_string())
+            .collect();
+        ids.sort();
+
+        assert_eq!(
+            ids,
+            ["block-d1", "database-d1", "page-p1", "page-p2", "page-p3"]
+        );
+        // in the query string of GET, and in the body of POST, including the
+        // next pages
+        assert_eq!(
+            executor.bodies(),
+            [
+                json!({ "page_size": 1 }),
+                json!({ "page_size": 1, "start_cursor": "c2" }),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetch_users() {
+        let executor = RecordingExecutor::default()
+            .route("GET users", list_json(vec![user_json("u1")], Some("c2")))
+            .route(
+                "GET users?start_cursor=c2",
+                list_json(vec![user_json("u2")], None),
+            );
+        let users = fetcher(&executor).fetch_users().await.unwrap();
+        let ids: Vec<_> = users.iter().map(|x| x.id()).collect();
+        assert_eq!(ids, ["u1", "u2"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetch_changed() {
+        let edited = |mut js: serde_json::Value, time: &str| {
+            js["last_edited_time"] = json!(time);
+            js
+        };
+        let executor = RecordingExecutor::default()
+            .route(
+                "POST search",
+                list_json(
+                    vec![edited(
+                        page_json("p1", "workspace"),
+                        "2024-08-03T00:00:00.000Z",
+                    )],
+                    Some("c2"),
+                ),
+            )
+            .route(
+                "POST search start_cursor=c2",
+                list_json(
+                    vec![
+                        edited(page_json("p2", "p1"), "2024-08-02T00:00:00.000Z"),
+                        edited(page_json("p3", "workspace"), "2024-07-01T00:00:00.000Z"),
+                    ],
+                    Some("c3"),
+                ),
+            )
+            .route("GET blocks/p1", block_json("p1", "workspace", "child_page"))
+            .route("GET blocks/p2", block_json("p2", "p1", "child_page"))
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route("GET pages/p2", page_json("p2", "p1"))
+            .route(
+                "GET blocks/p1/children",
+                list_json(vec![block_json("p2", "p1", "child_page")], None),
+            )
+            .route("GET blocks/p2/children", list_json(vec![], None))
+            .route("GET comments?block_id=p1", list_json(vec![], None))
+            .route("GET comments?block_id=p2", list_json(vec![], None));
+
+        let since = "2024-08-01T00:00:00Z".parse().unwrap();
+        let mut keys: Vec<_> = fetcher(&executor)
+            .fetch_changed(since)
+            .await
+            .unwrap()
+            .map(|x| x.unwrap().key().to_string())
+            .collect()
+            .await;
+        keys.sort();
+
+        // p2 is reached from both p1 & itself, but emitted once
+        assert_eq!(keys, ["block-p1", "block-p2", "page-p1", "page-p2"]);
+        let requests = executor.requests();
+        // stops at p3, which is older than `since`, without the page c3
+        assert_eq!(requests.iter().filter(|x| *x == "POST search").count(), 2);
+        assert!(!requests.iter().any(|x| x.contains("p3")));
+        // the cursor is sent along with the sort
+        let sort = json!({ "direction": "descending", "timestamp": "last_edited_time" });
+        let bodies = executor.bodies();
+        assert_eq!(bodies[1], json!({ "sort": sort, "start_cursor": "c2" }));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetch_changed_concurrency() {
+        use std::sync::{atomic::AtomicUsize, Arc};
+
+        use futures::future::BoxFuture;
+        use reqwest::{Request, Response};
+
+        // serves the routes slowly, recording the max requests in flight
+        #[derive(Clone)]
+        struct SlowExecutor {
+            inner: RecordingExecutor,
+            in_flight: Arc<AtomicUsize>,
+            max_in_flight: Arc<AtomicUsize>,
+        }
+
+        impl HttpExecutor for SlowExecutor {
+            fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+                let res = self.inner.execute(request);
+                Box::pin(async move {
+                    let n = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    self.max_in_flight.fetch_max(n, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                    res.await
+                })
+            }
+        }
+
+        let mut page = page_json("p1", "workspace");
+        page["last_edited_time"] = json!("2024-08-02T00:00:00.000Z");
+        let mut inner = RecordingExecutor::default()
+            .route("POST search", list_json(vec![page], None))
+            .route("GET blocks/p1", block_json("p1", "workspace", "child_page"))
+            .route("GET pages/p1", page_json("p1", "workspace"))
+            .route("GET blocks/p1/children", list_json(vec![], None))
+            .route("GET comments?block_id=p1", list_json(vec![], None));
+        for i in 0..4 {
+            let id = format!("b{i}");
+            inner = inner.route(
+                &format!("GET blocks/{id}"),
+                block_json(&id, "p2", "paragraph"),
+            );
+        }
+        let executor = SlowExecutor {
+            inner,
+            in_flight: Default::default(),
+            max_in_flight: Default::default(),
+        };
+        let fetcher = Fetcher::build(Api::with_executor("token", executor.clone()), 100, 100)
+            .with_concurrency(1);
+
+        // the search is sent while the blocks are being fetched
+        let ids = (0..4).map(|i| format!("b{i}")).collect();
+        let blocks = fetcher.fetch_ids(ids, false).await;
+        let since = "2024-08-01T00:00:00Z".parse().unwrap();
+        let changed = fetcher.fetch_changed(since).await.unwrap();
+        let results: Vec<_> = blocks.chain(changed).collect().await;
+
+        assert!(results.iter().all(|x| x.is_ok()), "{results:?}");
+        assert_eq!(results.len(), 6);
+        assert_eq!(executor.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn title_mismatch() {
+        let mut page = page_json("p2", "p1");
+        page["properties"] = json!({
+            "Name": {
+                "id": "title",
+                "type": "title",
+                "title": [rich_text_json("New")],
+            },
+        });
+        let mut block = block_json("p2", "p1", "child_page");
+        block["child_page"]["title"] = json!("Old");
+        let executor = RecordingExecutor::default()
+            .route("GET blocks/p1/children", list_json(vec![block], None))
+            .route("GET pages/p2", page);
+
+        let fetcher = fetcher(&executor).with_options(FetcherOptions {
+            object_types: vec![ObjectType::Block, ObjectType::Page],
+            ..Default::default()
+        });
+        let task = Task {
+            descend: false,
+            ..Task::new(ReqType::BlockChildren(PaginationInfo::new::<
+                ObjectList<Block>,
+            >(
+                fetcher.api.base_url(), "p1"
+            )))
+        };
+        let (res_tx, res_rx) = fetcher.event_channel();
+        fetcher.spawn_task(task, res_tx);
+        let events: Vec<_> = res_rx.collect().await;
+
+        let mismatches: Vec<_> = events
+            .iter()
+            .filter_map(|x| match x {
+                SyncEvent::TitleMismatch {
+                    id,
+                    block_title,
+                    title,
+                } => Some((id.as_str(), block_title.as_str(), title.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(mismatches, [("p2", "Old", "New")]);
+        assert_eq!(events.len(), 3, "{events:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lenient_lists() {
+        let mut malformed = block_json("b2", "p1", "paragraph");
+        malformed.as_object_mut().unwrap().remove("created_time");
+        let children = list_json(
+            vec![
+                block_json("b1", "p1", "paragraph"),
+                malformed,
+                block_json("b3", "p1", "paragraph"),
+            ],
+            None,
+        );
+        let executor = RecordingExecutor::default().route("GET blocks/p1/children", children);
+        let task = |fetcher: &Fetcher| Task {
+            descend: false,
+            parent_id: Some("p1".to_owned()),
+            ..Task::new(ReqType::BlockChildren(PaginationInfo::new::<
+                ObjectList<Block>,
+            >(
+                fetcher.api.base_url(), "p1"
+            )))
+        };
+
+        // the whole page fails by default
+        let fetcher = fetcher(&executor);
+        let results = fetch_task(&fetcher, task(&fetcher)).await;
+        assert!(matches!(results[..], [Err(_)]), "{results:?}");
+
+        let fetcher = fetcher.with_options(FetcherOptions {
+            lenient_lists: true,
+            ..Default::default()
+        });
+        let (res_tx, res_rx) = fetcher.event_channel();
+        fetcher.spawn_task(task(&fetcher), res_tx);
+        let events: Vec<_> = res_rx.collect().await;
+
+        let invalid: Vec<_> = events
+            .iter()
+            .filter_map(|x| match x {
+                SyncEvent::InvalidElement {
+                    parent_id, index, ..
+                } => Some((parent_id.as_str(), *index)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(invalid, [("p1", 1)]);
+        let blocks: Vec<_> = events
+            .iter()
+            .filter_map(|x| match x {
+                SyncEvent::Object(FetchedObject {
+                    object: AnyObject::Block(block),
+                    ..
+                }) => Some((block.id(), block.child_index)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(blocks, [("b1", 0), ("b3", 2)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follow_mentions() {
+        let mention = |r#type: &str, id: &str| {
+            mention_json(json!({ "type": r#type, r#type: { "id": id } }), id)
+        };
+        let mut b1 = block_json("b1", "p1", "paragraph");
+        b1["paragraph"]["rich_text"] = json!([mention("page", "p2"), mention("page", "p1")]);
+        let mut b2 = block_json("b2", "p1", "paragraph");
+        b2["paragraph"]["rich_text"] = json!([mention("page", "p2"), mention("user", "u1")]);
+        let executor = || {
+            RecordingExecutor::default()
+                .route(
+                    "GET blocks/p1/children",
+                    list_json(vec![b1.clone(), b2.clone()], None),
+                )
+                .route("GET pages/p1", page_json("p1", "w"))
+                .route("GET pages/p2", page_json("p2", "w"))
+                .route("GET blocks/p2/children", list_json(vec![], None))
+        };
+
+        let options = |follow_mentions| FetcherOptions {
+            follow_mentions,
+            object_types: vec![ObjectType::Page],
+            ..Default::default()
+        };
+        let fetch = |follow_mentions| {
+            let executor = executor();
+            let fetcher = fetcher(&executor).with_options(options(follow_mentions));
+            async move {
+                let results = fetch_task(&fetcher, Task::new(ReqType::Page("p1".to_owned()))).await;
+                let pages: Vec<_> = results
+                    .iter()
+                    .map(|x| x.as_ref().unwrap().id().to_owned())
+                    .collect();
+                (pages, executor.requests())
+            }
+        };
+
+        // not followed by default
+        let (pages, _) = fetch(false).await;
+        assert_eq!(pages, ["p1"]);
+
+        let (pages, requests) = fetch(true).await;
+        assert_eq!(pages, ["p1", "p2"]);
+        // the mentioned page is fetched once, and the root isn't again
+        assert_eq!(
+            requests,
+            [
+                "GET blocks/p1/children",
+                "GET blocks/p2/children",
+                "GET pages/p1",
+                "GET pages/p2",
+            ]
+        );
+    }
+
+    #[test]
+    fn retry_jitter_seed() {
+        let options = FetcherOptions {
+            max_retry_jitter: Duration::from_secs(1),
+            jitter_seed: Some(42),
+            ..Default::default()
+        };
+        let executor = ScriptedExecutor::new([]);
+        let delays = |fetcher: Fetcher| {
+            (1..=5)
+                .map(|i| fetcher.retry_delay(i, 0).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let delays1 = delays(fetcher(&executor).with_options(options.clone()));
+        let delays2 = delays(fetcher(&executor).with_options(options));
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let expected: Vec<_> = (1..=5)
+            .map(|i| {
+                Duration::from_secs(i) + rng.gen_range(Duration::ZERO..=Duration::from_secs(1))
+            })
+            .collect();
+        assert_eq!(delays1, expected);
+        assert_eq!(delays2, expected);
+        assert!(expected
+            .iter()
+            .zip(1..)
+            .all(|(x, i)| *x >= Duration::from_secs(i) && *x <= Duration::from_secs(i + 1)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn query_data_sources() {
+        let mut database = database_json("d1", "p1");
+        database["data_sources"] = json!([
+            { "id": "ds1", "name": "Tasks" },
+            { "id": "ds2", "name": "Archive" },
+        ]);
+        let executor = RecordingExecutor::default()
+            .route("GET databases/d1", database)
+            .route(
+                "POST data_sources/ds1/query",
+                list_json(vec![page_json("r1", "ds1")], None),
+            )
+            .route("POST data_sources/ds2/query", list_json(vec![], None))
+            .route("GET blocks/r1/children", list_json(vec![], None));
+
+        let task = Task::new(ReqType::Database("d1".to_owned()));
+        let objects = fetch_task(&fetcher(&executor), task).await;
+
+        assert_eq!(objects.len(), 2);
+        assert!(objects.iter().all(|x| x.is_ok()));
+        assert_eq!(
+            executor.requests(),
+            [
+                "GET blocks/r1/children",
+                "GET databases/d1",
+                "POST data_sources/ds1/query",
+                "POST data_sources/ds2/query",
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeat() {
+        let executor = ScriptedExecutor::new([
+            retry_after(1),
+            response(200, block_json("b1", "p1", "paragraph")),
+        ]);
+        let fetcher = fetcher(&executor).with_options(FetcherOptions {
+            heartbeat: Some(Duration::from_millis(300)),
+            max_retry_jitter: Duration::ZERO,
+            ..Default::default()
+        });
+        let events: Vec<_> = fetcher.fetch_events("b1").await.collect().await;
+
+        let (heartbeats, objects): (Vec<_>, Vec<_>) = events
+            .iter()
+            .partition(|x| matches!(x, SyncEvent::Heartbeat { .. }));
+        // one every 300ms while waiting 1s to retry
+        assert!(heartbeats.len() >= 3, "{events:?}");
+        assert!(heartbeats
+            .iter()
+            .all(|x| matches!(x, SyncEvent::Heartbeat { pending_tasks: 1 })));
+        assert!(matches!(
+            &objects[..],
+            [SyncEvent::Object(FetchedObject {
+                object: AnyObject::Block(_),
+                ..
+            })]
+        ));
+    }
+}
@@ -1,10 +1,32 @@
-pub use api::Api;
-pub use block::{Block, BlockType};
+pub use api::{
+    Api, ApiErrorCode, ChildrenSummary, CommentTarget, HttpExecutor, PagePatch, QueryBody,
+    QuerySort, RequestError, ReqwestExecutor, SearchFilter, SortDirection, MAX_PAGE_SIZE,
+};
+pub use block::{
+    blocks_to_markdown, Block, BlockTree, BlockType, BookmarkData, CodeBlock, ToDoData,
+};
 pub use comment::Comment;
-pub use database::Database;
-pub use fetcher::{AnyObject, Fetcher};
-pub use object::Object;
+pub use control::{CancelHandle, FetchControl};
+pub use database::{DataSourceRef, Database};
+pub use error::NotionError;
+pub use fetcher::{
+    merge_fetchers, AnyObject, ErrorMode, FetchedObject, Fetcher, FetcherOptions, SyncEvent,
+    SyncStats,
+};
+pub use misc::{
+    hyphenated_id, DateProperty, Icon, LinkKind, LinkRef, NotionDate, NotionFile, NotionFileData,
+    NotionFileType, Property, PropertyValue, SelectOption, ViewRef,
+};
+pub use object::{Object, ObjectKey, ObjectType, Parent, ParentType};
 pub use page::Page;
+pub use result_channel::OverflowPolicy;
+pub use rich_text::{
+    resolve_mentions, to_markdown, to_markdown_resolved, to_plain_text, Annotations, Color,
+    MentionResolver, MentionType, MentionTypeData, RichText, RichTextType, TemplateMentionDate,
+    TemplateMentionUser,
+};
+pub use transform::{apply_transforms, LowercasePropertyKeys, StripFileUrls, Transform};
+pub use user::{OwnerType, User, UserType};
 
 // objects
 mod block;
@@ -14,11 +36,245 @@ mod page;
 mod user;
 
 mod api;
+mod control;
 mod error;
 mod fetcher;
 mod misc;
 mod object;
+#[cfg(feature = "otel")]
+mod otel;
+mod result_channel;
 mod rich_text;
+mod transform;
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    //! Helpers shared by the unit tests of each module.
+
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use futures::future::BoxFuture;
+    use reqwest::{Request, Response};
+    use serde_json::{json, Value};
+
+    use crate::HttpExecutor;
+
+    /// An [`HttpExecutor`] which replies with pre-defined responses in order,
+    /// and counts the requests it has received.
+    #[derive(Clone, Default)]
+    pub(crate) struct ScriptedExecutor {
+        responses: Arc<Mutex<VecDeque<http::Response<String>>>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ScriptedExecutor {
+        pub(crate) fn new(responses: impl IntoIterator<Item = http::Response<String>>) -> Self {
+            Self {
+                responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+                calls: Default::default(),
+            }
+        }
+
+        pub(crate) fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl HttpExecutor for ScriptedExecutor {
+        fn execute(&self, _: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let res = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no more scripted responses");
+            Box::pin(async move { Ok(res.into()) })
+        }
+    }
+
+    /// An [`HttpExecutor`] which serves a mock tree of objects by request
+    /// path, and records every request it receives. Requests which aren't
+    /// routed get a 500 response which isn't a Notion error, i.e. a failed
+    /// request rather than a missing object, see
+    /// [`RecordingExecutor::route_not_found`].
+    #[derive(Clone, Default)]
+    pub(crate) struct RecordingExecutor {
+        routes: Arc<Mutex<HashMap<String, (u16, String)>>>,
+        requests: Arc<Mutex<Vec<String>>>,
+        bodies: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl RecordingExecutor {
+        /// Reply `body` to `request`, e.g. `"GET blocks/b1"`. A POST request
+        /// with a cursor in its body is routed by e.g. `"POST
+        /// databases/d1/query start_cursor=c2"` if it's routed, otherwise by
+        /// the request itself.
+        pub(crate) fn route(self, request: &str, body: Value) -> Self {
+            self.route_status(request, 200, body)
+        }
+
+        /// Reply the `object_not_found` error of Notion to `request`.
+        pub(crate) fn route_not_found(self, request: &str) -> Self {
+            let body = json!({
+                "object": "error",
+                "status": 404,
+                "code": "object_not_found",
+                "message": format!("not found: {request}"),
+            });
+            self.route_status(request, 404, body)
+        }
+
+        pub(crate) fn route_status(self, request: &str, status: u16, body: Value) -> Self {
+            self.routes
+                .lock()
+                .unwrap()
+                .insert(request.to_owned(), (status, body.to_string()));
+            self
+        }
+
+        /// The requests received so far, in the form of `"GET blocks/b1"`,
+        /// sorted since they may be sent concurrently.
+        pub(crate) fn requests(&self) -> Vec<String> {
+            let mut requests = self.requests.lock().unwrap().clone();
+            requests.sort();
+            requests
+        }
+
+        /// The JSON bodies of the requests received so far, in order.
+        pub(crate) fn bodies(&self) -> Vec<Value> {
+            self.bodies.lock().unwrap().clone()
+        }
+    }
+
+    impl HttpExecutor for RecordingExecutor {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+            let url = request.url();
+            let path = url.path().strip_prefix("/v1/").unwrap_or(url.path());
+            let key = match url.query() {
+                Some(query) => format!("{} {path}?{query}", request.method()),
+                None => format!("{} {path}", request.method()),
+            };
+            self.requests.lock().unwrap().push(key.clone());
+            let mut cursor_key = None;
+            if let Some(body) = request.body().and_then(|x| x.as_bytes()) {
+                let body: Value = serde_json::from_slice(body).unwrap_or_default();
+                if let Some(cursor) = body["start_cursor"].as_str() {
+                    cursor_key = Some(format!("{key} start_cursor={cursor}"));
+                }
+                self.bodies.lock().unwrap().push(body);
+            }
+
+            let routes = self.routes.lock().unwrap();
+            let (status, body) = cursor_key
+                .and_then(|x| routes.get(&x))
+                .or_else(|| routes.get(&key))
+                .cloned()
+                .unwrap_or_else(|| (500, format!("unrouted: {key}")));
+            Box::pin(async move { Ok(response(status, body).into()) })
+        }
+    }
+
+    pub(crate) fn response(status: u16, body: impl ToString) -> http::Response<String> {
+        http::Response::builder()
+            .status(status)
+            .body(body.to_string())
+            .unwrap()
+    }
+
+    pub(crate) fn retry_after(secs: u64) -> http::Response<String> {
+        http::Response::builder()
+            .status(429)
+            .header("Retry-After", secs)
+            .body(String::new())
+            .unwrap()
+    }
+
+    /// A plain text element of rich text, see [`RichText::text`].
+    ///
+    /// [`RichText::text`]: crate::RichText::text
+    pub(crate) fn rich_text_json(text: &str) -> Value {
+        serde_json::to_value(crate::RichText::text(text)).unwrap()
+    }
+
+    /// A mention element of rich text, e.g. `mention_json(json!({ "type":
+    /// "page", "page": { "id": "p1" } }), "p1")`.
+    pub(crate) fn mention_json(mention: Value, plain_text: &str) -> Value {
+        let mut js = rich_text_json(plain_text);
+        js.as_object_mut().unwrap().remove("text");
+        js["type"] = json!("mention");
+        js["mention"] = mention;
+        js
+    }
+
+    pub(crate) fn user_json(id: &str) -> Value {
+        json!({ "object": "user", "id": id })
+    }
+
+    pub(crate) fn block_json(id: &str, parent_id: &str, block_type: &str) -> Value {
+        let type_data = match block_type {
+            "child_page" | "child_database" => json!({ "title": id }),
+            _ => json!({ "rich_text": [], "color": "default" }),
+        };
+        json!({
+            "object": "block",
+            "id": id,
+            "parent": { "type": "block_id", "block_id": parent_id },
+            "created_time": "2024-08-01T00:00:00.000Z",
+            "created_by": user_json("user"),
+            "last_edited_time": "2024-08-01T00:00:00.000Z",
+            "last_edited_by": user_json("user"),
+            "archived": false,
+            "in_trash": false,
+            "has_children": false,
+            "type": block_type,
+            block_type: type_data,
+        })
+    }
+
+    pub(crate) fn page_json(id: &str, parent_id: &str) -> Value {
+        json!({
+            "object": "page",
+            "id": id,
+            "parent": { "type": "page_id", "page_id": parent_id },
+            "created_time": "2024-08-01T00:00:00.000Z",
+            "created_by": user_json("user"),
+            "last_edited_time": "2024-08-01T00:00:00.000Z",
+            "last_edited_by": user_json("user"),
+            "archived": false,
+            "in_trash": false,
+            "properties": {},
+            "url": format!("https://www.notion.so/{id}"),
+            "public_url": null,
+            "icon": null,
+            "cover": null,
+        })
+    }
+
+    pub(crate) fn database_json(id: &str, parent_id: &str) -> Value {
+        let mut database = page_json(id, parent_id);
+        database["object"] = json!("database");
+        database["is_inline"] = json!(false);
+        database["title"] = json!([]);
+        database["description"] = json!([]);
+        database
+    }
+
+    /// A list response with `results`, `next_cursor` is set if there are
+    /// more pages.
+    pub(crate) fn list_json(results: Vec<Value>, next_cursor: Option<&str>) -> Value {
+        json!({
+            "object": "list",
+            "type": "block",
+            "results": results,
+            "next_cursor": next_cursor,
+            "has_more": next_cursor.is_some(),
+        })
+    }
+}
@@ -2,13 +2,19 @@ use std::collections::BTreeMap;
 use std::ops::Deref;
 use std::{fmt::Display, str::FromStr};
 
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::serde_as;
 use serde_with::{DisplayFromStr, MapSkipError};
 use thiserror::Error;
 
+use crate::object::{ObjectKey, ObjectType};
+use crate::rich_text::RichText;
+use crate::user::User;
+
 /// Refer to:
 /// - [Property object](https://developers.notion.com/reference/property-object)
 /// - [Page properties](https://developers.notion.com/reference/page-property-values)
@@ -24,6 +30,102 @@ pub struct Property {
     pub type_data: BTreeMap<String, Value>,
 }
 
+impl Property {
+    /// Deserialize the value of the property if its type is `r#type`.
+    fn type_value<T: DeserializeOwned>(&self, r#type: &str) -> Option<T> {
+        if self.r#type != r#type {
+            return None;
+        }
+        serde_json::from_value(self.type_data.get(r#type)?.clone()).ok()
+    }
+
+    /// The value of a `title` property.
+    pub fn as_title(&self) -> Option<Vec<RichText>> {
+        self.type_value("title")
+    }
+
+    /// The value of a `created_time` property.
+    pub fn as_created_time(&self) -> Option<DateTime<Utc>> {
+        self.type_value("created_time")
+    }
+
+    /// The value of a `last_edited_time` property.
+    pub fn as_last_edited_time(&self) -> Option<DateTime<Utc>> {
+        self.type_value("last_edited_time")
+    }
+
+    /// The value of a `created_by` property.
+    pub fn as_created_by(&self) -> Option<User> {
+        self.type_value("created_by")
+    }
+
+    /// The value of a `last_edited_by` property.
+    pub fn as_last_edited_by(&self) -> Option<User> {
+        self.type_value("last_edited_by")
+    }
+
+    /// The typed value of a page property, by its `type`. The value of the
+    /// other types (e.g. formulas & rollups), or one failed to be parsed, is
+    /// [`PropertyValue::Other`].
+    pub fn value(&self) -> PropertyValue {
+        let t = self.r#type.as_str();
+        let value = match t {
+            "title" => self.type_value(t).map(PropertyValue::Title),
+            "rich_text" => self.type_value(t).map(PropertyValue::RichText),
+            "number" => self.type_value(t).map(PropertyValue::Number),
+            "select" => self.type_value(t).map(PropertyValue::Select),
+            "multi_select" => self.type_value(t).map(PropertyValue::MultiSelect),
+            "status" => self.type_value(t).map(PropertyValue::Status),
+            "date" => self.type_value(t).map(PropertyValue::Date),
+            "checkbox" => self.type_value(t).map(PropertyValue::Checkbox),
+            "url" => self.type_value(t).map(PropertyValue::Url),
+            "email" => self.type_value(t).map(PropertyValue::Email),
+            "phone_number" => self.type_value(t).map(PropertyValue::PhoneNumber),
+            "people" => self.type_value(t).map(PropertyValue::People),
+            "relation" => self
+                .type_value::<Vec<IdData>>(t)
+                .map(|x| PropertyValue::Relation(x.into_iter().map(|x| x.id).collect())),
+            _ => None,
+        };
+        value.unwrap_or_else(|| {
+            PropertyValue::Other(self.type_data.get(t).cloned().unwrap_or(Value::Null))
+        })
+    }
+}
+
+/// The typed value of a page property, see [`Property::value`].
+///
+/// Refer to: [Page properties](https://developers.notion.com/reference/page-property-values)
+#[derive(Debug, Clone)]
+pub enum PropertyValue {
+    Title(Vec<RichText>),
+    RichText(Vec<RichText>),
+    Number(Option<f64>),
+    Select(Option<SelectOption>),
+    MultiSelect(Vec<SelectOption>),
+    Status(Option<SelectOption>),
+    Date(Option<DateProperty>),
+    Checkbox(bool),
+    Url(Option<String>),
+    Email(Option<String>),
+    PhoneNumber(Option<String>),
+    People(Vec<User>),
+    /// The IDs of the related pages.
+    Relation(Vec<String>),
+    /// The raw JSON of the other types, see [`Property::value`].
+    Other(Value),
+}
+
+/// An option of a select, multi-select or status property.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SelectOption {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Icon {
@@ -31,6 +133,15 @@ pub enum Icon {
     File(NotionFile),
 }
 
+impl Icon {
+    /// Rewrite the URL of a file icon with `f`.
+    pub fn map_url(&mut self, f: &dyn Fn(&str) -> String) {
+        if let Icon::File(file) = self {
+            file.map_url(f);
+        }
+    }
+}
+
 impl Display for Icon {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = serde_json::to_string(self).unwrap();
@@ -45,6 +156,24 @@ pub enum NotionFile {
     External { external: UrlData },
 }
 
+impl NotionFile {
+    pub fn url(&self) -> &str {
+        match self {
+            NotionFile::File { file } => &file.url,
+            NotionFile::External { external } => &external.url,
+        }
+    }
+
+    /// Rewrite the URL with `f`, e.g. to point at a mirror of the file.
+    pub fn map_url(&mut self, f: &dyn Fn(&str) -> String) {
+        let url = match self {
+            NotionFile::File { file } => &mut file.url,
+            NotionFile::External { external } => &mut external.url,
+        };
+        *url = f(url);
+    }
+}
+
 impl Display for NotionFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = serde_json::to_string(self).unwrap();
@@ -92,14 +221,59 @@ pub struct UnsupportFileTypeError(String);
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct IdData {
-    id: String,
+    pub id: String,
 }
 
+/// Refer to: [Date](https://developers.notion.com/reference/page-property-values#date)
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct DateProperty {
-    start: DateTime<Utc>,
-    end: Option<DateTime<Utc>>,
-    // optional field `time_zone` is ignored
+    pub start: NotionDate,
+    pub end: Option<NotionDate>,
+    /// An IANA time zone name, e.g. `America/New_York`.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+}
+
+// Format the date (range) in `time_zone` if it's set, e.g.
+// `2024-08-01 09:00 CST → 2024-08-01 10:30 CST`.
+impl Display for DateProperty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tz = self.time_zone.as_deref().and_then(|x| x.parse::<Tz>().ok());
+        write!(f, "{}", self.start.format(tz))?;
+        if let Some(end) = self.end {
+            write!(f, " → {}", end.format(tz))?;
+        }
+        Ok(())
+    }
+}
+
+/// A date, with or without time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum NotionDate {
+    DateTime(DateTime<FixedOffset>),
+    /// A date time without offset, which is in the `time_zone` of the
+    /// [`DateProperty`].
+    LocalDateTime(NaiveDateTime),
+    Date(NaiveDate),
+}
+
+impl NotionDate {
+    fn format(&self, tz: Option<Tz>) -> String {
+        const DATE_TIME: &str = "%Y-%m-%d %H:%M";
+        match (self, tz) {
+            (NotionDate::DateTime(t), Some(tz)) => {
+                t.with_timezone(&tz).format("%Y-%m-%d %H:%M %Z").to_string()
+            }
+            (NotionDate::DateTime(t), None) => t.format("%Y-%m-%d %H:%M %:z").to_string(),
+            (NotionDate::LocalDateTime(t), Some(tz)) => match t.and_local_timezone(tz).earliest() {
+                Some(t) => t.format("%Y-%m-%d %H:%M %Z").to_string(),
+                None => t.format(DATE_TIME).to_string(),
+            },
+            (NotionDate::LocalDateTime(t), None) => t.format(DATE_TIME).to_string(),
+            (NotionDate::Date(d), _) => d.format("%Y-%m-%d").to_string(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -107,6 +281,157 @@ pub struct UrlData {
     url: String,
 }
 
+impl UrlData {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// A database view referenced by a notion link, such as
+/// `https://www.notion.so/{database_id}?v={view_id}`.
+///
+/// The public API doesn't expose views or their configs (table, board,
+/// calendar, etc.), so the IDs found in links are all we can capture.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ViewRef {
+    pub view_id: String,
+    pub database_id: String,
+    pub url: String,
+}
+
+impl ViewRef {
+    pub fn parse(url: &str) -> Option<Self> {
+        let parsed = Url::parse(url).ok()?;
+        let host = parsed.host_str()?;
+        if !(host == "notion.so" || host.ends_with(".notion.so") || host.ends_with(".notion.site"))
+        {
+            return None;
+        }
+
+        let (_, view_id) = parsed.query_pairs().find(|(k, _)| k == "v")?;
+        let last = parsed.path_segments()?.next_back()?;
+        let database_id = last.rsplit_once('-').map(|(_, id)| id).unwrap_or(last);
+
+        Some(Self {
+            view_id: hyphenated_id(&view_id)?,
+            database_id: hyphenated_id(database_id)?,
+            url: url.to_owned(),
+        })
+    }
+
+    /// Find all view links in the string values of `value`, recursively.
+    pub fn find_all(value: &Value) -> Vec<Self> {
+        let mut views = Vec::new();
+        find_views(value, &mut views);
+        views.sort();
+        views.dedup();
+        views
+    }
+}
+
+fn find_views(value: &Value, views: &mut Vec<ViewRef>) {
+    match value {
+        Value::String(s) => views.extend(ViewRef::parse(s)),
+        Value::Array(arr) => arr.iter().for_each(|x| find_views(x, views)),
+        Value::Object(map) => map.values().for_each(|x| find_views(x, views)),
+        _ => (),
+    }
+}
+
+/// A reference from an object to a page or database, i.e. a mention, a
+/// relation property value, or a link to page block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinkRef {
+    pub target: ObjectKey,
+    pub kind: LinkKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    Mention,
+    Relation,
+    LinkToPage,
+}
+
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = serde_json::to_string(self).unwrap();
+        s.unquotes().fmt(f)
+    }
+}
+
+impl LinkRef {
+    /// Find all links in `value`, i.e. the JSON of an object, recursively.
+    pub fn find_all(value: &Value) -> Vec<Self> {
+        let mut links = Vec::new();
+        find_links(value, &mut links);
+        links.sort();
+        links.dedup();
+        links
+    }
+}
+
+fn find_links(value: &Value, links: &mut Vec<LinkRef>) {
+    let link = |target, kind| LinkRef { target, kind };
+    match value {
+        Value::Array(arr) => arr.iter().for_each(|x| find_links(x, links)),
+        Value::Object(map) => {
+            // {"mention": {"type": "page", "page": {"id": "..."}}}
+            if let Some(target) = map.get("mention").and_then(|x| link_target(x, "")) {
+                links.push(link(target, LinkKind::Mention));
+            }
+            // {"type": "relation", "relation": [{"id": "..."}]}
+            if let (Some("relation"), Some(Value::Array(ids))) = (
+                map.get("type").and_then(|x| x.as_str()),
+                map.get("relation"),
+            ) {
+                let ids = ids.iter().filter_map(|x| x.get("id")?.as_str());
+                links.extend(
+                    ids.map(|id| link(ObjectKey::new(ObjectType::Page, id), LinkKind::Relation)),
+                );
+            }
+            // {"link_to_page": {"type": "page_id", "page_id": "..."}}
+            if let Some(target) = map.get("link_to_page").and_then(|x| link_target(x, "_id")) {
+                links.push(link(target, LinkKind::LinkToPage));
+            }
+            map.values().for_each(|x| find_links(x, links));
+        }
+        _ => (),
+    }
+}
+
+/// The target of `{"type": "page{suffix}", "page{suffix}": ...}`, or the
+/// database equivalent. The ID is either the value itself or its `id` field.
+fn link_target(value: &Value, suffix: &str) -> Option<ObjectKey> {
+    let r#type = value.get("type")?.as_str()?;
+    let object_type = match r#type.strip_suffix(suffix)? {
+        "page" => ObjectType::Page,
+        "database" => ObjectType::Database,
+        _ => return None,
+    };
+    let target = value.get(r#type)?;
+    let id = target.as_str().or_else(|| target.get("id")?.as_str())?;
+    Some(ObjectKey::new(object_type, id))
+}
+
+/// Convert an ID in a notion link (32 hex digits) to the hyphenated form used
+/// by the API, `None` if it isn't an ID.
+pub fn hyphenated_id(id: &str) -> Option<String> {
+    let id = id.replace('-', "");
+    if id.len() != 32 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &id[..8],
+        &id[8..12],
+        &id[12..16],
+        &id[16..20],
+        &id[20..]
+    ))
+}
+
 pub(crate) trait Unquotes {
     fn unquotes(&self) -> &str;
 }
@@ -137,7 +462,11 @@ where
 mod tests {
     use chrono::{DateTime, FixedOffset};
 
-    use super::NotionFile;
+    use serde_json::json;
+
+    use crate::{Object, ObjectKey, ObjectType};
+
+    use super::{LinkKind, LinkRef, NotionFile, Property, PropertyValue, ViewRef};
 
     #[test]
     fn notion_file() {
@@ -170,4 +499,172 @@ mod tests {
         let url = "https://images.unsplash.com/photo-1525310072745-f49212b5ac6d?ixlib=rb-1.2.1&ixid=MnwxMjA3fDB8MHxwaG90by1wYWdlfHx8fGVufDB8fHx8&auto=format&fit=crop&w=1065&q=80";
         assert!(matches!(file, NotionFile::External { external } if external.url == url));
     }
+
+    #[test]
+    fn view_ref() {
+        let url = "https://www.notion.so/myspace/Tasks-0c5f3bb8e3b44b8a9b4e3f2cfa0c6a21?v=3d1e2a6c9b8f4e7da5c2b1f0e9d8c7b6&pvs=4";
+        let view = ViewRef::parse(url).unwrap();
+        assert_eq!(view.database_id, "0c5f3bb8-e3b4-4b8a-9b4e-3f2cfa0c6a21");
+        assert_eq!(view.view_id, "3d1e2a6c-9b8f-4e7d-a5c2-b1f0e9d8c7b6");
+
+        assert!(ViewRef::parse("https://example.com/0c5f3bb8e3b44b8a9b4e3f2cfa0c6a21?v=3d1e2a6c9b8f4e7da5c2b1f0e9d8c7b6").is_none());
+        assert!(ViewRef::parse("https://www.notion.so/0c5f3bb8e3b44b8a9b4e3f2cfa0c6a21").is_none());
+
+        let data = json!({
+            "rich_text": [{ "href": url }, { "href": "https://www.notion.so/abc" }],
+            "url": url,
+        });
+        assert_eq!(ViewRef::find_all(&data), vec![view]);
+    }
+
+    #[test]
+    fn time_and_user_properties() {
+        let prop: Property = serde_json::from_value(json!({
+            "id": "a%3Aq",
+            "type": "created_time",
+            "created_time": "2024-08-01T08:30:00.000Z",
+        }))
+        .unwrap();
+        let t = DateTime::parse_from_rfc3339("2024-08-01T08:30:00.000Z").unwrap();
+        assert_eq!(prop.as_created_time(), Some(t.to_utc()));
+        assert_eq!(prop.as_last_edited_time(), None);
+
+        let prop: Property = serde_json::from_value(json!({
+            "id": "b%3Aq",
+            "type": "last_edited_time",
+            "last_edited_time": "2024-08-02T08:30:00.000Z",
+        }))
+        .unwrap();
+        let t = DateTime::parse_from_rfc3339("2024-08-02T08:30:00.000Z").unwrap();
+        assert_eq!(prop.as_last_edited_time(), Some(t.to_utc()));
+
+        let prop: Property = serde_json::from_value(json!({
+            "id": "c%3Aq",
+            "type": "created_by",
+            "created_by": { "object": "user", "id": "u1", "name": "Ann", "type": "person" },
+        }))
+        .unwrap();
+        let user = prop.as_created_by().unwrap();
+        assert_eq!(user.id(), "u1");
+        assert_eq!(user.name.as_deref(), Some("Ann"));
+        assert!(prop.as_last_edited_by().is_none());
+
+        let prop: Property = serde_json::from_value(json!({
+            "id": "d%3Aq",
+            "type": "last_edited_by",
+            "last_edited_by": { "object": "user", "id": "u2" },
+        }))
+        .unwrap();
+        assert_eq!(prop.as_last_edited_by().unwrap().id(), "u2");
+
+        // database schema properties have no value
+        let prop: Property = serde_json::from_value(json!({
+            "id": "e%3Aq",
+            "name": "Created",
+            "type": "created_time",
+            "created_time": {},
+        }))
+        .unwrap();
+        assert_eq!(prop.as_created_time(), None);
+    }
+
+    #[test]
+    fn property_value() {
+        let value = |js: serde_json::Value| serde_json::from_value::<Property>(js).unwrap().value();
+
+        let v = value(json!({ "id": "a", "type": "number", "number": 1.5 }));
+        assert!(matches!(v, PropertyValue::Number(Some(x)) if x == 1.5));
+        let v = value(json!({ "id": "a", "type": "number", "number": null }));
+        assert!(matches!(v, PropertyValue::Number(None)));
+
+        let v = value(json!({
+            "id": "b",
+            "type": "select",
+            "select": { "id": "s1", "name": "Done", "color": "green" },
+        }));
+        let PropertyValue::Select(Some(option)) = v else {
+            panic!("unexpected {v:?}");
+        };
+        assert_eq!(option.name, "Done");
+        assert_eq!(option.color.as_deref(), Some("green"));
+
+        let v = value(json!({
+            "id": "c",
+            "type": "multi_select",
+            "multi_select": [{ "name": "a" }, { "name": "b" }],
+        }));
+        assert!(matches!(v, PropertyValue::MultiSelect(x) if x.len() == 2));
+
+        let v = value(json!({ "id": "d", "type": "checkbox", "checkbox": true }));
+        assert!(matches!(v, PropertyValue::Checkbox(true)));
+
+        let v = value(json!({ "id": "e", "type": "url", "url": "https://notion.so" }));
+        assert!(matches!(v, PropertyValue::Url(Some(x)) if x == "https://notion.so"));
+
+        let v = value(json!({
+            "id": "f",
+            "type": "relation",
+            "relation": [{ "id": "p1" }, { "id": "p2" }],
+            "has_more": false,
+        }));
+        assert!(matches!(v, PropertyValue::Relation(x) if x == ["p1", "p2"]));
+
+        let v = value(json!({
+            "id": "g",
+            "type": "date",
+            "date": { "start": "2024-08-01", "end": null },
+        }));
+        assert!(matches!(v, PropertyValue::Date(Some(_))));
+
+        let v = value(json!({
+            "id": "h",
+            "type": "people",
+            "people": [{ "object": "user", "id": "u1" }],
+        }));
+        assert!(matches!(v, PropertyValue::People(x) if x.len() == 1));
+
+        let formula = json!({ "type": "number", "number": 2 });
+        let v = value(json!({ "id": "i", "type": "formula", "formula": formula }));
+        assert!(matches!(v, PropertyValue::Other(x) if x == formula));
+
+        // a schema property of a database
+        let v = value(json!({ "id": "j", "name": "Done", "type": "checkbox", "checkbox": {} }));
+        assert!(matches!(v, PropertyValue::Other(_)));
+    }
+
+    #[test]
+    fn link_ref() {
+        let data = json!({
+            "paragraph": {
+                "rich_text": [
+                    { "type": "mention", "mention": { "type": "page", "page": { "id": "p1" } } },
+                    { "type": "mention", "mention": { "type": "database", "database": { "id": "d1" } } },
+                    { "type": "mention", "mention": { "type": "user", "user": { "id": "u1" } } },
+                    { "type": "mention", "mention": { "type": "page", "page": { "id": "p1" } } },
+                ],
+            },
+            "properties": {
+                "Related": { "id": "x", "type": "relation", "relation": [{ "id": "p2" }, { "id": "p3" }] },
+            },
+            "link_to_page": { "type": "database_id", "database_id": "d2" },
+        });
+        let link = |object_type, id, kind| LinkRef {
+            target: ObjectKey::new(object_type, id),
+            kind,
+        };
+        assert_eq!(
+            LinkRef::find_all(&data),
+            [
+                link(ObjectType::Page, "p1", LinkKind::Mention),
+                link(ObjectType::Page, "p2", LinkKind::Relation),
+                link(ObjectType::Page, "p3", LinkKind::Relation),
+                link(ObjectType::Database, "d1", LinkKind::Mention),
+                link(ObjectType::Database, "d2", LinkKind::LinkToPage),
+            ]
+        );
+
+        // the relation config of a database property isn't a link
+        let schema = json!({ "type": "relation", "relation": { "database_id": "d3" } });
+        assert!(LinkRef::find_all(&schema).is_empty());
+    }
 }
@@ -3,9 +3,9 @@ use std::fmt::Display;
 
 use chrono::{DateTime, Utc};
 use monostate::MustBe;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 use serde_with::serde_as;
-use thiserror::Error;
 
 use crate::misc::Unquotes;
 use crate::user::User;
@@ -13,6 +13,32 @@ use crate::user::User;
 pub trait Object: Send {
     fn id(&self) -> &str;
     fn object_type(&self) -> ObjectType;
+
+    fn key(&self) -> ObjectKey {
+        ObjectKey::new(self.object_type(), self.id())
+    }
+}
+
+/// Identifies an object by its type & ID.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ObjectKey {
+    pub object_type: ObjectType,
+    pub id: String,
+}
+
+impl ObjectKey {
+    pub fn new(object_type: ObjectType, id: impl Into<String>) -> Self {
+        Self {
+            object_type,
+            id: id.into(),
+        }
+    }
+}
+
+impl Display for ObjectKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.object_type, self.id)
+    }
 }
 
 /// Common object info shared between [`Block`](crate::Block),
@@ -36,7 +62,10 @@ pub struct ObjectCommon {
     pub last_edited_time: DateTime<Utc>,
     pub last_edited_by: User,
 
+    // Older API versions only set `archived`, newer ones set both.
+    #[serde(default)]
     pub archived: bool,
+    #[serde(default)]
     pub in_trash: bool,
 }
 
@@ -44,9 +73,15 @@ impl ObjectCommon {
     pub fn parent_type(&self) -> ParentType {
         self.parent.r#type()
     }
+
+    /// Whether the object has been deleted (moved to trash), which is
+    /// indicated by either `archived` or `in_trash`.
+    pub fn is_deleted(&self) -> bool {
+        self.archived || self.in_trash
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ObjectType {
     Block,
@@ -55,6 +90,9 @@ pub enum ObjectType {
     User,
     Comment,
     List,
+    /// An object type unknown to this crate.
+    #[serde(other)]
+    Unknown,
 }
 
 impl Display for ObjectType {
@@ -66,15 +104,12 @@ impl Display for ObjectType {
             ObjectType::User => "user",
             ObjectType::Comment => "comment",
             ObjectType::List => "list",
+            ObjectType::Unknown => "unknown",
         };
         s.fmt(f)
     }
 }
 
-#[derive(Debug, Error)]
-#[error("UnsupportObjectError({0})")]
-pub struct UnsupportObjectError(String);
-
 #[allow(unused)]
 #[derive(Deserialize, Debug, Clone)]
 pub struct ObjectList<T> {
@@ -87,10 +122,56 @@ pub struct ObjectList<T> {
     #[serde(skip)]
     pub start_index: usize,
 
+    /// The elements failed to be parsed, which are dropped from `results`
+    /// when the list is parsed leniently (see [`ObjectList::parse_each`]),
+    /// by their indexes in the page, with the errors.
+    #[serde(skip)]
+    pub invalid: Vec<(usize, String)>,
+
     #[serde(flatten)]
     next_page_info: NextPageInfo,
 }
 
+impl<T> ObjectList<T> {
+    /// The results with their indexes in the whole list, i.e. counted from
+    /// `start_index`, with the invalid elements in between.
+    pub fn into_indexed_results(self) -> impl Iterator<Item = (usize, T)> {
+        let invalid: Vec<usize> = self.invalid.iter().map(|x| x.0).collect();
+        let start_index = self.start_index;
+        let mut index = 0;
+        self.results.into_iter().map(move |x| {
+            while invalid.contains(&index) {
+                index += 1;
+            }
+            index += 1;
+            (start_index + index - 1, x)
+        })
+    }
+}
+
+impl ObjectList<Value> {
+    /// Parse each element on its own, so that a malformed element is only
+    /// recorded in `invalid` rather than failing the whole page.
+    pub fn parse_each<T: DeserializeOwned>(self) -> ObjectList<T> {
+        let mut results = Vec::new();
+        let mut invalid = Vec::new();
+        for (i, x) in self.results.into_iter().enumerate() {
+            match serde_json::from_value(x) {
+                Ok(x) => results.push(x),
+                Err(e) => invalid.push((i, e.to_string())),
+            }
+        }
+        ObjectList {
+            object: self.object,
+            results,
+            ttype: self.ttype,
+            start_index: self.start_index,
+            invalid,
+            next_page_info: self.next_page_info,
+        }
+    }
+}
+
 /// See: [Pagination](https://developers.notion.com/reference/intro#pagination)
 #[derive(Deserialize, Debug, Clone)]
 struct NextPageInfo {
@@ -116,10 +197,21 @@ impl<T> NextCursor for ObjectList<T> {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Parent {
-    Block { block_id: String },
-    Page { page_id: String },
-    Database { database_id: String },
-    Workspace { workspace: MustBe!(true) },
+    Block {
+        block_id: String,
+    },
+    Page {
+        page_id: String,
+    },
+    Database {
+        database_id: String,
+    },
+    Workspace {
+        workspace: MustBe!(true),
+    },
+    /// A parent type unknown to this crate, e.g. `{"type": "x_id", "x_id":
+    /// "..."}`.
+    Unknown(JsonObject),
 }
 
 impl Parent {
@@ -129,6 +221,12 @@ impl Parent {
             Parent::Page { page_id } => page_id,
             Parent::Database { database_id } => database_id,
             Parent::Workspace { workspace: _ } => "workspace",
+            Parent::Unknown(obj) => obj
+                .map
+                .get("type")
+                .and_then(|x| obj.map.get(x.as_str()?))
+                .and_then(|x| x.as_str())
+                .unwrap_or_default(),
         }
     }
 
@@ -138,6 +236,7 @@ impl Parent {
             Parent::Page { page_id: _ } => ParentType::PageId,
             Parent::Database { database_id: _ } => ParentType::DatabaseId,
             Parent::Workspace { workspace: _ } => ParentType::Workspace,
+            Parent::Unknown(_) => ParentType::Unknown,
         }
     }
 
@@ -155,6 +254,9 @@ pub enum ParentType {
     PageId,
     BlockId,
     Workspace,
+    /// A parent type unknown to this crate.
+    #[serde(other)]
+    Unknown,
 }
 
 impl Display for ParentType {
@@ -164,15 +266,6 @@ impl Display for ParentType {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct ImpossibleParseError;
-
-impl Display for ImpossibleParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        "ImpossibleParseError".fmt(f)
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonObject {
     #[serde(flatten)]
@@ -185,3 +278,61 @@ impl Display for JsonObject {
         s.unquotes().fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::tests::user_json;
+
+    use super::{ObjectCommon, ObjectType, Parent, ParentType};
+
+    fn object_common(flags: serde_json::Value) -> ObjectCommon {
+        let mut js = json!({
+            "id": "b1",
+            "parent": { "type": "page_id", "page_id": "p1" },
+            "created_time": "2024-08-01T00:00:00.000Z",
+            "created_by": user_json("user"),
+            "last_edited_time": "2024-08-01T00:00:00.000Z",
+            "last_edited_by": user_json("user"),
+        });
+        js.as_object_mut()
+            .unwrap()
+            .extend(flags.as_object().unwrap().clone());
+        serde_json::from_value(js).unwrap()
+    }
+
+    #[test]
+    fn is_deleted() {
+        let cases = [
+            (json!({ "archived": false, "in_trash": false }), false),
+            (json!({ "archived": true, "in_trash": false }), true),
+            (json!({ "archived": false, "in_trash": true }), true),
+            (json!({ "archived": true, "in_trash": true }), true),
+            (json!({ "archived": true }), true),
+            (json!({ "archived": false }), false),
+            (json!({}), false),
+        ];
+        for (flags, deleted) in cases {
+            assert_eq!(
+                object_common(flags.clone()).is_deleted(),
+                deleted,
+                "{flags}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_types() {
+        let object_type: ObjectType = serde_json::from_value(json!("file_upload")).unwrap();
+        assert_eq!(object_type, ObjectType::Unknown);
+
+        let parent: Parent =
+            serde_json::from_value(json!({ "type": "data_source_id", "data_source_id": "d1" }))
+                .unwrap();
+        assert_eq!(parent.r#type(), ParentType::Unknown);
+        assert_eq!(parent.id(), "d1");
+        let parent_type: ParentType = serde_json::from_value(json!("data_source_id")).unwrap();
+        assert_eq!(parent_type, ParentType::Unknown);
+    }
+}
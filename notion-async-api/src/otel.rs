@@ -0,0 +1,78 @@
+//! OpenTelemetry instrumentation of [`Fetcher`](crate::Fetcher), enabled by
+//! the `otel` feature.
+//!
+//! Each task of a fetch, i.e. a request along with its retries, is a span
+//! nested under the span of the task it's derived from, e.g. the span of a
+//! page's children is a child of the page's span. The spans are created by
+//! the global tracer, which is set up by the application.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use opentelemetry::{
+    global,
+    trace::{Span, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+
+const TRACER_NAME: &str = "notion-async-api";
+
+// number of the retries of the task in a span's context
+#[derive(Default)]
+struct Retries(Arc<AtomicUsize>);
+
+/// The span of a running task.
+pub(crate) struct TaskSpan {
+    cx: Context,
+    started: Instant,
+}
+
+impl TaskSpan {
+    /// Start a span named `name` under `parent`, with `attributes` such as
+    /// the ID of the requested object.
+    pub(crate) fn start(parent: &Context, name: &'static str, attributes: Vec<KeyValue>) -> Self {
+        let mut span = global::tracer(TRACER_NAME).start_with_context(name, parent);
+        span.set_attributes(attributes);
+        Self {
+            cx: parent.with_span(span).with_value(Retries::default()),
+            started: Instant::now(),
+        }
+    }
+
+    /// The context of the span, the tasks derived from the running one are
+    /// started under it.
+    pub(crate) fn context(&self) -> &Context {
+        &self.cx
+    }
+
+    pub(crate) fn end(self) {
+        let retries = self
+            .cx
+            .get::<Retries>()
+            .map_or(0, |x| x.0.load(Ordering::SeqCst));
+        let span = self.cx.span();
+        span.set_attribute(KeyValue::new("notion.retry_count", retries as i64));
+        span.set_attribute(KeyValue::new(
+            "notion.duration_ms",
+            self.started.elapsed().as_millis() as i64,
+        ));
+        span.end();
+    }
+}
+
+/// Record a retry of the task running in `cx`, after waiting `secs` for
+/// `Retry-After`.
+pub(crate) fn record_retry(cx: &Context, secs: u64) {
+    if let Some(retries) = cx.get::<Retries>() {
+        retries.0.fetch_add(1, Ordering::SeqCst);
+    }
+    cx.span().add_event(
+        "retry",
+        vec![KeyValue::new("notion.retry_after_secs", secs as i64)],
+    );
+}
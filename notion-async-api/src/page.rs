@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::misc::{Icon, NotionFile, Property};
 use crate::object::{Object, ObjectCommon};
+use crate::rich_text::to_plain_text;
 
 /// Refer to:
 /// - [Notion JSON conventions](https://developers.notion.com/reference/intro#json-conventions)
@@ -24,6 +25,18 @@ pub struct Page {
     pub cover: Option<NotionFile>,
 }
 
+impl Page {
+    /// The plain text of the title property (the one of type `title`,
+    /// whatever its name), empty if there's no title, e.g. some database rows.
+    pub fn title(&self) -> String {
+        self.properties
+            .values()
+            .find_map(|x| x.as_title())
+            .map(|x| to_plain_text(&x))
+            .unwrap_or_default()
+    }
+}
+
 impl Object for Page {
     fn id(&self) -> &str {
         &self.obj.id
@@ -33,3 +46,26 @@ impl Object for Page {
         crate::object::ObjectType::Page
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::tests::{page_json, rich_text_json as text};
+
+    use super::Page;
+
+    #[test]
+    fn title() {
+        let mut js = page_json("p1", "d1");
+        js["properties"] = json!({
+            "Tags": { "id": "t", "type": "multi_select", "multi_select": [] },
+            "Task name": { "id": "title", "type": "title", "title": [text("Ship "), text("it")] },
+        });
+        let page: Page = serde_json::from_value(js).unwrap();
+        assert_eq!(page.title(), "Ship it");
+
+        let page: Page = serde_json::from_value(page_json("p2", "d1")).unwrap();
+        assert_eq!(page.title(), "");
+    }
+}
@@ -0,0 +1,267 @@
+//! A bounded channel for the fetch results, which applies an
+//! [`OverflowPolicy`] when the buffer is full.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use futures::{stream::BoxStream, StreamExt};
+use tokio::sync::Notify;
+
+/// What to do when the result buffer of a fetch is full, i.e. the consumer
+/// doesn't drain the stream as fast as objects are fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait until the consumer makes room. Nothing is lost, but a slow
+    /// consumer throttles the whole fetch.
+    #[default]
+    Block,
+    /// Drop the oldest buffered result to make room for the new one. The
+    /// fetch never waits for the consumer, but the consumer may miss objects.
+    DropOldest,
+    /// Abort the fetch. The consumer receives the buffered results, then a
+    /// [`NotionError::BufferFull`](crate::error::NotionError::BufferFull)
+    /// error, then the stream ends.
+    Error,
+}
+
+#[derive(Debug)]
+pub(crate) struct SendError;
+
+pub(crate) fn result_channel<T>(
+    capacity: usize,
+    policy: OverflowPolicy,
+    overflow: fn() -> T,
+) -> (ResultSender<T>, ResultReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            senders: 1,
+            closed: false,
+        }),
+        capacity: capacity.max(1),
+        policy,
+        overflow,
+        item_ready: Notify::new(),
+        space_ready: Notify::new(),
+    });
+    (
+        ResultSender {
+            shared: shared.clone(),
+        },
+        ResultReceiver { shared },
+    )
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    // the item to send when overflowed with OverflowPolicy::Error
+    overflow: fn() -> T,
+    item_ready: Notify,
+    space_ready: Notify,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    // the receiver is dropped, or the buffer overflowed with
+    // OverflowPolicy::Error
+    closed: bool,
+}
+
+pub(crate) struct ResultSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> ResultSender<T> {
+    pub(crate) async fn send(&self, item: T) -> Result<(), SendError> {
+        self.shared.send(item).await
+    }
+
+    /// A sender which doesn't keep the channel open, i.e. the receiver ends
+    /// once all the [`ResultSender`]s are dropped.
+    pub(crate) fn downgrade(&self) -> WeakResultSender<T> {
+        WeakResultSender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    async fn send(&self, item: T) -> Result<(), SendError> {
+        let shared = self;
+        let mut item = Some(item);
+        loop {
+            let notified = shared.space_ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let res = {
+                let mut state = shared.state.lock().unwrap();
+                if state.closed || state.senders == 0 {
+                    Some(Err(SendError))
+                } else if state.queue.len() < shared.capacity {
+                    state.queue.extend(item.take());
+                    Some(Ok(()))
+                } else {
+                    match shared.policy {
+                        OverflowPolicy::Block => None,
+                        OverflowPolicy::DropOldest => {
+                            state.queue.pop_front();
+                            state.queue.extend(item.take());
+                            Some(Ok(()))
+                        }
+                        OverflowPolicy::Error => {
+                            state.queue.push_back((shared.overflow)());
+                            state.closed = true;
+                            Some(Err(SendError))
+                        }
+                    }
+                }
+            };
+
+            match res {
+                Some(res) => {
+                    shared.item_ready.notify_one();
+                    return res;
+                }
+                None => notified.await,
+            }
+        }
+    }
+}
+
+pub(crate) struct WeakResultSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> WeakResultSender<T> {
+    /// Fails if all the [`ResultSender`]s are dropped.
+    pub(crate) async fn send(&self, item: T) -> Result<(), SendError> {
+        self.shared.send(item).await
+    }
+}
+
+impl<T> Clone for ResultSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for ResultSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.item_ready.notify_one();
+            // wake up the weak senders waiting for space
+            self.shared.space_ready.notify_waiters();
+        }
+    }
+}
+
+pub(crate) struct ResultReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> ResultReceiver<T> {
+    pub(crate) async fn recv(&self) -> Option<T> {
+        loop {
+            let notified = self.shared.item_ready.notified();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(item) = state.queue.pop_front() {
+                    self.shared.space_ready.notify_waiters();
+                    return Some(item);
+                }
+                if state.senders == 0 || state.closed {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    pub(crate) fn into_stream(self) -> BoxStream<'static, T>
+    where
+        T: Send + 'static,
+    {
+        futures::stream::unfold(self, |rx| async move { rx.recv().await.map(|x| (x, rx)) }).boxed()
+    }
+}
+
+impl<T> Drop for ResultReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.space_ready.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use super::{result_channel, OverflowPolicy};
+
+    #[tokio::test]
+    async fn block() {
+        let (tx, rx) = result_channel(2, OverflowPolicy::Block, || 0);
+        let producer = tokio::spawn(async move {
+            for i in 1..=5 {
+                tx.send(i).await.unwrap();
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!producer.is_finished());
+
+        assert_eq!(rx.into_stream().collect::<Vec<_>>().await, [1, 2, 3, 4, 5]);
+        producer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let (tx, rx) = result_channel(2, OverflowPolicy::DropOldest, || 0);
+        for i in 1..=5 {
+            tx.send(i).await.unwrap();
+        }
+        drop(tx);
+        assert_eq!(rx.into_stream().collect::<Vec<_>>().await, [4, 5]);
+    }
+
+    #[tokio::test]
+    async fn error() {
+        let (tx, rx) = result_channel(2, OverflowPolicy::Error, || 0);
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        assert!(tx.send(3).await.is_err());
+        assert!(tx.send(4).await.is_err());
+        assert_eq!(rx.into_stream().collect::<Vec<_>>().await, [1, 2, 0]);
+    }
+
+    #[tokio::test]
+    async fn receiver_dropped() {
+        let (tx, rx) = result_channel(1, OverflowPolicy::Block, || 0);
+        tx.send(1).await.unwrap();
+        let producer = tokio::spawn(async move { tx.send(2).await });
+        drop(rx);
+        assert!(producer.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn weak_sender() {
+        let (tx, rx) = result_channel(1, OverflowPolicy::Block, || 0);
+        let weak = tx.downgrade();
+        weak.send(1).await.unwrap();
+        drop(tx);
+        assert!(weak.send(2).await.is_err());
+        assert_eq!(rx.into_stream().collect::<Vec<_>>().await, [1]);
+    }
+}
@@ -1,8 +1,12 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     misc::{DateProperty, IdData, UrlData},
     user::User,
+    Object, ObjectKey, ObjectType,
 };
 
 /// Refer to:
@@ -17,22 +21,358 @@ pub struct RichText {
     pub href: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl RichText {
+    /// A plain text without annotations, e.g. for [`Api::create_comment`].
+    ///
+    /// [`Api::create_comment`]: crate::Api::create_comment
+    pub fn text(content: impl Into<String>) -> Self {
+        let content = content.into();
+        Self {
+            rich_text_type: RichTextType::Text {
+                text: TextData {
+                    content: content.clone(),
+                    link: None,
+                },
+            },
+            annotations: Annotations::default(),
+            plain_text: content,
+            href: None,
+        }
+    }
+
+    /// Render the rich text as readable text, e.g. date mentions are
+    /// formatted in their time zone. Otherwise it's the `plain_text`.
+    pub fn render(&self) -> String {
+        match &self.rich_text_type {
+            RichTextType::Mention {
+                mention: MentionType::Date { date },
+            } => date.to_string(),
+            _ => self.plain_text.clone(),
+        }
+    }
+
+    /// Like [`RichText::render`], but a page/database mention is rendered
+    /// as its title looked up by `resolver`, e.g. the titles of
+    /// [`AnyObject::titles`], or its ID if it isn't resolved.
+    ///
+    /// [`AnyObject::titles`]: crate::AnyObject::titles
+    pub fn render_resolved<R: MentionResolver + ?Sized>(&self, resolver: &R) -> String {
+        let RichTextType::Mention { mention } = &self.rich_text_type else {
+            return self.render();
+        };
+        match mention.target() {
+            Some(key) => self.mention_label(resolver).unwrap_or(key.id),
+            None => self.render(),
+        }
+    }
+
+    /// A readable label of a mention, i.e. the title of the mentioned
+    /// page/database (see [`MentionType::target`]) or `@name` of the
+    /// mentioned user, looked up by `resolver` (the name embedded in the user
+    /// object is preferred), the formatted date of a date mention, or the URL
+    /// of a link preview.
+    ///
+    /// `None` if it isn't a mention, or the mentioned object isn't resolved.
+    pub fn mention_label<R: MentionResolver + ?Sized>(&self, resolver: &R) -> Option<String> {
+        let RichTextType::Mention { mention } = &self.rich_text_type else {
+            return None;
+        };
+        if let Some(key) = mention.target() {
+            return resolver.title(&key);
+        }
+        match mention {
+            MentionType::User { user } => user
+                .name
+                .clone()
+                .or_else(|| resolver.user_name(user.id()))
+                .map(|name| format!("@{name}")),
+            MentionType::Date { date } => Some(date.to_string()),
+            MentionType::LinkPreview { link_preview } => Some(link_preview.url().to_owned()),
+            MentionType::Page { .. }
+            | MentionType::Database { .. }
+            | MentionType::TemplateMention { .. } => None,
+        }
+    }
+
+    /// Render the rich text as markdown, i.e. [`RichText::render`] with the
+    /// bold/italic/strikethrough/code annotations and the link, see
+    /// [`to_markdown`].
+    pub fn to_markdown(&self) -> String {
+        to_markdown(std::slice::from_ref(self))
+    }
+
+    /// Like [`RichText::to_markdown`], but a mention is rendered as its
+    /// [`RichText::mention_label`] if it's resolved.
+    pub fn to_markdown_resolved<R: MentionResolver + ?Sized>(&self, resolver: &R) -> String {
+        to_markdown_resolved(std::slice::from_ref(self), resolver)
+    }
+}
+
+/// The concatenated `plain_text` of `rich_text`, without annotations.
+pub fn to_plain_text(rich_text: &[RichText]) -> String {
+    rich_text.iter().map(|x| x.plain_text.as_str()).collect()
+}
+
+/// Render `rich_text` as markdown, i.e. the text of each span (see
+/// [`RichText::render`]) with its annotations: bold as `**`, italic as `*`,
+/// strikethrough as `~~` and code as backticks, and the link of `href`. The
+/// other annotations (e.g. `color`) are dropped, and the text isn't escaped.
+///
+/// The annotations shared by adjacent spans are merged, e.g. a bold span
+/// followed by a bold italic one is `**a *b***`, and so are the links.
+pub fn to_markdown(rich_text: &[RichText]) -> String {
+    spans_to_markdown(rich_text, RichText::render)
+}
+
+/// Like [`to_markdown`], but the mentions are rendered as their
+/// [`RichText::mention_label`] if they're resolved.
+pub fn to_markdown_resolved<R: MentionResolver + ?Sized>(
+    rich_text: &[RichText],
+    resolver: &R,
+) -> String {
+    spans_to_markdown(rich_text, |x| {
+        x.mention_label(resolver).unwrap_or_else(|| x.render())
+    })
+}
+
+fn spans_to_markdown(rich_text: &[RichText], text_of: impl Fn(&RichText) -> String) -> String {
+    let mut md = String::new();
+    // the adjacent spans with the same link are one link
+    for spans in rich_text.chunk_by(|a, b| a.href == b.href) {
+        let text = emphasize(
+            &spans
+                .iter()
+                .map(|x| (&x.annotations, text_of(x)))
+                .collect::<Vec<_>>(),
+        );
+        match &spans[0].href {
+            Some(href) => {
+                // the whitespace around is kept out of the link
+                let inner = text.trim();
+                if inner.is_empty() {
+                    md.push_str(&text);
+                    continue;
+                }
+                let start = text.len() - text.trim_start().len();
+                let end = start + inner.len();
+                md.push_str(&format!(
+                    "{}[{inner}]({href}){}",
+                    &text[..start],
+                    &text[end..]
+                ));
+            }
+            None => md.push_str(&text),
+        }
+    }
+    md
+}
+
+/// Render the spans with the emphasis markers, which are opened & closed
+/// like a stack, so that the ones shared by adjacent spans stay open. The
+/// markers opened together are nested by how long they last, e.g.
+/// `***a**b*` for a bold italic span followed by an italic one.
+fn emphasize(spans: &[(&Annotations, String)]) -> String {
+    let markers_of = |a: &Annotations| -> Vec<&'static str> {
+        [(a.bold, "**"), (a.italic, "*"), (a.strikethrough, "~~")]
+            .into_iter()
+            .filter_map(|(on, marker)| on.then_some(marker))
+            .collect()
+    };
+    // the number of the spans from `i` with `marker`, the whitespace only
+    // spans don't break it
+    let run = |i: usize, marker: &str| {
+        spans[i..]
+            .iter()
+            .take_while(|(a, text)| text.trim().is_empty() || markers_of(a).contains(&marker))
+            .count()
+    };
+
+    let mut md = String::new();
+    let mut open: Vec<&str> = Vec::new();
+    // the trailing whitespace of the last span, which is written after the
+    // closing markers, since the markers must be next to non-whitespace
+    // chars, e.g. `**a** b` rather than `**a **b`
+    let mut whitespace = String::new();
+    for (i, (a, text)) in spans.iter().enumerate() {
+        let inner = text.trim();
+        if inner.is_empty() {
+            whitespace.push_str(text);
+            continue;
+        }
+        let mut markers = markers_of(a);
+        markers.sort_by_key(|x| std::cmp::Reverse(run(i, x)));
+        // close the markers from the first one not wanted
+        if let Some(j) = open.iter().position(|x| !markers.contains(x)) {
+            for marker in open.drain(j..).rev() {
+                md.push_str(marker);
+            }
+        }
+        md.push_str(&whitespace);
+        md.push_str(&text[..text.len() - text.trim_start().len()]);
+        for marker in markers {
+            if !open.contains(&marker) {
+                md.push_str(marker);
+                open.push(marker);
+            }
+        }
+        if a.code {
+            md.push_str(&code_span(inner));
+        } else {
+            md.push_str(inner);
+        }
+        whitespace = text[text.trim_end().len()..].to_owned();
+    }
+    for marker in open.iter().rev() {
+        md.push_str(marker);
+    }
+    md.push_str(&whitespace);
+    md
+}
+
+/// A code span of `code`, delimited by more backticks than any run of
+/// backticks in it.
+fn code_span(code: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat(longest + 1);
+    // a space separates the backticks at the ends from the fence
+    let pad = if longest > 0 { " " } else { "" };
+    format!("{fence}{pad}{code}{pad}{fence}")
+}
+
+/// Looks up the labels of the mentioned objects for
+/// [`RichText::mention_label`], e.g. from the synced objects or a db.
+pub trait MentionResolver {
+    /// The title of the page/database of `key`.
+    fn title(&self, key: &ObjectKey) -> Option<String>;
+
+    /// The name of the user of `id`.
+    fn user_name(&self, _id: &str) -> Option<String> {
+        None
+    }
+}
+
+/// The titles of pages/databases, e.g. by [`AnyObject::titles`].
+///
+/// [`AnyObject::titles`]: crate::AnyObject::titles
+impl MentionResolver for HashMap<ObjectKey, String> {
+    fn title(&self, key: &ObjectKey) -> Option<String> {
+        self.get(key).cloned()
+    }
+}
+
+/// Render `rich_text` as readable text, with the page/database mentions
+/// resolved to their titles, see [`RichText::render_resolved`].
+pub fn resolve_mentions<R: MentionResolver + ?Sized>(
+    rich_text: &[RichText],
+    resolver: &R,
+) -> String {
+    rich_text
+        .iter()
+        .map(|x| x.render_resolved(resolver))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Annotations {
     pub bold: bool,
     pub italic: bool,
     pub strikethrough: bool,
     pub underline: bool,
     pub code: bool,
-    pub color: String,
+    pub color: Color,
+}
+
+/// The color of text, or its background for the `_background` ones.
+///
+/// Refer to: [Rich text](https://developers.notion.com/reference/rich-text#the-annotation-object)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Color {
+    #[default]
+    Default,
+    Gray,
+    Brown,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Pink,
+    Red,
+    GrayBackground,
+    BrownBackground,
+    OrangeBackground,
+    YellowBackground,
+    GreenBackground,
+    BlueBackground,
+    PurpleBackground,
+    PinkBackground,
+    RedBackground,
+    /// A value unknown to this crate.
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl Color {
+    /// The raw value, e.g. `red_background`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Color::Default => "default",
+            Color::Gray => "gray",
+            Color::Brown => "brown",
+            Color::Orange => "orange",
+            Color::Yellow => "yellow",
+            Color::Green => "green",
+            Color::Blue => "blue",
+            Color::Purple => "purple",
+            Color::Pink => "pink",
+            Color::Red => "red",
+            Color::GrayBackground => "gray_background",
+            Color::BrownBackground => "brown_background",
+            Color::OrangeBackground => "orange_background",
+            Color::YellowBackground => "yellow_background",
+            Color::GreenBackground => "green_background",
+            Color::BlueBackground => "blue_background",
+            Color::PurpleBackground => "purple_background",
+            Color::PinkBackground => "pink_background",
+            Color::RedBackground => "red_background",
+            Color::Other(x) => x,
+        }
+    }
+
+    /// Whether it's the color of the background rather than the text.
+    pub fn is_background(&self) -> bool {
+        self.as_str().ends_with("_background")
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum RichTextType {
-    Equation { equation: EquationData },
-    Mention { mention: MentionType },
-    Text { text: TextData },
+    Equation {
+        equation: EquationData,
+    },
+    Mention {
+        mention: MentionType,
+    },
+    Text {
+        text: TextData,
+    },
+    /// A rich text type unknown to this crate (or an unknown subtype of a
+    /// known type, e.g. a new kind of mention), kept as its raw JSON, i.e.
+    /// the `type` and the type data. It's rendered as its `plain_text`.
+    Unknown(serde_json::Value),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,9 +397,380 @@ pub enum MentionType {
     TemplateMention { template_mention: MentionTypeData },
 }
 
+impl MentionType {
+    pub fn as_page_id(&self) -> Option<&str> {
+        match self {
+            MentionType::Page { page } => Some(&page.id),
+            _ => None,
+        }
+    }
+
+    pub fn as_database_id(&self) -> Option<&str> {
+        match self {
+            MentionType::Database { database } => Some(&database.id),
+            _ => None,
+        }
+    }
+
+    /// The page/database mentioned, e.g. to fetch it too, see
+    /// [`FetcherOptions::follow_mentions`](crate::FetcherOptions::follow_mentions).
+    pub fn target(&self) -> Option<ObjectKey> {
+        match self {
+            MentionType::Page { page } => Some(ObjectKey::new(ObjectType::Page, &page.id)),
+            MentionType::Database { database } => {
+                Some(ObjectKey::new(ObjectType::Database, &database.id))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_user(&self) -> Option<&User> {
+        match self {
+            MentionType::User { user } => Some(user),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<&DateProperty> {
+        match self {
+            MentionType::Date { date } => Some(date),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(rename_all = "snake_case")]
+#[serde(untagged)]
 pub enum MentionTypeData {
-    TemplateMentionDate(String),
-    TemplateMentionUser(String),
+    TemplateMentionDate {
+        template_mention_date: TemplateMentionDate,
+    },
+    TemplateMentionUser {
+        template_mention_user: TemplateMentionUser,
+    },
+}
+
+/// A dynamic date in a template, which is resolved when the template is
+/// used.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateMentionDate {
+    Today,
+    Now,
+    /// A value unknown to this crate.
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl TemplateMentionDate {
+    /// The concrete date when the template is used at `now`. `Today` is the
+    /// start of the day (in UTC) of `now`. `None` for unknown values.
+    pub fn resolve(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            TemplateMentionDate::Today => {
+                Some(now.date_naive().and_time(Default::default()).and_utc())
+            }
+            TemplateMentionDate::Now => Some(now),
+            TemplateMentionDate::Other(_) => None,
+        }
+    }
+}
+
+/// A dynamic user in a template, which is resolved when the template is
+/// used.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateMentionUser {
+    Me,
+    /// A value unknown to this crate.
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl TemplateMentionUser {
+    /// The concrete user when the template is used by `me`. `None` for
+    /// unknown values.
+    pub fn resolve<'a>(&self, me: &'a User) -> Option<&'a User> {
+        match self {
+            TemplateMentionUser::Me => Some(me),
+            TemplateMentionUser::Other(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    use crate::{
+        tests::{mention_json, rich_text_json, user_json},
+        ObjectKey, ObjectType,
+    };
+
+    use super::{
+        resolve_mentions, to_markdown, to_plain_text, Annotations, Color, MentionResolver,
+        MentionType, MentionTypeData, RichText, RichTextType, TemplateMentionDate,
+        TemplateMentionUser,
+    };
+
+    fn date_mention(date: serde_json::Value) -> RichText {
+        mention(json!({ "type": "date", "date": date }))
+    }
+
+    fn mention(mention: serde_json::Value) -> RichText {
+        serde_json::from_value(mention_json(mention, "@August 1, 2024")).unwrap()
+    }
+
+    #[test]
+    fn date_range_mention() {
+        let rt = date_mention(json!({
+            "start": "2024-08-01T01:00:00.000Z",
+            "end": "2024-08-01T02:30:00.000Z",
+            "time_zone": "Asia/Shanghai",
+        }));
+        let RichTextType::Mention { mention } = &rt.rich_text_type else {
+            panic!("not a mention");
+        };
+        assert!(mention.as_date().is_some());
+        assert!(mention.as_page_id().is_none());
+        assert_eq!(rt.render(), "2024-08-01 09:00 CST → 2024-08-01 10:30 CST");
+
+        let rt = date_mention(json!({
+            "start": "2024-08-01T09:00:00.000",
+            "end": null,
+            "time_zone": "America/New_York",
+        }));
+        assert_eq!(rt.render(), "2024-08-01 09:00 EDT");
+
+        let rt = date_mention(json!({
+            "start": "2024-08-01T09:00:00.000+08:00",
+            "end": null,
+        }));
+        assert_eq!(rt.render(), "2024-08-01 09:00 +08:00");
+
+        let rt = date_mention(json!({ "start": "2024-08-01", "end": "2024-08-03" }));
+        assert_eq!(rt.render(), "2024-08-01 → 2024-08-03");
+    }
+
+    #[test]
+    fn resolved_mentions() {
+        let titles = HashMap::from([
+            (
+                ObjectKey::new(ObjectType::Page, "p1"),
+                "Meeting notes".to_owned(),
+            ),
+            (
+                ObjectKey::new(ObjectType::Database, "d1"),
+                "Tasks".to_owned(),
+            ),
+        ]);
+        let page = |id| mention(json!({ "type": "page", "page": { "id": id } }));
+        let database = |id| mention(json!({ "type": "database", "database": { "id": id } }));
+
+        assert_eq!(page("p1").render_resolved(&titles), "Meeting notes");
+        assert_eq!(database("d1").render_resolved(&titles), "Tasks");
+        // unresolved
+        assert_eq!(page("p2").render_resolved(&titles), "p2");
+        // same ID but another type
+        assert_eq!(database("p1").render_resolved(&titles), "p1");
+        // not a page/database mention
+        let date = date_mention(json!({ "start": "2024-08-01", "end": null }));
+        assert_eq!(date.render_resolved(&titles), "2024-08-01");
+
+        assert_eq!(
+            resolve_mentions(&[page("p1"), date, page("p2")], &titles),
+            "Meeting notes2024-08-01p2"
+        );
+    }
+
+    #[test]
+    fn mention_label() {
+        struct Resolver;
+        impl MentionResolver for Resolver {
+            fn title(&self, key: &ObjectKey) -> Option<String> {
+                (key.object_type == ObjectType::Page && key.id == "p1")
+                    .then(|| "Meeting notes".to_owned())
+            }
+            fn user_name(&self, id: &str) -> Option<String> {
+                (id == "u1").then(|| "Ada".to_owned())
+            }
+        }
+
+        let page = mention(json!({ "type": "page", "page": { "id": "p1" } }));
+        assert_eq!(
+            page.mention_label(&Resolver).as_deref(),
+            Some("Meeting notes")
+        );
+        assert_eq!(page.render_resolved(&Resolver), "Meeting notes");
+        let page = mention(json!({ "type": "page", "page": { "id": "p2" } }));
+        assert_eq!(page.mention_label(&Resolver), None);
+        assert_eq!(page.render_resolved(&Resolver), "p2");
+        assert_eq!(page.to_markdown_resolved(&Resolver), "@August 1, 2024");
+
+        let user = mention(json!({ "type": "user", "user": user_json("u1") }));
+        assert_eq!(user.mention_label(&Resolver).as_deref(), Some("@Ada"));
+        let mut js = user_json("u2");
+        js["name"] = json!("Grace");
+        let user = mention(json!({ "type": "user", "user": js }));
+        assert_eq!(user.mention_label(&Resolver).as_deref(), Some("@Grace"));
+
+        let date = date_mention(json!({ "start": "2024-08-01", "end": null }));
+        assert_eq!(date.mention_label(&Resolver).as_deref(), Some("2024-08-01"));
+        let link = mention(json!({
+            "type": "link_preview",
+            "link_preview": { "url": "https://github.com/mindeng/notion-async" },
+        }));
+        assert_eq!(
+            link.mention_label(&Resolver).as_deref(),
+            Some("https://github.com/mindeng/notion-async")
+        );
+
+        assert_eq!(RichText::text("text").mention_label(&Resolver), None);
+    }
+
+    #[test]
+    fn markdown() {
+        let span = |s: &str, f: fn(&mut Annotations)| {
+            let mut x = RichText::text(s);
+            f(&mut x.annotations);
+            x
+        };
+        let bold = |s| span(s, |a| a.bold = true);
+        let bold_italic = |s| {
+            span(s, |a| {
+                a.bold = true;
+                a.italic = true;
+            })
+        };
+        let plain = RichText::text;
+
+        let rt = [plain("a "), bold("b "), bold_italic("c"), plain(" d")];
+        assert_eq!(to_plain_text(&rt), "a b c d");
+        assert_eq!(to_markdown(&rt), "a **b *c*** d");
+        // the longer lasting marker is the outer one
+        let rt = [bold_italic("a"), span("b", |a| a.italic = true)];
+        assert_eq!(to_markdown(&rt), "***a**b*");
+
+        let code = span("x = `y`", |a| {
+            a.code = true;
+            a.strikethrough = true;
+            a.color = Color::Red;
+        });
+        assert_eq!(to_markdown(&[code]), "~~`` x = `y` ``~~");
+
+        let mut link = [bold("Notion"), plain(" API ")];
+        for x in link.iter_mut() {
+            x.href = Some("https://developers.notion.com".to_owned());
+        }
+        assert_eq!(
+            to_markdown(&[
+                plain("see "),
+                link[0].clone(),
+                link[1].clone(),
+                plain("now")
+            ]),
+            "see [**Notion** API](https://developers.notion.com) now"
+        );
+        assert_eq!(to_markdown(&[plain(" "), bold(" ")]), "  ");
+    }
+
+    #[test]
+    fn color() {
+        let color = |s: &str| serde_json::from_value::<Color>(json!(s)).unwrap();
+        assert_eq!(color("default"), Color::Default);
+        assert_eq!(color("red"), Color::Red);
+        assert_eq!(color("blue_background"), Color::BlueBackground);
+        assert!(color("blue_background").is_background());
+        assert!(!color("blue").is_background());
+        assert_eq!(color("teal"), Color::Other("teal".to_owned()));
+        for s in ["gray_background", "teal"] {
+            assert_eq!(color(s).as_str(), s);
+            assert_eq!(serde_json::to_value(color(s)).unwrap(), json!(s));
+        }
+
+        let mut js = rich_text_json("a");
+        js["annotations"]["color"] = json!("pink_background");
+        let rt: RichText = serde_json::from_value(js).unwrap();
+        assert_eq!(rt.annotations.color, Color::PinkBackground);
+    }
+
+    #[test]
+    fn template_mention() {
+        let now: DateTime<Utc> = "2024-08-01T09:30:00Z".parse().unwrap();
+        let date = |value: &str| -> TemplateMentionDate {
+            let mention: MentionType = serde_json::from_value(json!({
+                "type": "template_mention",
+                "template_mention": { "type": "template_mention_date", "template_mention_date": value },
+            }))
+            .unwrap();
+            match mention {
+                MentionType::TemplateMention {
+                    template_mention:
+                        MentionTypeData::TemplateMentionDate {
+                            template_mention_date,
+                        },
+                } => template_mention_date,
+                x => panic!("unexpected {x:?}"),
+            }
+        };
+
+        assert_eq!(date("today"), TemplateMentionDate::Today);
+        assert_eq!(
+            date("today").resolve(now),
+            Some("2024-08-01T00:00:00Z".parse().unwrap())
+        );
+        assert_eq!(date("now").resolve(now), Some(now));
+        assert_eq!(
+            date("tomorrow"),
+            TemplateMentionDate::Other("tomorrow".to_owned())
+        );
+        assert_eq!(date("tomorrow").resolve(now), None);
+
+        let me = serde_json::from_value(user_json("u1")).unwrap();
+        let user: TemplateMentionUser = serde_json::from_value(json!("me")).unwrap();
+        assert!(user.resolve(&me).is_some());
+    }
+
+    #[test]
+    fn unknown_rich_text_type() {
+        let mut js = rich_text_json("a hologram");
+        js.as_object_mut().unwrap().remove("text");
+        js["type"] = json!("hologram");
+        js["hologram"] = json!({ "depth": 3 });
+        let rt: RichText = serde_json::from_value(js.clone()).unwrap();
+        let RichTextType::Unknown(raw) = &rt.rich_text_type else {
+            panic!("not unknown: {:?}", rt.rich_text_type);
+        };
+        assert_eq!(
+            raw,
+            &json!({ "type": "hologram", "hologram": { "depth": 3 } })
+        );
+        assert_eq!(rt.render(), "a hologram");
+        // the raw JSON is kept as is
+        assert_eq!(serde_json::to_value(&rt).unwrap(), js);
+
+        // an unknown mention subtype doesn't fail the surrounding object
+        let comment = json!({
+            "object": "comment",
+            "id": "c1",
+            "parent": { "type": "page_id", "page_id": "p1" },
+            "created_time": "2024-08-01T00:00:00.000Z",
+            "created_by": { "object": "user", "id": "u" },
+            "last_edited_time": "2024-08-01T00:00:00.000Z",
+            "discussion_id": "d1",
+            "rich_text": [mention_json(
+                json!({ "type": "hologram", "hologram": {} }),
+                "@hologram",
+            )],
+        });
+        let comment: crate::Comment = serde_json::from_value(comment).unwrap();
+        assert!(matches!(
+            comment.rich_text[0].rich_text_type,
+            RichTextType::Unknown(_)
+        ));
+        assert_eq!(comment.rich_text[0].render(), "@hologram");
+    }
 }
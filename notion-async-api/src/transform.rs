@@ -0,0 +1,113 @@
+use crate::AnyObject;
+
+/// Normalizes an object before it's stored, e.g. to flatten properties, drop
+/// volatile fields or rename keys for a downstream schema.
+///
+/// Closures of `Fn(AnyObject) -> AnyObject` are transforms too.
+pub trait Transform: Send + Sync {
+    fn apply(&self, obj: AnyObject) -> AnyObject;
+}
+
+impl<F> Transform for F
+where
+    F: Fn(AnyObject) -> AnyObject + Send + Sync,
+{
+    fn apply(&self, obj: AnyObject) -> AnyObject {
+        self(obj)
+    }
+}
+
+/// Apply `transforms` to `obj` in order.
+pub fn apply_transforms(transforms: &[Box<dyn Transform>], obj: AnyObject) -> AnyObject {
+    transforms.iter().fold(obj, |obj, x| x.apply(obj))
+}
+
+/// Clears the URLs of the files (see [`AnyObject::map_file_urls`]), which
+/// are volatile since the URLs of the files hosted by notion expire in an
+/// hour.
+pub struct StripFileUrls;
+
+impl Transform for StripFileUrls {
+    fn apply(&self, mut obj: AnyObject) -> AnyObject {
+        obj.map_file_urls(&|_| String::new());
+        obj
+    }
+}
+
+/// Lowercases the names of the properties of pages & databases. If two
+/// names collide, the last one in order wins.
+pub struct LowercasePropertyKeys;
+
+impl Transform for LowercasePropertyKeys {
+    fn apply(&self, mut obj: AnyObject) -> AnyObject {
+        let properties = match &mut obj {
+            AnyObject::Page(x) => &mut x.properties,
+            AnyObject::Database(x) => &mut x.properties,
+            _ => return obj,
+        };
+        *properties = std::mem::take(properties)
+            .into_iter()
+            .map(|(k, v)| (k.to_lowercase(), v))
+            .collect();
+        obj
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        tests::{block_json, page_json},
+        AnyObject, Object,
+    };
+
+    use super::{apply_transforms, LowercasePropertyKeys, StripFileUrls, Transform};
+
+    fn page() -> AnyObject {
+        let mut js = page_json("p1", "workspace");
+        js["properties"] = json!({
+            "Status": { "id": "s", "type": "checkbox", "checkbox": true },
+            "Due Date": { "id": "d", "type": "checkbox", "checkbox": false },
+        });
+        js["icon"] = json!({ "type": "external", "external": { "url": "https://a.com/icon.png" } });
+        AnyObject::Page(serde_json::from_value(js).unwrap())
+    }
+
+    #[test]
+    fn builtin_transforms() {
+        let transforms: Vec<Box<dyn Transform>> =
+            vec![Box::new(StripFileUrls), Box::new(LowercasePropertyKeys)];
+        let AnyObject::Page(page) = apply_transforms(&transforms, page()) else {
+            panic!("not a page");
+        };
+        assert_eq!(
+            page.properties.keys().collect::<Vec<_>>(),
+            ["due date", "status"]
+        );
+        let icon = serde_json::to_value(&page.icon).unwrap();
+        assert_eq!(icon["external"]["url"], "");
+
+        // other objects are kept as is
+        let block =
+            AnyObject::Block(serde_json::from_value(block_json("b1", "p1", "paragraph")).unwrap());
+        assert_eq!(apply_transforms(&transforms, block).id(), "b1");
+    }
+
+    #[test]
+    fn custom_transform() {
+        // an example transform, which drops the properties other than the
+        // title
+        let title_only = |mut obj: AnyObject| {
+            if let AnyObject::Page(page) = &mut obj {
+                page.properties.retain(|_, v| v.as_title().is_some());
+            }
+            obj
+        };
+        let transforms: Vec<Box<dyn Transform>> = vec![Box::new(title_only)];
+        let AnyObject::Page(page) = apply_transforms(&transforms, page()) else {
+            panic!("not a page");
+        };
+        assert!(page.properties.is_empty());
+    }
+}
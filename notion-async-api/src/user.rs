@@ -1,6 +1,9 @@
+use std::fmt::Display;
+
 use monostate::MustBe;
 use serde::{Deserialize, Serialize};
 
+use crate::misc::Unquotes;
 use crate::object::{JsonObject, Object};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,11 +18,45 @@ pub struct User {
     pub user_data: Option<UserTypeData>,
 }
 
+impl User {
+    /// A user of only its ID, like the ones embedded in the `created_by` &
+    /// `last_edited_by` of the objects.
+    pub fn from_id(id: impl Into<String>) -> Self {
+        Self {
+            object: Default::default(),
+            id: id.into(),
+            r#type: None,
+            name: None,
+            avatar_url: None,
+            user_data: None,
+        }
+    }
+
+    /// The email of a person, if the integration has the capability to read
+    /// it.
+    pub fn email(&self) -> Option<&str> {
+        match &self.user_data {
+            Some(UserTypeData::Person { email }) => email.as_deref(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum UserType {
     Person,
     Bot,
+    /// A user type unknown to this crate.
+    #[serde(other)]
+    Unknown,
+}
+
+impl Display for UserType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = serde_json::to_string(self).unwrap();
+        s.unquotes().fmt(f)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +71,9 @@ pub enum UserTypeData {
 pub enum OwnerType {
     Workspace,
     User,
+    /// An owner type unknown to this crate.
+    #[serde(other)]
+    Unknown,
 }
 
 impl Object for User {
@@ -44,3 +84,34 @@ impl Object for User {
         crate::object::ObjectType::User
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::tests::user_json;
+
+    use super::{OwnerType, User, UserType};
+
+    #[test]
+    fn unknown_types() {
+        let mut js = user_json("u1");
+        js["type"] = json!("agent");
+        let user: User = serde_json::from_value(js).unwrap();
+        assert_eq!(user.r#type, Some(UserType::Unknown));
+
+        let owner: OwnerType = serde_json::from_value(json!("team")).unwrap();
+        assert_eq!(owner, OwnerType::Unknown);
+    }
+
+    #[test]
+    fn person() {
+        let mut js = user_json("u1");
+        js["type"] = json!("person");
+        js["person"] = json!({ "email": "a@example.com" });
+        let user: User = serde_json::from_value(js).unwrap();
+        assert_eq!(user.r#type.unwrap().to_string(), "person");
+        assert_eq!(user.email(), Some("a@example.com"));
+        assert_eq!(UserType::Unknown.to_string(), "unknown");
+    }
+}
@@ -1,6 +1,15 @@
-use notion_async_api::{Block, Comment, Database, Object, Page};
+use std::collections::BTreeMap;
+
+use notion_async_api::{
+    hyphenated_id, Block, Comment, Database, LinkRef, Object, ObjectKey, ObjectType, Page, Parent,
+    RichText, User, ViewRef,
+};
 use sqlx::{
     sqlite::{SqliteConnectOptions, SqliteQueryResult},
+    types::{
+        chrono::{DateTime, Utc},
+        Json,
+    },
     Connection, SqliteConnection,
 };
 
@@ -20,19 +29,543 @@ pub async fn init_db(path: &str) -> sqlx::Result<SqliteConnection> {
         .create_if_missing(true);
     let mut conn = SqliteConnection::connect_with(&options).await?;
     sqlx::query(sql).execute(&mut conn).await?;
+    migrate(&mut conn).await?;
 
     Ok(conn)
 }
 
+/// Apply the [`MIGRATIONS`] which haven't been applied yet. The number of
+/// applied migrations is recorded in `PRAGMA user_version`.
+async fn migrate(conn: &mut SqliteConnection) -> sqlx::Result<()> {
+    let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let mut tx = conn.begin().await?;
+        sqlx::query(sql).execute(&mut *tx).await?;
+        sqlx::query(&format!("PRAGMA user_version = {}", i + 1))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+/// Find the objects which haven't been synced since `older_than`.
+pub async fn stale_objects(
+    db: &mut SqliteConnection,
+    older_than: DateTime<Utc>,
+) -> sqlx::Result<Vec<ObjectKey>> {
+    let mut keys = Vec::new();
+    for (object_type, table) in OBJECT_TABLES {
+        let ids: Vec<(String,)> = sqlx::query_as(&format!(
+            "select id from {table} \
+             where synced_at is null or datetime(synced_at) < datetime($1)"
+        ))
+        .bind(older_than)
+        .fetch_all(&mut *db)
+        .await?;
+        keys.extend(ids.into_iter().map(|(id,)| ObjectKey::new(object_type, id)));
+    }
+    Ok(keys)
+}
+
+/// The current time of the db, i.e. of the clock setting `synced_at`, to be
+/// used as the `since` of [`stale_descendants`].
+pub async fn current_time(db: &mut SqliteConnection) -> sqlx::Result<DateTime<Utc>> {
+    let (now,): (DateTime<Utc>,) = sqlx::query_as("select CURRENT_TIMESTAMP")
+        .fetch_one(db)
+        .await?;
+    Ok(now)
+}
+
+/// Record the start of syncing the root `root_id`, return the ID of the
+/// sync run, see [`finish_sync_run`].
+pub async fn start_sync_run(db: &mut SqliteConnection, root_id: &str) -> sqlx::Result<i64> {
+    let (run_id,): (i64,) = sqlx::query_as(
+        "insert into sync_runs (root_id, started_at, status) \
+         values ($1, CURRENT_TIMESTAMP, 'running') returning run_id",
+    )
+    .bind(root_id)
+    .fetch_one(db)
+    .await?;
+    Ok(run_id)
+}
+
+/// Record the end of the sync run `run_id`, with the number of the synced
+/// objects, and whether there was any error.
+pub async fn finish_sync_run(
+    db: &mut SqliteConnection,
+    run_id: i64,
+    objects_count: usize,
+    failed: bool,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "update sync_runs \
+         set finished_at = CURRENT_TIMESTAMP, objects_count = $2, status = $3 \
+         where run_id = $1",
+    )
+    .bind(run_id)
+    .bind(objects_count as i64)
+    .bind(if failed { "error" } else { "ok" })
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// The CTE `tree (object_type, id, synced_at)` of the objects under the root
+/// `$1` (without dashes), including the root itself, by the `parent_id` of
+/// the rows, shared by the stores.
+pub(crate) const SUBTREE: &str =
+    "with recursive objects (object_type, id, parent_id, synced_at) as ( \
+       select 'block', id, parent_id, synced_at from blocks \
+       union all select 'page', id, parent_id, synced_at from pages \
+       union all select 'database', id, parent_id, synced_at from databases \
+       union all select 'comment', id, parent_id, synced_at from comments \
+     ), \
+     tree (object_type, id, synced_at) as ( \
+       select object_type, id, synced_at from objects where replace(id, '-', '') = $1 \
+       union \
+       select o.object_type, o.id, o.synced_at from objects o join tree t on o.parent_id = t.id \
+     )";
+
+/// Find the objects under the root `root_id` (with or without dashes,
+/// including the root itself) in db which haven't been synced since `since`,
+/// i.e. deleted in Notion (or moved out of the root) if the root is fully
+/// synced since then. The subtree is found by the `parent_id` of the rows.
+pub async fn stale_descendants(
+    db: &mut SqliteConnection,
+    root_id: &str,
+    since: DateTime<Utc>,
+) -> sqlx::Result<Vec<ObjectKey>> {
+    let rows: Vec<(String, String)> = sqlx::query_as(&format!(
+        "{SUBTREE} \
+         select object_type, id from tree \
+         where synced_at is null or datetime(synced_at) < datetime($2) \
+         order by object_type, id",
+    ))
+    .bind(root_id.replace('-', ""))
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+    rows.into_iter()
+        .map(|(object_type, id)| {
+            let object_type = serde_json::from_value(serde_json::Value::String(object_type))
+                .map_err(|e| sqlx::Error::Decode(e.into()))?;
+            Ok(ObjectKey::new(object_type, id))
+        })
+        .collect()
+}
+
+/// Delete the objects, along with their provenance, links, rich text, text
+/// index & downloaded files.
+pub async fn delete_objects(db: &mut SqliteConnection, keys: &[ObjectKey]) -> sqlx::Result<()> {
+    let mut tx = db.begin().await?;
+    let mut files = Vec::new();
+    for key in keys {
+        let Some((_, table)) = OBJECT_TABLES.iter().find(|(x, _)| *x == key.object_type) else {
+            continue;
+        };
+        sqlx::query(&format!("delete from {table} where id = $1"))
+            .bind(&key.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("delete from provenance where object_id = $1 and object_type = $2")
+            .bind(&key.id)
+            .bind(key.object_type.to_string())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("delete from links where source_id = $1 and source_type = $2")
+            .bind(&key.id)
+            .bind(key.object_type.to_string())
+            .execute(&mut *tx)
+            .await?;
+        let paths: Vec<(String,)> =
+            sqlx::query_as("delete from files where object_id = $1 returning local_path")
+                .bind(&key.id)
+                .fetch_all(&mut *tx)
+                .await?;
+        files.extend(paths.into_iter().map(|(path,)| path));
+        match key.object_type {
+            ObjectType::Block => {
+                sqlx::query("delete from block_rich_text where block_id = $1")
+                    .bind(&key.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            ObjectType::Page => {
+                sqlx::query("delete from pages_text where page_id = $1")
+                    .bind(&key.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            _ => (),
+        }
+    }
+    tx.commit().await?;
+
+    for path in files {
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// The columns of a block, see [`get_child_blocks`].
+#[derive(sqlx::FromRow)]
+struct BlockRow {
+    id: String,
+    parent_type: String,
+    parent_id: String,
+    created_time: DateTime<Utc>,
+    created_by: String,
+    last_edited_time: DateTime<Utc>,
+    last_edited_by: String,
+    archived: bool,
+    in_trash: bool,
+    child_index: i64,
+    has_children: bool,
+    block_type: String,
+    type_data: String,
+}
+
+impl BlockRow {
+    /// Rebuild the block, with empty data if `type_data` can't be parsed,
+    /// e.g. it's truncated.
+    fn into_block(self) -> serde_json::Result<Block> {
+        let parent = if self.parent_type == "workspace" {
+            serde_json::json!({ "type": "workspace", "workspace": true })
+        } else {
+            serde_json::json!({ "type": self.parent_type, self.parent_type.as_str(): self.parent_id })
+        };
+        let data = serde_json::from_str::<serde_json::Value>(&self.type_data)
+            .ok()
+            .and_then(|mut x| x.get_mut(&self.block_type).map(|x| x.take()))
+            .unwrap_or_else(|| serde_json::json!({}));
+        serde_json::from_value(serde_json::json!({
+            "object": "block",
+            "id": self.id,
+            "parent": parent,
+            "created_time": self.created_time,
+            "created_by": { "object": "user", "id": self.created_by },
+            "last_edited_time": self.last_edited_time,
+            "last_edited_by": { "object": "user", "id": self.last_edited_by },
+            "archived": self.archived,
+            "in_trash": self.in_trash,
+            "child_index": self.child_index,
+            "has_children": self.has_children,
+            "type": self.block_type,
+            self.block_type.as_str(): data,
+        }))
+    }
+}
+
+/// The blocks under `parent_id` which aren't deleted, i.e. archived or in
+/// trash, rebuilt from their columns.
+pub async fn get_child_blocks(
+    db: &mut SqliteConnection,
+    parent_id: &str,
+) -> sqlx::Result<Vec<Block>> {
+    let rows: Vec<BlockRow> = sqlx::query_as(
+        "select id, parent_type, parent_id, created_time, created_by, last_edited_time, \
+           last_edited_by, archived, in_trash, child_index, has_children, block_type, type_data \
+         from blocks where parent_id = $1",
+    )
+    .bind(parent_id)
+    .fetch_all(db)
+    .await?;
+    let mut blocks = Vec::new();
+    for row in rows {
+        let block = row
+            .into_block()
+            .map_err(|e| sqlx::Error::Decode(e.into()))?;
+        if !block.obj.is_deleted() {
+            blocks.push(block);
+        }
+    }
+    Ok(blocks)
+}
+
+/// The columns of a comment, see [`get_discussions`].
+#[derive(sqlx::FromRow)]
+struct CommentRow {
+    id: String,
+    parent_type: String,
+    parent_id: String,
+    created_time: DateTime<Utc>,
+    created_by: String,
+    last_edited_time: DateTime<Utc>,
+    discussion_id: String,
+    rich_text: Json<Vec<RichText>>,
+}
+
+impl CommentRow {
+    /// Rebuild the comment, `None` if the type of its parent isn't known.
+    fn into_comment(self) -> Option<Comment> {
+        let parent = match self.parent_type.as_str() {
+            "page_id" => Parent::Page {
+                page_id: self.parent_id,
+            },
+            "block_id" => Parent::Block {
+                block_id: self.parent_id,
+            },
+            _ => return None,
+        };
+        Some(Comment::new(
+            self.id,
+            parent,
+            self.created_time,
+            User::from_id(self.created_by),
+            self.last_edited_time,
+            self.discussion_id,
+            self.rich_text.0,
+        ))
+    }
+}
+
+/// Get the comments of `parent_id`, grouped into discussion threads by
+/// `discussion_id`, each thread is ordered by `created_time`.
+pub async fn get_discussions(
+    db: &mut SqliteConnection,
+    parent_id: &str,
+) -> sqlx::Result<BTreeMap<String, Vec<Comment>>> {
+    let rows: Vec<CommentRow> = sqlx::query_as(
+        "select id, parent_type, parent_id, created_time, created_by, last_edited_time, \
+         discussion_id, rich_text from comments \
+         where parent_id = $1 order by discussion_id, created_time",
+    )
+    .bind(parent_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut discussions = BTreeMap::<String, Vec<Comment>>::new();
+    for row in rows {
+        let parent_type = row.parent_type.clone();
+        let comment = row.into_comment().ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown parent type of comment: {parent_type}").into())
+        })?;
+        discussions
+            .entry(comment.discussion_id.clone())
+            .or_default()
+            .push(comment);
+    }
+    Ok(discussions)
+}
+
+/// Record that the object `key` is reached from the root page `root_id`.
+pub async fn insert_provenance(
+    db: &mut SqliteConnection,
+    key: &ObjectKey,
+    root_id: &str,
+) -> sqlx::Result<SqliteQueryResult> {
+    sqlx::query("insert or ignore into provenance values ($1, $2, $3)")
+        .bind(&key.id)
+        .bind(key.object_type.to_string())
+        .bind(root_id)
+        .execute(db)
+        .await
+}
+
+/// Record that the objects under the root page `root_id` in db (including
+/// itself) are reached from it, e.g. the objects emitted under another root
+/// which contains `root_id`.
+pub async fn insert_subtree_provenance(
+    db: &mut SqliteConnection,
+    root_id: &str,
+) -> sqlx::Result<()> {
+    sqlx::query(&format!(
+        "{SUBTREE} \
+         insert or ignore into provenance (object_id, object_type, root_id) \
+         select id, object_type, $2 from tree"
+    ))
+    .bind(root_id.replace('-', ""))
+    .bind(root_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Replace the full-text indexed content of the page.
+pub async fn update_page_text(
+    db: &mut SqliteConnection,
+    page_id: &str,
+    text: &str,
+) -> sqlx::Result<()> {
+    sqlx::query("delete from pages_text where page_id = $1")
+        .bind(page_id)
+        .execute(&mut *db)
+        .await?;
+    sqlx::query("insert into pages_text (page_id, content) values ($1, $2)")
+        .bind(page_id)
+        .bind(text)
+        .execute(&mut *db)
+        .await?;
+    Ok(())
+}
+
+/// Replace the rich text of the block, see the `block_rich_text` table.
+pub async fn update_block_rich_text(
+    db: &mut SqliteConnection,
+    block_id: &str,
+    rich_text: &[RichText],
+) -> sqlx::Result<()> {
+    sqlx::query("delete from block_rich_text where block_id = $1")
+        .bind(block_id)
+        .execute(&mut *db)
+        .await?;
+    for (position, x) in rich_text.iter().enumerate() {
+        sqlx::query("insert into block_rich_text values ($1, $2, $3, $4, $5)")
+            .bind(block_id)
+            .bind(position as i64)
+            .bind(&x.plain_text)
+            .bind(&x.href)
+            .bind(serde_json::to_string(&x.annotations).unwrap())
+            .execute(&mut *db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Full-text search the content of pages synced with `--index-text`, the
+/// most relevant pages come first. `query` is in the [FTS5 query
+/// syntax](https://www.sqlite.org/fts5.html#full_text_query_syntax).
+pub async fn search_pages(db: &mut SqliteConnection, query: &str) -> sqlx::Result<Vec<ObjectKey>> {
+    let ids: Vec<(String,)> =
+        sqlx::query_as("select page_id from pages_text where pages_text match $1 order by rank")
+            .bind(query)
+            .fetch_all(db)
+            .await?;
+    Ok(ids
+        .into_iter()
+        .map(|(id,)| ObjectKey::new(ObjectType::Page, id))
+        .collect())
+}
+
+/// A JSON field of an object which is truncated before it's stored, see
+/// [`insert_or_update_block`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedField {
+    pub id: String,
+    pub field: &'static str,
+    /// The size of the original JSON text.
+    pub bytes: usize,
+}
+
+/// The JSON text of a field. If the text exceeds `max_bytes`, it's replaced
+/// by a marker object which keeps a prefix of the text, i.e.
+/// `{"truncated": true, "bytes": <original size>, "prefix": "..."}`, and the
+/// field is added to `truncated`.
+pub(crate) fn json_field(
+    json: String,
+    max_bytes: Option<usize>,
+    field: &'static str,
+    id: &str,
+    truncated: &mut Vec<TruncatedField>,
+) -> String {
+    let Some(max_bytes) = max_bytes.filter(|max| json.len() > *max) else {
+        return json;
+    };
+
+    let mut end = max_bytes;
+    while !json.is_char_boundary(end) {
+        end -= 1;
+    }
+    truncated.push(TruncatedField {
+        id: id.to_owned(),
+        field,
+        bytes: json.len(),
+    });
+    serde_json::json!({
+        "truncated": true,
+        "bytes": json.len(),
+        "prefix": &json[..end],
+    })
+    .to_string()
+}
+
+pub(crate) const OBJECT_TABLES: [(ObjectType, &str); 4] = [
+    (ObjectType::Block, "blocks"),
+    (ObjectType::Page, "pages"),
+    (ObjectType::Database, "databases"),
+    (ObjectType::Comment, "comments"),
+];
+
+/// Renumber the blocks under the parents with duplicate `child_index`
+/// values, e.g. stale blocks left after the blocks are moved, to `0..count`
+/// in the order of `child_index`, `created_time` then ID (see
+/// `Block::sibling_order`). Return the number of the renumbered parents.
+pub async fn renumber_duplicate_child_indexes(db: &mut SqliteConnection) -> sqlx::Result<u64> {
+    let mut tx = db.begin().await?;
+    let parents: Vec<(String,)> = sqlx::query_as(
+        "select parent_id from blocks group by parent_id \
+         having count(distinct child_index) != count(*)",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for (parent_id,) in parents.iter() {
+        sqlx::query(
+            "update blocks set child_index = x.rn - 1 from ( \
+               select id, row_number() over (order by child_index, created_time, id) as rn \
+               from blocks where parent_id = $1) x \
+             where blocks.id = x.id",
+        )
+        .bind(parent_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(parents.len() as u64)
+}
+
+/// Guard against duplicate `child_index` values with a unique index on
+/// `(parent_id, child_index)`, after renumbering the existing duplicates
+/// (see [`renumber_duplicate_child_indexes`]). Then storing a block replaces
+/// the stale block at its index, since the blocks are stored with `insert or
+/// replace`.
+pub async fn create_unique_child_index(db: &mut SqliteConnection) -> sqlx::Result<()> {
+    renumber_duplicate_child_indexes(db).await?;
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS blocks_child_index ON blocks (parent_id, child_index)",
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Drop the unique index of [`create_unique_child_index`], so that storing
+/// a block no longer replaces another block at its index.
+pub async fn drop_unique_child_index(db: &mut SqliteConnection) -> sqlx::Result<()> {
+    sqlx::query("DROP INDEX IF EXISTS blocks_child_index")
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Insert or replace the block, return its JSON fields which are truncated
+/// since they're larger than `max_field_bytes`.
 pub async fn insert_or_update_block(
     db: &mut SqliteConnection,
     block: Block,
-) -> sqlx::error::Result<SqliteQueryResult> {
+    max_field_bytes: Option<usize>,
+) -> sqlx::Result<Vec<TruncatedField>> {
+    let id = block.id().to_owned();
+    let mut truncated = Vec::new();
+    let checked = block.as_todo().map(|x| x.checked);
+    let raw_json = json_field(
+        serde_json::to_string(&block).unwrap(),
+        max_field_bytes,
+        "raw_json",
+        &id,
+        &mut truncated,
+    );
     sqlx::query(
         "insert or replace into blocks \
-         values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+         values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, CURRENT_TIMESTAMP, $14, $15)",
     )
-    .bind(block.id().to_owned())
+    .bind(&id)
     .bind(block.obj.parent_type().to_string())
     .bind(block.obj.parent.id())
     .bind(block.obj.created_time)
@@ -44,20 +577,39 @@ pub async fn insert_or_update_block(
     .bind(block.child_index as i64)
     .bind(block.has_children)
     .bind(block.block_type.to_string())
-    .bind(serde_json::to_string(&block.type_data).unwrap())
+    .bind(json_field(
+        serde_json::to_string(&block.type_data).unwrap(),
+        max_field_bytes,
+        "type_data",
+        &id,
+        &mut truncated,
+    ))
+    .bind(checked)
+    .bind(raw_json)
     .execute(db)
-    .await
+    .await?;
+    Ok(truncated)
 }
 
 pub async fn insert_or_update_page(
     db: &mut SqliteConnection,
     page: Page,
-) -> sqlx::error::Result<SqliteQueryResult> {
+    max_field_bytes: Option<usize>,
+) -> sqlx::Result<Vec<TruncatedField>> {
+    let id = page.id().to_owned();
+    let mut truncated = Vec::new();
+    let raw_json = json_field(
+        serde_json::to_string(&page).unwrap(),
+        max_field_bytes,
+        "raw_json",
+        &id,
+        &mut truncated,
+    );
     sqlx::query(
         "insert or replace into pages \
-         values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+         values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, CURRENT_TIMESTAMP, $15)",
     )
-    .bind(page.id().to_owned())
+    .bind(&id)
     .bind(page.obj.parent_type().to_string())
     .bind(page.obj.parent.id())
     .bind(page.obj.created_time)
@@ -66,24 +618,42 @@ pub async fn insert_or_update_page(
     .bind(page.obj.last_edited_by.id())
     .bind(page.obj.archived)
     .bind(page.obj.in_trash)
-    .bind(serde_json::to_string(&page.properties).unwrap())
+    .bind(json_field(
+        serde_json::to_string(&page.properties).unwrap(),
+        max_field_bytes,
+        "properties",
+        &id,
+        &mut truncated,
+    ))
     .bind(page.url)
     .bind(page.public_url)
     .bind(page.icon.map(|x| x.to_string()))
     .bind(page.cover.map(|x| x.to_string()))
+    .bind(raw_json)
     .execute(db)
-    .await
+    .await?;
+    Ok(truncated)
 }
 
 pub async fn insert_or_update_database(
     db: &mut SqliteConnection,
     database: Database,
-) -> sqlx::error::Result<SqliteQueryResult> {
+    max_field_bytes: Option<usize>,
+) -> sqlx::Result<Vec<TruncatedField>> {
+    let id = database.id().to_owned();
+    let mut truncated = Vec::new();
+    let raw_json = json_field(
+        serde_json::to_string(&database).unwrap(),
+        max_field_bytes,
+        "raw_json",
+        &id,
+        &mut truncated,
+    );
     sqlx::query(
         "insert or replace into databases \
-         values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)",
+         values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, CURRENT_TIMESTAMP, $18, $19)",
     )
-    .bind(database.id().to_owned())
+    .bind(&id)
     .bind(database.obj.parent_type().to_string())
     .bind(database.obj.parent.id())
     .bind(database.obj.created_time)
@@ -92,48 +662,303 @@ pub async fn insert_or_update_database(
     .bind(database.obj.last_edited_by.id())
     .bind(database.obj.archived)
     .bind(database.obj.in_trash)
-    .bind(serde_json::to_string(&database.properties).unwrap())
+    .bind(json_field(
+        serde_json::to_string(&database.properties).unwrap(),
+        max_field_bytes,
+        "properties",
+        &id,
+        &mut truncated,
+    ))
     .bind(database.url)
     .bind(database.public_url)
     .bind(database.icon.map(|x| x.to_string()))
     .bind(database.cover.map(|x| x.to_string()))
     .bind(database.is_inline)
-    .bind(serde_json::to_string(&database.title).unwrap())
-    .bind(serde_json::to_string(&database.description).unwrap())
+    .bind(json_field(
+        serde_json::to_string(&database.title).unwrap(),
+        max_field_bytes,
+        "title",
+        &id,
+        &mut truncated,
+    ))
+    .bind(json_field(
+        serde_json::to_string(&database.description).unwrap(),
+        max_field_bytes,
+        "description",
+        &id,
+        &mut truncated,
+    ))
+    .bind(serde_json::to_string(&database.data_sources).unwrap())
+    .bind(raw_json)
     .execute(db)
-    .await
+    .await?;
+    Ok(truncated)
 }
 
 pub async fn insert_or_update_comment(
     db: &mut SqliteConnection,
     comment: Comment,
-) -> sqlx::error::Result<SqliteQueryResult> {
+    max_field_bytes: Option<usize>,
+) -> sqlx::Result<Vec<TruncatedField>> {
+    let id = comment.id().to_owned();
+    let mut truncated = Vec::new();
+    let raw_json = json_field(
+        serde_json::to_string(&comment).unwrap(),
+        max_field_bytes,
+        "raw_json",
+        &id,
+        &mut truncated,
+    );
     sqlx::query(
         "insert or replace into comments \
-         values ($1, $2, $3, $4, $5, $6, $7, $8)",
+         values ($1, $2, $3, $4, $5, $6, $7, $8, CURRENT_TIMESTAMP, $9)",
     )
-    .bind(comment.id().to_owned())
+    .bind(&id)
     .bind(comment.parent.r#type().to_string())
     .bind(comment.parent.id())
     .bind(comment.created_time)
     .bind(comment.created_by.id())
     .bind(comment.last_edited_time)
     .bind(comment.discussion_id)
+    // not truncated, it's parsed by `get_discussions`
     .bind(serde_json::to_string(&comment.rich_text).unwrap())
+    .bind(raw_json)
+    .execute(db)
+    .await?;
+    Ok(truncated)
+}
+
+/// Insert a user, or fill in the fields of an existing one. The users
+/// embedded in `created_by`/`last_edited_by` usually have only their IDs, so
+/// a missing field never overwrites a known one.
+pub async fn insert_or_update_user(
+    db: &mut SqliteConnection,
+    user: &User,
+) -> sqlx::error::Result<SqliteQueryResult> {
+    sqlx::query(
+        "insert into users values ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP) \
+         on conflict (id) do update set \
+           type = coalesce(excluded.type, type), \
+           name = coalesce(excluded.name, name), \
+           avatar_url = coalesce(excluded.avatar_url, avatar_url), \
+           email = coalesce(excluded.email, email), \
+           synced_at = excluded.synced_at",
+    )
+    .bind(user.id())
+    .bind(user.r#type.map(|x| x.to_string()))
+    .bind(&user.name)
+    .bind(&user.avatar_url)
+    .bind(user.email())
     .execute(db)
     .await
 }
 
-// async fn save_object(obj: impl AnyObject, dir: &str) -> Result<(), Box<dyn Error>> {
-//     // save
-//     let name = format!("{}-{}.json", obj.object_type(), obj.id());
-//     let p = path::Path::new(dir).join(name);
-//     let mut file = File::create(p).await?;
-//     let data = serde_json::to_vec_pretty(&obj)?;
-//     file.write_all(&data).await?;
+/// Record the database views referenced by the object `source_id`.
+pub async fn insert_or_update_views(
+    db: &mut SqliteConnection,
+    source_id: &str,
+    views: &[ViewRef],
+) -> sqlx::error::Result<()> {
+    for view in views {
+        sqlx::query(
+            "insert or replace into views \
+             values ($1, $2, $3, $4)",
+        )
+        .bind(&view.view_id)
+        .bind(&view.database_id)
+        .bind(source_id)
+        .bind(&view.url)
+        .execute(&mut *db)
+        .await?;
+    }
+    Ok(())
+}
 
-//     Ok(())
-// }
+/// The ID of a link target as stored in the `links` table, i.e. hyphenated
+/// like the IDs of the objects.
+fn link_target_id(id: &str) -> String {
+    hyphenated_id(id).unwrap_or_else(|| id.to_owned())
+}
+
+/// Replace the links from the object `source`.
+pub async fn update_links(
+    db: &mut SqliteConnection,
+    source: &ObjectKey,
+    links: &[LinkRef],
+) -> sqlx::Result<()> {
+    sqlx::query("delete from links where source_id = $1")
+        .bind(&source.id)
+        .execute(&mut *db)
+        .await?;
+    for link in links {
+        sqlx::query("insert or replace into links values ($1, $2, $3, $4, $5)")
+            .bind(&source.id)
+            .bind(source.object_type.to_string())
+            .bind(link_target_id(&link.target.id))
+            .bind(link.target.object_type.to_string())
+            .bind(link.kind.to_string())
+            .execute(&mut *db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// The objects linking to the page/database `target_id` (with or without
+/// dashes), i.e. mentioning, relating or linking to it, from the `links`
+/// table. A block is returned as is rather than the page containing it.
+pub async fn get_backlinks(
+    db: &mut SqliteConnection,
+    target_id: &str,
+) -> sqlx::Result<Vec<ObjectKey>> {
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        "select distinct source_type, source_id from links \
+         where target_id = $1 \
+         order by source_type, source_id",
+    )
+    .bind(link_target_id(target_id))
+    .fetch_all(db)
+    .await?;
+    rows.into_iter()
+        .map(|(object_type, id)| {
+            let object_type = serde_json::from_value(serde_json::Value::String(object_type))
+                .map_err(|e| sqlx::Error::Decode(e.into()))?;
+            Ok(ObjectKey::new(object_type, id))
+        })
+        .collect()
+}
+
+/// Migrations on top of [`SQL_SCHEMA`], applied in order by [`migrate`].
+/// Never modify or reorder existing entries, append new ones instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: when each row was synced
+    r#"
+ALTER TABLE blocks ADD COLUMN synced_at TEXT;
+ALTER TABLE pages ADD COLUMN synced_at TEXT;
+ALTER TABLE databases ADD COLUMN synced_at TEXT;
+ALTER TABLE comments ADD COLUMN synced_at TEXT;
+"#,
+    // 2: comment threads
+    r#"
+CREATE INDEX comments_discussion ON comments (parent_id, discussion_id, created_time);
+"#,
+    // 3: full-text index of page content
+    r#"
+CREATE VIRTUAL TABLE pages_text USING fts5(page_id UNINDEXED, content);
+"#,
+    // 4: which roots each object is reached from, one row per root
+    r#"
+CREATE TABLE provenance (
+    object_id TEXT not null,
+    object_type TEXT not null,
+    root_id TEXT not null,
+
+    primary key (object_id, object_type, root_id)
+);
+"#,
+    // 5: the data sources of databases, json array of `{id, name}`
+    r#"
+ALTER TABLE databases ADD COLUMN data_sources TEXT;
+"#,
+    // 6: links to pages/databases, i.e. mentions, relations & link to page
+    // blocks
+    r#"
+CREATE TABLE links (
+    source_id TEXT not null,
+    source_type TEXT not null,
+    target_id TEXT not null,
+    target_type TEXT not null,
+    -- mention, relation, link_to_page
+    kind TEXT not null,
+
+    primary key (source_id, target_id, kind)
+);
+CREATE INDEX links_target ON links (target_id);
+"#,
+    // 7: the rich text of blocks, one row per rich text object
+    r#"
+CREATE TABLE block_rich_text (
+    block_id TEXT not null,
+    -- index in the rich text array of the block
+    position INTEGER not null,
+    plain_text TEXT not null,
+    href TEXT,
+    -- json object
+    annotations TEXT not null,
+
+    primary key (block_id, position)
+);
+"#,
+    // 8: the checked state of to-do blocks, null for the other blocks
+    r#"
+ALTER TABLE blocks ADD COLUMN checked BOOLEAN;
+"#,
+    // 9: the users, including the ones only referenced by `created_by` &
+    // `last_edited_by`, whose other fields are null
+    r#"
+CREATE TABLE users (
+    id TEXT not null primary key,
+    -- person, bot
+    type TEXT,
+    name TEXT,
+    avatar_url TEXT,
+    email TEXT,
+    synced_at TEXT
+);
+"#,
+    // 10: the whole objects as json, with the fields which aren't parsed into
+    // the other columns
+    r#"
+ALTER TABLE blocks ADD COLUMN raw_json TEXT;
+ALTER TABLE pages ADD COLUMN raw_json TEXT;
+ALTER TABLE databases ADD COLUMN raw_json TEXT;
+ALTER TABLE comments ADD COLUMN raw_json TEXT;
+"#,
+    // 11: the syncs of each root
+    r#"
+CREATE TABLE sync_runs (
+    run_id INTEGER primary key autoincrement,
+    root_id TEXT not null,
+    started_at TEXT not null,
+    -- null if the sync is still running or interrupted
+    finished_at TEXT,
+    objects_count INTEGER,
+    -- running, ok, error
+    status TEXT not null
+);
+"#,
+    // 12: look up the children of a parent, the index of blocks also serves
+    // the lookups by `parent_id` only
+    r#"
+CREATE INDEX blocks_parent ON blocks (parent_id, child_index);
+CREATE INDEX pages_parent ON pages (parent_id);
+CREATE INDEX databases_parent ON databases (parent_id);
+CREATE INDEX comments_parent ON comments (parent_id);
+"#,
+    // 13: the files downloaded by `sync --download-files`
+    r#"
+CREATE TABLE files (
+    object_id TEXT not null,
+    -- file, icon, cover
+    field TEXT not null,
+    url TEXT not null,
+    -- null for the external files, whose URLs don't expire
+    expiry_time TEXT,
+    local_path TEXT not null,
+    -- of the object when the file is downloaded
+    last_edited_time TEXT not null,
+    downloaded_at TEXT not null,
+    primary key (object_id, field)
+);
+"#,
+    // 14: hyphenate the IDs of the link targets, so that they are looked up
+    // by the index
+    r#"
+UPDATE OR REPLACE links
+SET target_id = substr(target_id, 1, 8) || '-' || substr(target_id, 9, 4) || '-' ||
+    substr(target_id, 13, 4) || '-' || substr(target_id, 17, 4) || '-' || substr(target_id, 21)
+WHERE length(target_id) = 32 AND instr(target_id, '-') = 0;
+"#,
+];
 
 const SQL_SCHEMA: &str = r#"
 CREATE TABLE IF NOT EXISTS blocks (
@@ -234,4 +1059,395 @@ CREATE TABLE IF NOT EXISTS comments (
     -- array of rich text objects
     rich_text TEXT not null
 );
+
+-- Database views referenced by links. The public API doesn't expose views, so
+-- only the view IDs found in links are recorded here.
+CREATE TABLE IF NOT EXISTS views (
+    id TEXT not null,
+    database_id TEXT not null,
+
+    -- the object in which the view link is found
+    source_id TEXT not null,
+    url TEXT not null,
+
+    primary key (id, source_id)
+);
 "#;
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use notion_async_api::{
+        LinkKind, LinkRef, Object, ObjectKey, ObjectType, ParentType, RichText,
+    };
+    use serde_json::json;
+    use sqlx::{types::chrono::Utc, SqliteConnection};
+
+    use crate::tests::{block_json, memory_db, page_json, user_json};
+
+    use super::{
+        create_unique_child_index, current_time, delete_objects, drop_unique_child_index,
+        get_backlinks, get_discussions, insert_or_update_block, insert_or_update_comment,
+        insert_or_update_page, insert_provenance, insert_subtree_provenance,
+        renumber_duplicate_child_indexes, stale_descendants, update_links, TruncatedField,
+        MIGRATIONS,
+    };
+
+    async fn insert_block(
+        db: &mut SqliteConnection,
+        id: &str,
+        parent_id: &str,
+        child_index: usize,
+    ) {
+        let block = serde_json::from_value(block_json(id, parent_id, child_index)).unwrap();
+        insert_or_update_block(db, block, None).await.unwrap();
+    }
+
+    /// The blocks under `parent_id`, in the order of `child_index`.
+    async fn children(db: &mut SqliteConnection, parent_id: &str) -> Vec<(String, i64)> {
+        sqlx::query_as(
+            "select id, child_index from blocks where parent_id = $1 order by child_index, id",
+        )
+        .bind(parent_id)
+        .fetch_all(db)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn renumber_duplicates() {
+        let mut db = memory_db().await;
+        insert_block(&mut db, "b2", "p1", 0).await;
+        insert_block(&mut db, "b1", "p1", 0).await;
+        insert_block(&mut db, "b3", "p1", 1).await;
+        insert_block(&mut db, "c1", "p2", 0).await;
+        insert_block(&mut db, "c2", "p2", 3).await;
+
+        // only p1 has duplicates, the ties are broken by ID
+        assert_eq!(renumber_duplicate_child_indexes(&mut db).await.unwrap(), 1);
+        assert_eq!(
+            children(&mut db, "p1").await,
+            [("b1".into(), 0), ("b2".into(), 1), ("b3".into(), 2)]
+        );
+        assert_eq!(
+            children(&mut db, "p2").await,
+            [("c1".into(), 0), ("c2".into(), 3)]
+        );
+        assert_eq!(renumber_duplicate_child_indexes(&mut db).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn unique_child_index() {
+        let mut db = memory_db().await;
+        insert_block(&mut db, "b1", "p1", 0).await;
+        insert_block(&mut db, "b2", "p1", 0).await;
+
+        // the existing duplicates are renumbered, then a block replaces the
+        // stale block at its index
+        create_unique_child_index(&mut db).await.unwrap();
+        insert_block(&mut db, "b3", "p1", 1).await;
+        assert_eq!(
+            children(&mut db, "p1").await,
+            [("b1".into(), 0), ("b3".into(), 1)]
+        );
+
+        // without the index the duplicates are kept
+        drop_unique_child_index(&mut db).await.unwrap();
+        insert_block(&mut db, "b4", "p1", 1).await;
+        assert_eq!(
+            children(&mut db, "p1").await,
+            [("b1".into(), 0), ("b3".into(), 1), ("b4".into(), 1)]
+        );
+        drop_unique_child_index(&mut db).await.unwrap();
+    }
+
+    /// Make the objects look synced long ago.
+    async fn make_stale(db: &mut SqliteConnection, table: &str, ids: &[&str]) {
+        for id in ids {
+            sqlx::query(&format!(
+                "update {table} set synced_at = '2024-01-01 00:00:00' where id = $1"
+            ))
+            .bind(id)
+            .execute(&mut *db)
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_and_delete() {
+        let mut db = memory_db().await;
+        for (id, parent_id) in [
+            ("1234-5678", "workspace"),
+            ("r2", "workspace"),
+            ("p1", "1234-5678"),
+        ] {
+            let page = serde_json::from_value(page_json(id, parent_id)).unwrap();
+            insert_or_update_page(&mut db, page, None).await.unwrap();
+        }
+        for (id, parent_id, child_index) in [
+            ("b1", "1234-5678", 0),
+            ("b2", "1234-5678", 1),
+            ("b3", "b2", 0),
+            ("c1", "r2", 0),
+        ] {
+            insert_block(&mut db, id, parent_id, child_index).await;
+        }
+        make_stale(&mut db, "pages", &["p1", "r2"]).await;
+        make_stale(&mut db, "blocks", &["b2", "b3", "c1"]).await;
+        let b2 = ObjectKey::new(ObjectType::Block, "b2".to_owned());
+        insert_provenance(&mut db, &b2, "1234-5678").await.unwrap();
+
+        // only the subtree of the root, found with or without dashes
+        let since = Utc::now() - Duration::from_secs(60);
+        let stale = stale_descendants(&mut db, "12345678", since).await.unwrap();
+        let key = |object_type, id: &str| ObjectKey::new(object_type, id.to_owned());
+        assert_eq!(
+            stale,
+            [
+                key(ObjectType::Block, "b2"),
+                key(ObjectType::Block, "b3"),
+                key(ObjectType::Page, "p1"),
+            ]
+        );
+
+        delete_objects(&mut db, &stale).await.unwrap();
+        assert_eq!(children(&mut db, "1234-5678").await, [("b1".into(), 0)]);
+        assert_eq!(children(&mut db, "r2").await, [("c1".into(), 0)]);
+        let (provenance,): (i64,) = sqlx::query_as("select count(*) from provenance")
+            .fetch_one(&mut db)
+            .await
+            .unwrap();
+        assert_eq!(provenance, 0);
+        assert!(stale_descendants(&mut db, "12345678", since)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn stale_since_current_time() {
+        let mut db = memory_db().await;
+        let page = serde_json::from_value(page_json("r1", "workspace")).unwrap();
+        insert_or_update_page(&mut db, page, None).await.unwrap();
+        insert_block(&mut db, "b1", "r1", 0).await;
+        make_stale(&mut db, "blocks", &["b1"]).await;
+
+        // the objects synced after the db time aren't stale, whatever the
+        // local clock is
+        let since = current_time(&mut db).await.unwrap();
+        insert_block(&mut db, "b2", "r1", 1).await;
+        let stale = stale_descendants(&mut db, "r1", since).await.unwrap();
+        assert_eq!(stale, [ObjectKey::new(ObjectType::Block, "b1".to_owned())]);
+    }
+
+    #[tokio::test]
+    async fn subtree_provenance() {
+        let mut db = memory_db().await;
+        for (id, parent_id) in [
+            ("r1", "workspace"),
+            ("p1", "r1"),
+            ("p2", "p1"),
+            ("x1", "r2"),
+        ] {
+            let page = serde_json::from_value(page_json(id, parent_id)).unwrap();
+            insert_or_update_page(&mut db, page, None).await.unwrap();
+        }
+        insert_block(&mut db, "b1", "p2", 0).await;
+        // emitted under r0, which contains p1
+        insert_provenance(&mut db, &ObjectKey::new(ObjectType::Page, "p1"), "r0")
+            .await
+            .unwrap();
+
+        insert_subtree_provenance(&mut db, "p1").await.unwrap();
+        insert_subtree_provenance(&mut db, "p1").await.unwrap();
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            "select object_type, object_id, root_id from provenance order by 1, 2, 3",
+        )
+        .fetch_all(&mut db)
+        .await
+        .unwrap();
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|(t, id, root)| format!("{t} {id} {root}"))
+            .collect();
+        assert_eq!(
+            rows,
+            ["block b1 p1", "page p1 p1", "page p1 r0", "page p2 p1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn truncate_fields() {
+        let mut db = memory_db().await;
+        let text = serde_json::to_value(RichText::text("x".repeat(500))).unwrap();
+        let mut block = block_json("b1", "p1", 0);
+        block["paragraph"]["rich_text"] = json!([text]);
+        let truncated =
+            insert_or_update_block(&mut db, serde_json::from_value(block).unwrap(), Some(300))
+                .await
+                .unwrap();
+        let fields: Vec<_> = truncated.iter().map(|x| x.field).collect();
+        assert_eq!(fields, ["raw_json", "type_data"]);
+        let (type_data,): (String,) = sqlx::query_as("select type_data from blocks")
+            .fetch_one(&mut db)
+            .await
+            .unwrap();
+        let type_data: serde_json::Value = serde_json::from_str(&type_data).unwrap();
+        assert_eq!(type_data["truncated"], json!(true));
+        assert_eq!(type_data["prefix"].as_str().unwrap().len(), 300);
+
+        // the rich text of comments is kept for `get_discussions`
+        let comment = json!({
+            "object": "comment",
+            "id": "c1",
+            "parent": { "type": "page_id", "page_id": "p1" },
+            "created_time": "2024-08-01T00:00:00.000Z",
+            "created_by": user_json("user"),
+            "last_edited_time": "2024-08-01T00:00:00.000Z",
+            "discussion_id": "d1",
+            "rich_text": [text],
+        });
+        let truncated =
+            insert_or_update_comment(&mut db, serde_json::from_value(comment).unwrap(), Some(300))
+                .await
+                .unwrap();
+        assert_eq!(
+            truncated,
+            [TruncatedField {
+                id: "c1".to_owned(),
+                field: "raw_json",
+                bytes: truncated[0].bytes,
+            }]
+        );
+        let discussions = get_discussions(&mut db, "p1").await.unwrap();
+        assert_eq!(discussions["d1"][0].rich_text[0].plain_text.len(), 500);
+
+        assert!(
+            insert_or_update_comment(&mut db, discussions["d1"][0].clone(), None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn discussions() {
+        let mut db = memory_db().await;
+        for (id, parent, discussion_id, created_time) in [
+            ("c1", ("page_id", "p1"), "d2", "2024-08-02T00:00:00.000Z"),
+            ("c2", ("page_id", "p1"), "d1", "2024-08-01T00:00:00.000Z"),
+            ("c3", ("page_id", "p1"), "d2", "2024-08-01T00:00:00.000Z"),
+            ("c4", ("block_id", "b1"), "d3", "2024-08-01T00:00:00.000Z"),
+        ] {
+            let comment = json!({
+                "object": "comment",
+                "id": id,
+                "parent": { "type": parent.0, parent.0: parent.1 },
+                "created_time": created_time,
+                "created_by": user_json("user"),
+                "last_edited_time": created_time,
+                "discussion_id": discussion_id,
+                "rich_text": [RichText::text(id)],
+            });
+            let comment = serde_json::from_value(comment).unwrap();
+            insert_or_update_comment(&mut db, comment, None)
+                .await
+                .unwrap();
+        }
+
+        let discussions = get_discussions(&mut db, "p1").await.unwrap();
+        let ids: Vec<(&str, Vec<&str>)> = discussions
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.iter().map(|x| x.id.as_str()).collect()))
+            .collect();
+        assert_eq!(ids, [("d1", vec!["c2"]), ("d2", vec!["c3", "c1"])]);
+        let c1 = &discussions["d2"][1];
+        assert_eq!(c1.parent.r#type(), ParentType::PageId);
+        assert_eq!(c1.parent.id(), "p1");
+        assert_eq!(c1.created_by.id(), "user");
+        assert_eq!(c1.created_time, c1.last_edited_time);
+        assert_eq!(c1.rich_text[0].plain_text, "c1");
+
+        let discussions = get_discussions(&mut db, "b1").await.unwrap();
+        assert_eq!(discussions["d3"][0].parent.r#type(), ParentType::BlockId);
+    }
+
+    #[tokio::test]
+    async fn delete_downloaded_files() {
+        let mut db = memory_db().await;
+        let dir = tempfile::tempdir().unwrap();
+        insert_block(&mut db, "b1", "p1", 0).await;
+        for (object_id, name) in [("b1", "b1.png"), ("p1", "p1-cover.png")] {
+            let path = dir.path().join(name);
+            tokio::fs::write(&path, b"png").await.unwrap();
+            sqlx::query(
+                "insert into files values ($1, 'file', 'https://example.com', null, $2, \
+                 '2024-08-01T00:00:00Z', CURRENT_TIMESTAMP)",
+            )
+            .bind(object_id)
+            .bind(path.to_str().unwrap())
+            .execute(&mut db)
+            .await
+            .unwrap();
+        }
+        // the file of b1 is removed already
+        tokio::fs::remove_file(dir.path().join("b1.png"))
+            .await
+            .unwrap();
+
+        let b1 = ObjectKey::new(ObjectType::Block, "b1".to_owned());
+        delete_objects(&mut db, &[b1]).await.unwrap();
+        let files: Vec<(String,)> = sqlx::query_as("select object_id from files")
+            .fetch_all(&mut db)
+            .await
+            .unwrap();
+        assert_eq!(files, [("p1".into(),)]);
+        assert!(dir.path().join("p1-cover.png").exists());
+
+        let p1 = ObjectKey::new(ObjectType::Page, "p1".to_owned());
+        delete_objects(&mut db, &[p1]).await.unwrap();
+        assert!(!dir.path().join("p1-cover.png").exists());
+    }
+
+    #[tokio::test]
+    async fn backlinks() {
+        let mut db = memory_db().await;
+        let target = "9bc30ad4-9373-46a5-84ab-0a7845ee52e6";
+        let link = |id: &str, kind| LinkRef {
+            target: ObjectKey::new(ObjectType::Page, id.to_owned()),
+            kind,
+        };
+        // a link from a notion URL carries the ID without dashes
+        let b1 = ObjectKey::new(ObjectType::Block, "b1".to_owned());
+        let undashed = target.replace('-', "");
+        update_links(&mut db, &b1, &[link(&undashed, LinkKind::Mention)])
+            .await
+            .unwrap();
+        let p2 = ObjectKey::new(ObjectType::Page, "p2".to_owned());
+        update_links(&mut db, &p2, &[link(target, LinkKind::LinkToPage)])
+            .await
+            .unwrap();
+
+        for id in [target, undashed.as_str()] {
+            assert_eq!(
+                get_backlinks(&mut db, id).await.unwrap(),
+                [b1.clone(), p2.clone()]
+            );
+        }
+
+        // the links written before the IDs are hyphenated
+        sqlx::query("update links set target_id = $1 where source_id = 'b1'")
+            .bind(&undashed)
+            .execute(&mut db)
+            .await
+            .unwrap();
+        sqlx::query(MIGRATIONS[13]).execute(&mut db).await.unwrap();
+        let targets: Vec<(String,)> = sqlx::query_as("select distinct target_id from links")
+            .fetch_all(&mut db)
+            .await
+            .unwrap();
+        assert_eq!(targets, [(target.to_owned(),)]);
+    }
+}
@@ -0,0 +1,354 @@
+use std::path::{Path, PathBuf};
+
+use notion_async_api::{
+    AnyObject, Api, Block, Database, NotionFile, Object, ObjectKey, ObjectType, Page,
+};
+use reqwest::{Client, Url};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    SqliteConnection,
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+pub type DownloadError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The number of the files waiting to be downloaded, before
+/// [`FileDownloader::enqueue`] waits for the queue.
+const QUEUE_SIZE: usize = 64;
+
+/// Downloads the files of the synced objects (see [`AnyObject::files`]) into
+/// a directory, named `{id}` for blocks, `{id}-icon` & `{id}-cover` for
+/// pages/databases, with the extension in the URL. The local paths are
+/// recorded in the `files` table.
+///
+/// The files are downloaded one by one by a background task, through a
+/// bounded queue, so that storing the objects isn't held up by the downloads.
+/// The finished downloads are recorded by [`FileDownloader::record_finished`]
+/// and [`FileDownloader::finish`].
+///
+/// A file is only downloaded again if the object is edited since, or the
+/// local file is missing. The URLs of the files hosted by Notion expire
+/// (in an hour), an expired URL is refreshed by retrieving the object again.
+pub struct FileDownloader {
+    queue: mpsc::Sender<Download>,
+    finished: mpsc::UnboundedReceiver<(Download, Fetched)>,
+    worker: JoinHandle<()>,
+}
+
+/// A file of an object to be downloaded.
+struct Download {
+    key: ObjectKey,
+    field: &'static str,
+    file: NotionFile,
+    last_edited_time: DateTime<Utc>,
+}
+
+// the file with the URL it's downloaded from and its local path, or `None`
+// if it's removed from the object since
+type Fetched = Result<Option<(NotionFile, PathBuf)>, DownloadError>;
+
+/// The outcome of downloading a file of the object `key`.
+#[derive(Debug)]
+pub struct Downloaded {
+    pub key: ObjectKey,
+    pub result: Result<PathBuf, DownloadError>,
+}
+
+impl FileDownloader {
+    /// Start downloading into `dir` in the background.
+    pub fn new(dir: impl Into<PathBuf>, api: Api) -> Self {
+        let (queue, mut rx) = mpsc::channel::<Download>(QUEUE_SIZE);
+        let (finished_tx, finished) = mpsc::unbounded_channel();
+        let worker = Worker {
+            dir: dir.into(),
+            client: Client::new(),
+            api,
+        };
+        let worker = tokio::spawn(async move {
+            while let Some(download) = rx.recv().await {
+                let res = worker.download(&download).await;
+                if finished_tx.send((download, res)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            queue,
+            finished,
+            worker,
+        }
+    }
+
+    /// Queue the files of `obj` which aren't downloaded yet, waiting if the
+    /// queue is full.
+    pub async fn enqueue(
+        &mut self,
+        db: &mut SqliteConnection,
+        obj: &AnyObject,
+    ) -> Result<(), DownloadError> {
+        let last_edited_time = match obj {
+            AnyObject::Block(block) => block.obj.last_edited_time,
+            AnyObject::Page(page) => page.obj.last_edited_time,
+            AnyObject::Database(database) => database.obj.last_edited_time,
+            AnyObject::User(_) | AnyObject::Comment(_) => return Ok(()),
+        };
+
+        for (field, file) in obj.files() {
+            if is_downloaded(db, obj.id(), field, last_edited_time).await? {
+                continue;
+            }
+            let download = Download {
+                key: obj.key(),
+                field,
+                file,
+                last_edited_time,
+            };
+            self.queue
+                .send(download)
+                .await
+                .map_err(|_| "the download worker has stopped")?;
+        }
+        Ok(())
+    }
+
+    /// Record the downloads finished so far in db.
+    pub async fn record_finished(
+        &mut self,
+        db: &mut SqliteConnection,
+    ) -> sqlx::Result<Vec<Downloaded>> {
+        let mut downloaded = Vec::new();
+        while let Ok((download, res)) = self.finished.try_recv() {
+            downloaded.extend(record(db, download, res).await?);
+        }
+        Ok(downloaded)
+    }
+
+    /// Wait for the queued downloads, and record them in db.
+    pub async fn finish(self, db: &mut SqliteConnection) -> sqlx::Result<Vec<Downloaded>> {
+        let Self {
+            queue,
+            mut finished,
+            worker,
+        } = self;
+        drop(queue);
+        let mut downloaded = Vec::new();
+        while let Some((download, res)) = finished.recv().await {
+            downloaded.extend(record(db, download, res).await?);
+        }
+        // the worker has stopped since the channel is closed
+        let _ = worker.await;
+        Ok(downloaded)
+    }
+}
+
+/// Record a finished download in db if it succeeded.
+async fn record(
+    db: &mut SqliteConnection,
+    download: Download,
+    res: Fetched,
+) -> sqlx::Result<Option<Downloaded>> {
+    let result = match res {
+        Ok(Some((file, path))) => {
+            record_file(
+                db,
+                &download.key.id,
+                download.field,
+                &file,
+                &path,
+                download.last_edited_time,
+            )
+            .await?;
+            Ok(path)
+        }
+        Ok(None) => return Ok(None),
+        Err(e) => Err(e),
+    };
+    Ok(Some(Downloaded {
+        key: download.key,
+        result,
+    }))
+}
+
+/// Downloads the queued files in the background.
+struct Worker {
+    dir: PathBuf,
+    client: Client,
+    api: Api,
+}
+
+impl Worker {
+    /// Download a file, refreshing its URL if it has expired.
+    async fn download(&self, download: &Download) -> Fetched {
+        let file = match &download.file {
+            NotionFile::File { file: data } if data.expiry_time <= Utc::now() => {
+                match self.refresh(&download.key, download.field).await? {
+                    Some(file) => file,
+                    None => return Ok(None),
+                }
+            }
+            file => file.clone(),
+        };
+        let path = self
+            .dir
+            .join(file_name(&download.key.id, download.field, file.url()));
+        self.fetch(file.url(), &path).await?;
+        Ok(Some((file, path)))
+    }
+
+    /// Retrieve the object `key` again for a fresh URL of its file in
+    /// `field`.
+    async fn refresh(
+        &self,
+        key: &ObjectKey,
+        field: &str,
+    ) -> Result<Option<NotionFile>, DownloadError> {
+        let id = key.id.as_str();
+        let obj = match key.object_type {
+            ObjectType::Block => AnyObject::Block(self.api.get_object::<Block>(id).await?),
+            ObjectType::Page => AnyObject::Page(self.api.get_object::<Page>(id).await?),
+            ObjectType::Database => AnyObject::Database(self.api.get_object::<Database>(id).await?),
+            _ => return Ok(None),
+        };
+        Ok(obj
+            .files()
+            .into_iter()
+            .find(|(x, _)| *x == field)
+            .map(|(_, file)| file))
+    }
+
+    /// Download `url` to `path`, via a temporary file so that an interrupted
+    /// download isn't taken as downloaded.
+    async fn fetch(&self, url: &str, path: &Path) -> Result<(), DownloadError> {
+        let res = self.client.get(url).send().await?.error_for_status()?;
+        let bytes = res.bytes().await?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let part = path.with_extension("part");
+        tokio::fs::write(&part, bytes).await?;
+        tokio::fs::rename(&part, path).await?;
+        Ok(())
+    }
+}
+
+/// `{id}` for the file of a block, `{id}-{field}` for the others, with the
+/// extension of the last segment of the URL path if any.
+fn file_name(id: &str, field: &str, url: &str) -> String {
+    let mut name = if field == "file" {
+        id.to_owned()
+    } else {
+        format!("{id}-{field}")
+    };
+    let ext = Url::parse(url).ok().and_then(|url| {
+        let ext = Path::new(url.path()).extension()?.to_str()?.to_owned();
+        ext.chars()
+            .all(|c| c.is_ascii_alphanumeric())
+            .then_some(ext)
+    });
+    if let Some(ext) = ext {
+        name.push('.');
+        name.push_str(&ext.to_lowercase());
+    }
+    name
+}
+
+/// Whether the file is downloaded since the object was last edited, and is
+/// still there.
+async fn is_downloaded(
+    db: &mut SqliteConnection,
+    object_id: &str,
+    field: &str,
+    last_edited_time: DateTime<Utc>,
+) -> sqlx::Result<bool> {
+    let row: Option<(String, DateTime<Utc>)> = sqlx::query_as(
+        "select local_path, last_edited_time from files where object_id = $1 and field = $2",
+    )
+    .bind(object_id)
+    .bind(field)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.is_some_and(|(path, time)| time == last_edited_time && Path::new(&path).exists()))
+}
+
+async fn record_file(
+    db: &mut SqliteConnection,
+    object_id: &str,
+    field: &str,
+    file: &NotionFile,
+    path: &Path,
+    last_edited_time: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    let expiry_time = match file {
+        NotionFile::File { file } => Some(file.expiry_time),
+        NotionFile::External { .. } => None,
+    };
+    sqlx::query("insert or replace into files values ($1, $2, $3, $4, $5, $6, CURRENT_TIMESTAMP)")
+        .bind(object_id)
+        .bind(field)
+        .bind(file.url())
+        .bind(expiry_time)
+        .bind(path.to_string_lossy())
+        .bind(last_edited_time)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use notion_async_api::NotionFile;
+    use serde_json::json;
+    use sqlx::types::chrono::{DateTime, Utc};
+
+    use crate::tests::memory_db;
+
+    use super::{file_name, is_downloaded, record_file};
+
+    #[test]
+    fn file_name_of_url() {
+        let url = "https://prod-files.s3.amazonaws.com/a/b/Photo%201.JPG?X-Amz-Expires=3600";
+        assert_eq!(file_name("b1", "file", url), "b1.jpg");
+        assert_eq!(
+            file_name("p1", "cover", "https://example.com/c.png"),
+            "p1-cover.png"
+        );
+        // no extension, or not a plain one
+        assert_eq!(
+            file_name("p1", "icon", "https://example.com/icon"),
+            "p1-icon"
+        );
+        assert_eq!(
+            file_name("b1", "file", "https://example.com/a.tar%2F..%2Fx"),
+            "b1"
+        );
+        assert_eq!(file_name("b1", "file", "not a url"), "b1");
+    }
+
+    #[tokio::test]
+    async fn downloaded_files() {
+        let mut db = memory_db().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("p1-cover.png");
+        let file: NotionFile =
+            serde_json::from_value(json!({ "external": { "url": "https://example.com/c.png" } }))
+                .unwrap();
+        let edited: DateTime<Utc> = "2024-08-01T00:00:00Z".parse().unwrap();
+
+        assert!(!is_downloaded(&mut db, "p1", "cover", edited).await.unwrap());
+        record_file(&mut db, "p1", "cover", &file, &path, edited)
+            .await
+            .unwrap();
+        // the local file is missing
+        assert!(!is_downloaded(&mut db, "p1", "cover", edited).await.unwrap());
+
+        tokio::fs::write(&path, b"png").await.unwrap();
+        assert!(is_downloaded(&mut db, "p1", "cover", edited).await.unwrap());
+        assert!(!is_downloaded(&mut db, "p1", "icon", edited).await.unwrap());
+        // the object is edited since
+        let edited_again: DateTime<Utc> = "2024-08-02T00:00:00Z".parse().unwrap();
+        assert!(!is_downloaded(&mut db, "p1", "cover", edited_again)
+            .await
+            .unwrap());
+        assert!(Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,47 @@
+//! The JSON of the objects shared by the unit tests of the library & the
+//! binary, which can't see the test helpers of each other.
+// not all of them are used by each crate
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+
+pub(crate) fn user_json(id: &str) -> Value {
+    json!({ "object": "user", "id": id })
+}
+
+pub(crate) fn block_json(id: &str, parent_id: &str, child_index: usize) -> Value {
+    json!({
+        "object": "block",
+        "id": id,
+        "parent": { "type": "block_id", "block_id": parent_id },
+        "created_time": "2024-08-01T00:00:00.000Z",
+        "created_by": user_json("user"),
+        "last_edited_time": "2024-08-01T00:00:00.000Z",
+        "last_edited_by": user_json("user"),
+        "archived": false,
+        "in_trash": false,
+        "child_index": child_index,
+        "has_children": false,
+        "type": "paragraph",
+        "paragraph": { "rich_text": [], "color": "default" },
+    })
+}
+
+pub(crate) fn page_json(id: &str, parent_id: &str) -> Value {
+    json!({
+        "object": "page",
+        "id": id,
+        "parent": { "type": "page_id", "page_id": parent_id },
+        "created_time": "2024-08-01T00:00:00.000Z",
+        "created_by": user_json("user"),
+        "last_edited_time": "2024-08-01T00:00:00.000Z",
+        "last_edited_by": user_json("user"),
+        "archived": false,
+        "in_trash": false,
+        "properties": {},
+        "url": format!("https://www.notion.so/{id}"),
+        "public_url": null,
+        "icon": null,
+        "cover": null,
+    })
+}
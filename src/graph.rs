@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use notion_async_api::{to_plain_text, Property, RichText};
+use sqlx::SqliteConnection;
+
+use crate::escape::xml_escape;
+
+// guards against cycles in the parent chains of broken data
+const MAX_DEPTH: usize = 1000;
+
+/// The structure of a synced workspace, i.e. the pages & databases as nodes,
+/// linked by parent → child edges and by mentions/relations/links between
+/// them.
+#[derive(Debug, Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[derive(Debug)]
+struct Node {
+    id: String,
+    // page or database
+    node_type: &'static str,
+    title: String,
+}
+
+#[derive(Debug)]
+struct Edge {
+    from: String,
+    to: String,
+    // child, or the kind of the link, e.g. mention
+    kind: String,
+}
+
+impl Graph {
+    /// Load the graph from db. At most `max_nodes` nodes are kept (in the
+    /// order of their creation time) if set, edges from/to the dropped nodes
+    /// are dropped too.
+    pub async fn load(db: &mut SqliteConnection, max_nodes: Option<usize>) -> sqlx::Result<Self> {
+        let mut rows: Vec<(String, String, String, String, String)> = sqlx::query_as(
+            "select id, 'page', parent_id, properties, created_time from pages \
+             union all \
+             select id, 'database', parent_id, title, created_time from databases \
+             order by created_time, id",
+        )
+        .fetch_all(&mut *db)
+        .await?;
+        if let Some(max_nodes) = max_nodes {
+            rows.truncate(max_nodes);
+        }
+
+        let block_parents: HashMap<String, String> =
+            sqlx::query_as::<_, (String, String)>("select id, parent_id from blocks")
+                .fetch_all(&mut *db)
+                .await?
+                .into_iter()
+                .collect();
+        let links: Vec<(String, String, String)> =
+            sqlx::query_as("select source_id, target_id, kind from links order by 1, 2, 3")
+                .fetch_all(&mut *db)
+                .await?;
+
+        let ids: HashSet<&str> = rows.iter().map(|x| x.0.as_str()).collect();
+        // The nearest page/database containing `id`, including itself.
+        let container = |id: &str| -> Option<String> {
+            let mut id = id;
+            for _ in 0..MAX_DEPTH {
+                if ids.contains(id) {
+                    return Some(id.to_owned());
+                }
+                id = block_parents.get(id)?;
+            }
+            None
+        };
+
+        let mut edges = Vec::new();
+        for (id, _, parent_id, _, _) in rows.iter() {
+            if let Some(parent) = container(parent_id) {
+                edges.push(Edge {
+                    from: parent,
+                    to: id.clone(),
+                    kind: "child".to_owned(),
+                });
+            }
+        }
+        for (source_id, target_id, kind) in links {
+            match container(&source_id) {
+                Some(from) if ids.contains(target_id.as_str()) && from != target_id => {
+                    edges.push(Edge {
+                        from,
+                        to: target_id,
+                        kind,
+                    })
+                }
+                _ => (),
+            }
+        }
+        // the links from the blocks in the same page/database may duplicate
+        edges.sort_by(|a, b| (&a.from, &a.to, &a.kind).cmp(&(&b.from, &b.to, &b.kind)));
+        edges.dedup_by(|a, b| (&a.from, &a.to, &a.kind) == (&b.from, &b.to, &b.kind));
+
+        let nodes = rows
+            .into_iter()
+            .map(|(id, node_type, _, title, _)| {
+                let (node_type, title) = if node_type == "page" {
+                    ("page", page_title(&title))
+                } else {
+                    ("database", plain_text(&title))
+                };
+                Node {
+                    id,
+                    node_type,
+                    title,
+                }
+            })
+            .collect();
+
+        Ok(Self { nodes, edges })
+    }
+
+    /// Render as a [DOT](https://graphviz.org/doc/info/lang.html) digraph.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph notion {\n");
+        for node in self.nodes.iter() {
+            let shape = if node.node_type == "database" {
+                "box"
+            } else {
+                "ellipse"
+            };
+            out.push_str(&format!(
+                "  \"{}\" [label=\"{}\", shape={shape}];\n",
+                node.id,
+                dot_escape(&node.label())
+            ));
+        }
+        for edge in self.edges.iter() {
+            let style = if edge.kind == "child" {
+                "solid"
+            } else {
+                "dashed"
+            };
+            out.push_str(&format!(
+                "  \"{}\" -> \"{}\" [label=\"{}\", style={style}];\n",
+                edge.from, edge.to, edge.kind
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render as [GraphML](http://graphml.graphdrawing.org/).
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <key id=\"type\" for=\"node\" attr.name=\"type\" attr.type=\"string\"/>\n",
+            "  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <graph id=\"notion\" edgedefault=\"directed\">\n",
+        ));
+        for node in self.nodes.iter() {
+            out.push_str(&format!(
+                "    <node id=\"{}\"><data key=\"label\">{}</data><data key=\"type\">{}</data></node>\n",
+                xml_escape(&node.id),
+                xml_escape(&node.title),
+                node.node_type
+            ));
+        }
+        for edge in self.edges.iter() {
+            out.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{}</data></edge>\n",
+                xml_escape(&edge.from),
+                xml_escape(&edge.to),
+                xml_escape(&edge.kind)
+            ));
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+impl Node {
+    fn label(&self) -> String {
+        format!("{} ({})", self.title, self.node_type)
+    }
+}
+
+// The title of a page, from its properties json. Empty if the properties
+// can't be parsed, e.g. they have been truncated.
+pub(crate) fn page_title(properties: &str) -> String {
+    serde_json::from_str::<BTreeMap<String, Property>>(properties)
+        .ok()
+        .and_then(|x| x.values().find_map(|x| x.as_title()))
+        .map(|x| to_plain_text(&x))
+        .unwrap_or_default()
+}
+
+pub(crate) fn plain_text(rich_text: &str) -> String {
+    to_plain_text(&serde_json::from_str::<Vec<RichText>>(rich_text).unwrap_or_default())
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\r', "")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use notion_async_api::{LinkKind, LinkRef, ObjectKey, ObjectType, RichText};
+    use serde_json::json;
+
+    use crate::{
+        insert_or_update_block, insert_or_update_page,
+        tests::{block_json, memory_db, page_json},
+        update_links,
+    };
+
+    use super::Graph;
+
+    #[tokio::test]
+    async fn load_and_render() {
+        let mut db = memory_db().await;
+        let mut p1 = page_json("p1", "workspace");
+        let title = RichText::text("Plans \"v2\"\nDraft");
+        p1["properties"] = json!({ "Name": { "id": "title", "type": "title", "title": [title] } });
+        for page in [p1, page_json("p2", "p1"), page_json("p3", "workspace")] {
+            let page = serde_json::from_value(page).unwrap();
+            insert_or_update_page(&mut db, page, None).await.unwrap();
+        }
+        // a1 & c1 in p1 mention p2, in between b1 in p3 does too
+        for (id, parent_id) in [("a1", "p1"), ("b1", "p3"), ("c1", "p1")] {
+            let block = serde_json::from_value(block_json(id, parent_id, 0)).unwrap();
+            insert_or_update_block(&mut db, block, None).await.unwrap();
+            let mention = LinkRef {
+                target: ObjectKey::new(ObjectType::Page, "p2".to_owned()),
+                kind: LinkKind::Mention,
+            };
+            let source = ObjectKey::new(ObjectType::Block, id.to_owned());
+            update_links(&mut db, &source, &[mention]).await.unwrap();
+        }
+
+        let dot = Graph::load(&mut db, None).await.unwrap().to_dot();
+        assert_eq!(
+            dot,
+            concat!(
+                "digraph notion {\n",
+                "  \"p1\" [label=\"Plans \\\"v2\\\"\\nDraft (page)\", shape=ellipse];\n",
+                "  \"p2\" [label=\" (page)\", shape=ellipse];\n",
+                "  \"p3\" [label=\" (page)\", shape=ellipse];\n",
+                "  \"p1\" -> \"p2\" [label=\"child\", style=solid];\n",
+                "  \"p1\" -> \"p2\" [label=\"mention\", style=dashed];\n",
+                "  \"p3\" -> \"p2\" [label=\"mention\", style=dashed];\n",
+                "}\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::HashSet;
+
+use notion_async_api::{
+    to_plain_text, Block, BlockTree, BlockType, Object, RichText, RichTextType,
+};
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+use crate::{db::get_child_blocks, escape::xml_escape as html_escape, graph::page_title};
+
+// guards against cycles in the block trees of broken data
+const MAX_DEPTH: usize = 100;
+
+/// The built-in styling of the exported pages, roughly the look of Notion.
+pub const DEFAULT_CSS: &str = r#"
+body { max-width: 900px; margin: 2em auto; padding: 0 1em; color: #37352f; line-height: 1.5;
+  font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, Arial, sans-serif; }
+h1.title { font-size: 2.5em; margin-bottom: 0.5em; }
+img { max-width: 100%; }
+figure { margin: 1em 0; }
+figcaption { color: #787774; font-size: 0.9em; }
+blockquote { margin: 0.5em 0; padding-left: 0.9em; border-left: 3px solid currentColor; }
+pre { background: #f7f6f3; padding: 1em; border-radius: 4px; overflow-x: auto; }
+code { font-family: SFMono-Regular, Menlo, Consolas, monospace; font-size: 0.9em; }
+p code, li code { background: rgba(135,131,120,0.15); color: #eb5757; padding: 0.1em 0.3em; border-radius: 3px; }
+.callout { display: flex; gap: 0.6em; padding: 1em; border-radius: 4px; background: #f1f1ef; margin: 0.5em 0; }
+.callout-icon { flex: none; }
+ul.to-do { list-style: none; padding-left: 0.2em; }
+ul.to-do .checked { text-decoration: line-through; color: #787774; }
+.columns { display: flex; gap: 1em; }
+.column { flex: 1; min-width: 0; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+td, th { border: 1px solid #e9e9e7; padding: 0.3em 0.6em; text-align: left; }
+.bookmark { display: block; border: 1px solid #e9e9e7; border-radius: 4px; padding: 0.8em; margin: 0.5em 0; }
+.equation { font-family: serif; font-style: italic; }
+.color-gray { color: #787774; } .color-brown { color: #9f6b53; } .color-orange { color: #d9730d; }
+.color-yellow { color: #cb912f; } .color-green { color: #448361; } .color-blue { color: #337ea9; }
+.color-purple { color: #9065b0; } .color-pink { color: #c14c8a; } .color-red { color: #d44c47; }
+.bg-gray { background: #f1f1ef; } .bg-brown { background: #f4eeee; } .bg-orange { background: #fbecdd; }
+.bg-yellow { background: #fbf3db; } .bg-green { background: #edf3ec; } .bg-blue { background: #e7f3f8; }
+.bg-purple { background: #f6f3f9; } .bg-pink { background: #faf1f5; } .bg-red { background: #fdebec; }
+@media print { body { margin: 0; max-width: none; } pre, figure, table, .callout { break-inside: avoid; } }
+"#;
+
+/// Export a synced page in db as a standalone HTML document, with the CSS
+/// inlined, ready to be printed or converted to PDF, e.g. by a headless
+/// browser. `extra_css` is appended to [`DEFAULT_CSS`] to override the
+/// styling.
+///
+/// The images are linked, not embedded. Note that the URLs of the files
+/// hosted by notion expire in an hour, so sync right before exporting.
+///
+/// Return `None` if the page isn't in db.
+pub async fn export_html(
+    db: &mut SqliteConnection,
+    page_id: &str,
+    extra_css: Option<&str>,
+) -> sqlx::Result<Option<String>> {
+    let page: Option<(String, String)> = sqlx::query_as(
+        "select id, properties from pages where id = $1 or replace(id, '-', '') = $1",
+    )
+    .bind(page_id.replace('-', ""))
+    .fetch_optional(&mut *db)
+    .await?;
+    let Some((page_id, properties)) = page else {
+        return Ok(None);
+    };
+    let title = page_title(&properties);
+
+    let tree = load_tree(db, &page_id).await?;
+    let mut body = String::new();
+    render_children(&tree, &page_id, 0, &mut body);
+
+    Ok(Some(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>{DEFAULT_CSS}{extra_css}</style>\n</head>\n<body>\n\
+         <h1 class=\"title\">{title}</h1>\n{body}</body>\n</html>\n",
+        title = html_escape(&title),
+        extra_css = extra_css.unwrap_or_default(),
+    )))
+}
+
+/// Load the blocks under the page from db.
+async fn load_tree(db: &mut SqliteConnection, page_id: &str) -> sqlx::Result<BlockTree> {
+    let mut blocks = Vec::new();
+    let mut loaded = HashSet::new();
+    let mut parents = vec![page_id.to_owned()];
+    for _ in 0..MAX_DEPTH {
+        let mut next = Vec::new();
+        for parent in parents {
+            if !loaded.insert(parent.clone()) {
+                continue;
+            }
+            for block in get_child_blocks(db, &parent).await? {
+                if block.has_children {
+                    next.push(block.id().to_owned());
+                }
+                blocks.push(block);
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        parents = next;
+    }
+    Ok(BlockTree::from_blocks(blocks))
+}
+
+fn render_children(tree: &BlockTree, parent_id: &str, depth: usize, out: &mut String) {
+    if depth >= MAX_DEPTH {
+        return;
+    }
+    // the list item blocks are grouped into lists
+    let mut list: Option<&str> = None;
+    for block in tree.children(parent_id) {
+        let tag = match block.block_type {
+            BlockType::BulletedListItem => Some("ul"),
+            BlockType::NumberedListItem => Some("ol"),
+            BlockType::ToDo => Some("ul class=\"to-do\""),
+            _ => None,
+        };
+        if list != tag {
+            if let Some(list) = list {
+                out.push_str(&format!("</{}>\n", &list[..2]));
+            }
+            if let Some(tag) = tag {
+                out.push_str(&format!("<{tag}>\n"));
+            }
+            list = tag;
+        }
+        render_block(tree, block, depth, out);
+    }
+    if let Some(list) = list {
+        out.push_str(&format!("</{}>\n", &list[..2]));
+    }
+}
+
+fn render_block(tree: &BlockTree, block: &Block, depth: usize, out: &mut String) {
+    let block_type = block.block_type.to_string();
+    // the data under the `block_type` key
+    let data = &serde_json::to_value(&block.type_data).unwrap_or_default()[&block_type];
+    let text = render_rich_text(&rich_text(data, "rich_text"));
+    let class = color_class(data);
+    let mut children = String::new();
+    render_children(tree, block.id(), depth + 1, &mut children);
+
+    let html = match block.block_type {
+        BlockType::Paragraph => format!("<p{class}>{text}</p>\n{children}"),
+        BlockType::Heading1 | BlockType::Heading2 | BlockType::Heading3 => {
+            // shifted by one, since the page title is the h1
+            let level = block_type[8..].parse::<u8>().unwrap_or(1) + 1;
+            if data["is_toggleable"].as_bool() == Some(true) {
+                format!("<details><summary><h{level}{class}>{text}</h{level}></summary>\n{children}</details>\n")
+            } else {
+                format!("<h{level}{class}>{text}</h{level}>\n")
+            }
+        }
+        BlockType::BulletedListItem | BlockType::NumberedListItem => {
+            format!("<li{class}>{text}\n{children}</li>\n")
+        }
+        BlockType::ToDo => {
+            let checked = block.as_todo().is_some_and(|x| x.checked);
+            format!(
+                "<li{class}><input type=\"checkbox\" disabled{}> <span{}>{text}</span>\n{children}</li>\n",
+                if checked { " checked" } else { "" },
+                if checked { " class=\"checked\"" } else { "" },
+            )
+        }
+        BlockType::Toggle => {
+            format!("<details{class}><summary>{text}</summary>\n{children}</details>\n")
+        }
+        BlockType::Quote => format!("<blockquote{class}>{text}\n{children}</blockquote>\n"),
+        BlockType::Callout => {
+            let icon = data["icon"]["emoji"].as_str().unwrap_or_default();
+            let class = color_class(data).replace("class=\"", "class=\"callout ");
+            let class = if class.is_empty() {
+                " class=\"callout\"".to_owned()
+            } else {
+                class
+            };
+            format!(
+                "<div{class}><div class=\"callout-icon\">{}</div><div>{text}\n{children}</div></div>\n",
+                html_escape(icon)
+            )
+        }
+        BlockType::Code => {
+            let language = data["language"].as_str().unwrap_or("plain text");
+            let code = format!(
+                "<pre><code class=\"language-{}\">{}</code></pre>",
+                html_escape(&language.replace(' ', "-")),
+                text.replace("<br>", "\n")
+            );
+            figure(code, &render_caption(data))
+        }
+        BlockType::Equation => format!(
+            "<p class=\"equation\">{}</p>\n",
+            html_escape(data["expression"].as_str().unwrap_or_default())
+        ),
+        BlockType::Divider => "<hr>\n".to_owned(),
+        BlockType::Image => {
+            let img = format!(
+                "<img src=\"{}\" alt=\"{}\">",
+                html_escape(file_url(data)),
+                html_escape(&to_plain_text(&rich_text(data, "caption")))
+            );
+            figure(img, &render_caption(data))
+        }
+        BlockType::Video | BlockType::Pdf | BlockType::File => file_link(data),
+        BlockType::Unknown(ref x) if x == "audio" => file_link(data),
+        BlockType::Bookmark | BlockType::Embed | BlockType::LinkPreview => {
+            let url = data["url"].as_str().unwrap_or_default();
+            let caption = render_caption(data);
+            let text = if caption.is_empty() {
+                html_escape(url)
+            } else {
+                caption
+            };
+            format!(
+                "<a class=\"bookmark\" href=\"{}\">{text}</a>\n",
+                html_escape(url)
+            )
+        }
+        BlockType::ChildPage | BlockType::ChildDatabase => {
+            let icon = if block.block_type == BlockType::ChildPage {
+                "📄"
+            } else {
+                "🗂"
+            };
+            format!(
+                "<p class=\"{}\"><a href=\"https://www.notion.so/{}\">{icon} {}</a></p>\n",
+                block_type.replace('_', "-"),
+                block.id().replace('-', ""),
+                html_escape(block.child_title().unwrap_or_default())
+            )
+        }
+        BlockType::Table => {
+            let has_column_header = data["has_column_header"].as_bool() == Some(true);
+            let has_row_header = data["has_row_header"].as_bool() == Some(true);
+            let mut rows = String::new();
+            for (i, row) in tree.children(block.id()).iter().enumerate() {
+                rows.push_str("<tr>");
+                let cells = serde_json::to_value(&row.type_data).unwrap_or_default()["table_row"]
+                    ["cells"]
+                    .take();
+                let cells: Vec<Vec<RichText>> = serde_json::from_value(cells).unwrap_or_default();
+                for (j, cell) in cells.iter().enumerate() {
+                    let tag = if (i == 0 && has_column_header) || (j == 0 && has_row_header) {
+                        "th"
+                    } else {
+                        "td"
+                    };
+                    rows.push_str(&format!("<{tag}>{}</{tag}>", render_rich_text(cell)));
+                }
+                rows.push_str("</tr>\n");
+            }
+            format!("<table>\n{rows}</table>\n")
+        }
+        BlockType::ColumnList => format!("<div class=\"columns\">\n{children}</div>\n"),
+        BlockType::Column => format!("<div class=\"column\">\n{children}</div>\n"),
+        // the content of a synced block is its children, the same for the
+        // unsupported blocks, whose children may still be readable
+        _ if text.is_empty() => children,
+        _ => format!("<p{class}>{text}</p>\n{children}"),
+    };
+    out.push_str(&html);
+}
+
+fn figure(content: String, caption: &str) -> String {
+    if caption.is_empty() {
+        format!("<figure>{content}</figure>\n")
+    } else {
+        format!("<figure>{content}<figcaption>{caption}</figcaption></figure>\n")
+    }
+}
+
+// A link to the file of a file/video/pdf/audio block, with its caption or
+// name.
+fn file_link(data: &Value) -> String {
+    let url = file_url(data);
+    let caption = render_caption(data);
+    let text = if caption.is_empty() {
+        html_escape(data["name"].as_str().unwrap_or(url))
+    } else {
+        caption
+    };
+    format!("<p><a href=\"{}\">{text}</a></p>\n", html_escape(url))
+}
+
+// The URL of a file/image/video/pdf block, hosted by notion or external.
+fn file_url(data: &Value) -> &str {
+    ["file", "external"]
+        .into_iter()
+        .find_map(|x| data[x]["url"].as_str())
+        .unwrap_or_default()
+}
+
+// The class attribute for the `color` of a block, empty for the default
+// color.
+fn color_class(data: &Value) -> String {
+    color_attr(data["color"].as_str().unwrap_or("default"))
+}
+
+fn color_attr(color: &str) -> String {
+    match color {
+        "default" => String::new(),
+        color => match color.strip_suffix("_background") {
+            Some(color) => format!(" class=\"bg-{color}\""),
+            None => format!(" class=\"color-{color}\""),
+        },
+    }
+}
+
+fn rich_text(data: &Value, key: &str) -> Vec<RichText> {
+    serde_json::from_value(data[key].clone()).unwrap_or_default()
+}
+
+fn render_caption(data: &Value) -> String {
+    render_rich_text(&rich_text(data, "caption"))
+}
+
+fn render_rich_text(rich_text: &[RichText]) -> String {
+    rich_text.iter().map(render_span).collect()
+}
+
+fn render_span(x: &RichText) -> String {
+    let mut html = match &x.rich_text_type {
+        RichTextType::Equation { .. } => {
+            format!(
+                "<span class=\"equation\">{}</span>",
+                html_escape(&x.render())
+            )
+        }
+        _ => html_escape(&x.render()).replace('\n', "<br>"),
+    };
+    let a = &x.annotations;
+    for (on, tag) in [
+        (a.code, "code"),
+        (a.bold, "strong"),
+        (a.italic, "em"),
+        (a.strikethrough, "s"),
+        (a.underline, "u"),
+    ] {
+        if on {
+            html = format!("<{tag}>{html}</{tag}>");
+        }
+    }
+    let class = color_attr(a.color.as_str());
+    if !class.is_empty() {
+        html = format!("<span{class}>{html}</span>");
+    }
+    if let Some(href) = &x.href {
+        html = format!("<a href=\"{}\">{html}</a>", html_escape(href));
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use notion_async_api::RichText;
+    use serde_json::{json, Value};
+
+    use crate::{
+        insert_or_update_block, insert_or_update_page,
+        tests::{block_json, memory_db, page_json},
+    };
+
+    use super::export_html;
+
+    fn block(
+        id: &str,
+        parent_id: &str,
+        child_index: usize,
+        block_type: &str,
+        data: Value,
+    ) -> Value {
+        let mut js = block_json(id, parent_id, child_index);
+        js.as_object_mut().unwrap().remove("paragraph");
+        js["type"] = json!(block_type);
+        js[block_type] = data;
+        js
+    }
+
+    fn text(s: &str) -> Value {
+        json!({ "rich_text": [RichText::text(s)], "color": "default" })
+    }
+
+    #[tokio::test]
+    async fn render_page() {
+        let mut db = memory_db().await;
+        let mut page = page_json("p1", "workspace");
+        let title = RichText::text("Q&A <draft>");
+        page["properties"] =
+            json!({ "Name": { "id": "title", "type": "title", "title": [title] } });
+        insert_or_update_page(&mut db, serde_json::from_value(page).unwrap(), None)
+            .await
+            .unwrap();
+
+        let mut item = block("b2", "p1", 1, "bulleted_list_item", text("one"));
+        item["has_children"] = json!(true);
+        let mut todo = text("done");
+        todo["checked"] = json!(true);
+        // deleted, not rendered
+        let mut archived = block("b8", "p1", 6, "paragraph", text("archived"));
+        archived["archived"] = json!(true);
+        let mut trashed = block("b9", "p1", 7, "paragraph", text("trashed"));
+        trashed["in_trash"] = json!(true);
+        for js in [
+            block("b1", "p1", 0, "paragraph", text("a < b & \"c\"")),
+            item,
+            block("b3", "b2", 0, "bulleted_list_item", text("nested")),
+            block("b4", "p1", 2, "bulleted_list_item", text("two")),
+            block("b5", "p1", 3, "numbered_list_item", text("first")),
+            block("b6", "p1", 4, "to_do", todo),
+            block("b7", "p1", 5, "heading_1", text("End")),
+            archived,
+            trashed,
+        ] {
+            let block = serde_json::from_value(js).unwrap();
+            insert_or_update_block(&mut db, block, None).await.unwrap();
+        }
+
+        let html = export_html(&mut db, "p1", Some("p { color: red; }"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(html.contains("<title>Q&amp;A &lt;draft&gt;</title>"));
+        assert!(html.contains("p { color: red; }</style>"));
+        let body = html.split_once("</h1>\n").unwrap().1;
+        assert_eq!(
+            body,
+            concat!(
+                "<p>a &lt; b &amp; &quot;c&quot;</p>\n",
+                "<ul>\n<li>one\n<ul>\n<li>nested\n</li>\n</ul>\n</li>\n<li>two\n</li>\n</ul>\n",
+                "<ol>\n<li>first\n</li>\n</ol>\n",
+                "<ul class=\"to-do\">\n",
+                "<li><input type=\"checkbox\" disabled checked> <span class=\"checked\">done</span>\n",
+                "</li>\n</ul>\n",
+                "<h2>End</h2>\n",
+                "</body>\n</html>\n",
+            )
+        );
+    }
+}
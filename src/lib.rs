@@ -1,2 +1,41 @@
 mod db;
+mod escape;
+mod files;
+#[cfg(test)]
+mod fixtures;
+mod graph;
+mod html;
+mod mentions;
+mod parent_order;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod sink;
+mod store;
+mod text_index;
+mod verify;
 pub use db::*;
+pub use files::*;
+pub use graph::*;
+pub use html::*;
+pub use mentions::*;
+pub use parent_order::*;
+#[cfg(feature = "postgres")]
+pub use postgres::*;
+pub use sink::*;
+pub use store::*;
+pub use text_index::*;
+pub use verify::*;
+
+#[cfg(test)]
+mod tests {
+    //! Helpers shared by the unit tests of each module.
+
+    use sqlx::SqliteConnection;
+
+    pub(crate) use crate::fixtures::{block_json, page_json, user_json};
+
+    /// An empty in-memory db with the schema & migrations applied.
+    pub(crate) async fn memory_db() -> SqliteConnection {
+        crate::init_db(":memory:").await.unwrap()
+    }
+}
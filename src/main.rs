@@ -1,14 +1,37 @@
-use std::{collections::HashMap, env, path};
+use std::{
+    collections::{HashMap, HashSet},
+    env, path,
+    time::Duration,
+};
 
-use clap::{Parser, Subcommand};
+use async_compression::tokio::{
+    bufread::{GzipDecoder, ZstdDecoder},
+    write::{GzipEncoder, ZstdEncoder},
+};
+use clap::{Parser, Subcommand, ValueEnum};
 use futures::StreamExt;
 use http::Uri;
+#[cfg(feature = "postgres")]
+use notion_async::PgStore;
 use notion_async::{
-    init_db, insert_or_update_block, insert_or_update_comment, insert_or_update_database,
-    insert_or_update_page,
+    create_unique_child_index, drop_unique_child_index, export_html, get_backlinks, init_db,
+    insert_provenance, insert_subtree_provenance, renumber_duplicate_child_indexes,
+    update_page_text, verify, DbMentionResolver, Downloaded, FileDownloader, Graph, ObjectSink,
+    ParentOrder, SqliteStore, Store, TextIndex, WebhookSink,
+};
+use notion_async_api::{
+    apply_transforms, AnyObject, Api, ApiErrorCode, BlockTree, BlockType, ErrorMode, Fetcher,
+    FetcherOptions, LinkRef, LowercasePropertyKeys, Object, ObjectType, StripFileUrls, SyncEvent,
+    SyncStats, Transform, ViewRef,
+};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    SqliteConnection,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter},
 };
-use notion_async_api::{Fetcher, Object};
-use sqlx::SqliteConnection;
 
 /// A notion sync tool, in `async` style.
 #[derive(Parser, Debug)]
@@ -25,6 +48,54 @@ struct Cli {
     #[arg(long, value_name = "FILE", default_value_t=String::from("notion.db"))]
     db: String,
 
+    /// Database URL, selecting the backend by its scheme, i.e.
+    /// `sqlite:FILE` or `postgres://...` (built with the `postgres`
+    /// feature). Only syncing is supported by Postgres. Overrides `--db`.
+    #[arg(long, value_name = "URL", conflicts_with = "db")]
+    db_url: Option<String>,
+
+    /// Only store blocks of these types (e.g. `heading_1,heading_2,code`),
+    /// other blocks are still traversed but not stored. Store all blocks if
+    /// not set.
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    block_types: Vec<BlockType>,
+
+    /// Build a full-text index of the content of pages, which can be
+    /// searched with the `search_pages` function, and store the rich text of
+    /// blocks into the `block_rich_text` table, for SQL queries.
+    #[arg(long)]
+    index_text: bool,
+
+    /// Truncate the JSON fields (properties, rich texts, etc.) larger than
+    /// BYTES before storing them, a truncated field is replaced by a JSON
+    /// object like `{"truncated": true, "bytes": 123456, "prefix": "..."}`.
+    /// The rich text of comments is never truncated.
+    #[arg(long, value_name = "BYTES")]
+    max_field_bytes: Option<usize>,
+
+    /// Normalize the objects with these transforms in order before storing
+    /// them, the `--dump-json` dump is kept as is.
+    #[arg(long, value_name = "TRANSFORMS", value_delimiter = ',')]
+    transforms: Vec<TransformName>,
+
+    /// Store every object after its parent, so that the db can enforce
+    /// foreign keys. The objects whose parents aren't stored yet are
+    /// buffered in memory, which may be the most of a sync in the worst case.
+    #[arg(long)]
+    ordered: bool,
+
+    /// How to handle the blocks under the same parent with the same
+    /// `child_index`, e.g. stale blocks left after the blocks are moved.
+    #[arg(long, value_name = "MODE", default_value = "keep")]
+    child_index_conflicts: ChildIndexConflicts,
+
+    /// Export the sync as an OpenTelemetry trace to the OTLP/HTTP endpoint
+    /// URL (e.g. `http://localhost:4318/v1/traces`), each request is a span
+    /// nested under the span of its parent object.
+    #[cfg(feature = "otel")]
+    #[arg(long, value_name = "URL")]
+    otel_endpoint: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -33,72 +104,521 @@ struct Cli {
 enum Commands {
     /// Sync all pages/databases/comments into db, recursively.
     Sync {
-        /// A LINK or ID of a notion page/database. Everything in it will be
+        /// LINKs or IDs of notion pages/databases. Everything in them will be
         /// downloaded, in recursive way. Read from env var NOTION_ROOT_PAGE if
         /// not set.
-        page: Option<String>,
+        pages: Vec<String>,
+
+        /// Record which of the root pages each object is reached from, into
+        /// the `provenance` table.
+        #[arg(long)]
+        provenance: bool,
+
+        /// Also dump every synced object into FILE, as newline delimited
+        /// JSON, which can be imported later by the `import` command.
+        #[arg(long, value_name = "FILE")]
+        dump_json: Option<String>,
+
+        /// Compress the `--dump-json` FILE while writing it, with zstd if
+        /// FILE ends with `.zst`, otherwise with gzip.
+        #[arg(long, requires = "dump_json")]
+        dump_json_compressed: bool,
+
+        /// Also POST the synced objects to the webhook URL as they're
+        /// synced, in batches of JSON arrays. A failed batch is retried,
+        /// then written to the `--webhook-dead-letter` file.
+        #[arg(long, value_name = "URL")]
+        webhook: Option<String>,
+
+        /// The number of objects per webhook request.
+        #[arg(long, value_name = "N", default_value_t = 50, requires = "webhook")]
+        webhook_batch: usize,
+
+        /// Where the objects failed to be sent to the webhook are appended,
+        /// as newline delimited JSON, which can be imported later.
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "webhook-dead-letter.jsonl",
+            requires = "webhook"
+        )]
+        webhook_dead_letter: String,
+
+        /// Only sync the objects up to N levels below the roots, e.g. 0 for
+        /// only the root pages, 1 for the roots and their blocks, for a
+        /// shallow preview.
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+
+        /// What to do when an object fails to be fetched.
+        #[arg(long, value_enum, default_value_t = OnError::Continue)]
+        on_error: OnError,
+
+        /// Also sync all the users of the workspace, which needs the "read
+        /// user information" capability of the integration.
+        #[arg(long)]
+        users: bool,
+
+        /// Delete the objects under the roots which aren't found by the
+        /// sync, i.e. deleted in Notion. They're only listed if not set. A
+        /// root with any error isn't pruned, since its subtree may be
+        /// partially synced.
+        #[arg(long, conflicts_with = "max_depth")]
+        prune: bool,
+
+        /// Download the files of file/image/video/pdf blocks, and the icons
+        /// & covers of pages/databases into DIR, named by the object IDs.
+        /// The local paths are recorded in the `files` table, and a file is
+        /// only downloaded again after the object is edited.
+        #[arg(long, value_name = "DIR")]
+        download_files: Option<String>,
+
+        /// Also sync the pages/databases mentioned in the blocks, with their
+        /// children, even if they're outside the roots. Bounded by
+        /// --max-depth like the children.
+        #[arg(long)]
+        follow_mentions: bool,
+
+        /// Send at most N requests at once, on top of the rate limit, to
+        /// bound the connections & memory of a sync of a huge workspace.
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        concurrency: Option<u32>,
+    },
+    /// Sync only the pages/databases edited since a time (with their
+    /// children), which are found by searching the workspace rather than
+    /// crawling from a root. The search results are eventually consistent,
+    /// i.e. the most recent edits may be missed.
+    SyncChanged {
+        /// e.g. `2024-08-01T00:00:00Z`
+        #[arg(long, value_name = "TIME")]
+        since: DateTime<Utc>,
+    },
+    /// Import objects from a newline delimited JSON dump (see `sync
+    /// --dump-json`) into db, without network access.
+    Import {
+        /// The dump file, decompressed if it's compressed with gzip or zstd
+        /// (detected by its content).
+        file: String,
     },
+    /// List the databases reachable from a page, i.e. their titles & IDs,
+    /// without fetching the rows of databases or storing anything.
+    Databases {
+        /// LINK or ID of the notion page.
+        root: String,
+    },
+    /// Export all pages/databases/comments under a page into a directory,
+    /// one file per object, named `{object_type}-{id}.json`, or the page as
+    /// one markdown document, named by its title, without storing anything
+    /// into db.
+    Export {
+        /// LINK or ID of the notion page/database.
+        page: String,
+
+        /// The output directory, created if missing.
+        #[arg(long, value_name = "DIR")]
+        out_dir: String,
+
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+    },
+    /// Export the pages & databases in db as a graph, with parent → child
+    /// edges and mention/relation/link edges, without network access.
+    Graph {
+        /// The output file, in GraphML format if it ends with `.graphml`,
+        /// otherwise in DOT format.
+        #[arg(long, value_name = "FILE")]
+        out: String,
+
+        /// Export at most N nodes, the earliest created ones, to keep large
+        /// workspaces renderable.
+        #[arg(long, value_name = "N")]
+        max_nodes: Option<usize>,
+    },
+
+    /// Export a synced page in db as a standalone HTML document, with
+    /// Notion-like styling inlined, ready to be printed or converted to PDF,
+    /// without network access.
+    ExportHtml {
+        /// The page ID or URL.
+        page: String,
+
+        /// The output file.
+        #[arg(long, value_name = "FILE")]
+        out: String,
+
+        /// A CSS file appended to the built-in styling to override it.
+        #[arg(long, value_name = "FILE")]
+        css: Option<String>,
+    },
+
+    /// List the objects linking to a page/database in db, i.e. mentioning,
+    /// relating or linking to it, without network access.
+    Backlinks {
+        /// The ID or URL of the page/database.
+        id: String,
+    },
+
+    /// Check the integrity of db after a sync, i.e. dangling parents and
+    /// users, and gaps of the `child_index` of blocks, without network access.
+    Verify {
+        /// The IDs or URLs of the sync roots, whose parents are expected to
+        /// be missing.
+        roots: Vec<String>,
+    },
+}
+
+/// The file format of `export`.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExportFormat {
+    /// Pretty printed JSON, in the format of `sync --dump-json`.
+    Json,
+    /// Markdown with YAML front matter, the child pages are rendered inline.
+    /// The rows of databases aren't exported. The mentions are rendered as
+    /// the titles of the pages & the names of the users, the ones outside
+    /// the page are looked up in db.
+    Markdown,
+}
+
+/// The built-in transforms of objects.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TransformName {
+    /// Clear the URLs of files, which expire in an hour.
+    StripFileUrls,
+    /// Lowercase the names of properties.
+    LowercasePropertyKeys,
+}
+
+impl TransformName {
+    fn transform(self) -> Box<dyn Transform> {
+        match self {
+            TransformName::StripFileUrls => Box::new(StripFileUrls),
+            TransformName::LowercasePropertyKeys => Box::new(LowercasePropertyKeys),
+        }
+    }
+}
+
+/// The handling of the objects failed to be fetched, see [`ErrorMode`].
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OnError {
+    /// Report the error and sync the other objects, without the subtree of
+    /// the failed object.
+    Continue,
+    /// Retry the failed request once, then continue.
+    RetryOnce,
+    /// Stop syncing the root of the failed object.
+    Abort,
+}
+
+impl From<OnError> for ErrorMode {
+    fn from(value: OnError) -> Self {
+        match value {
+            OnError::Continue => ErrorMode::Continue,
+            OnError::RetryOnce => ErrorMode::RetryOnce,
+            OnError::Abort => ErrorMode::Abort,
+        }
+    }
+}
+
+/// The handling of duplicate `child_index` values.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ChildIndexConflicts {
+    /// Store them as is, the readers (e.g. `export-html`) break the ties by
+    /// `created_time` then ID.
+    Keep,
+    /// Renumber the blocks under each parent with duplicates after a sync,
+    /// in the order of `child_index`, `created_time` then ID.
+    Renumber,
+    /// Enforce a unique index on `(parent_id, child_index)` during a sync
+    /// (the existing duplicates are renumbered), so that storing a block
+    /// replaces the stale block at its index.
+    Replace,
 }
 
 const NOTION_TOKEN: &str = "NOTION_TOKEN";
 const NOTION_ROOT_PAGE: &str = "NOTION_ROOT_PAGE";
+// how often to report the progress of a sync
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
     let _ = dotenvy::dotenv();
 
-    let mut db = init_db(&cli.db).await?;
-    cli.run(&mut db).await?;
+    #[cfg(feature = "otel")]
+    let tracer_provider = cli.otel_endpoint.as_deref().map(init_otel).transpose()?;
+    // e.g. `RUST_LOG=notion_async_api=debug`
+    #[cfg(feature = "tracing")]
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let mut sqlite = None;
+    let store = open_store(&cli, &mut sqlite).await?;
+    cli.run(store).await?;
+
+    #[cfg(feature = "otel")]
+    if let Some(provider) = tracer_provider {
+        provider.shutdown()?;
+    }
 
     Ok(())
 }
 
+/// Set up the global tracer to export the spans to the OTLP `endpoint`.
+#[cfg(feature = "otel")]
+fn init_otel(endpoint: &str) -> Result<opentelemetry_sdk::trace::SdkTracerProvider> {
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .build();
+    opentelemetry::global::set_tracer_provider(provider.clone());
+    Ok(provider)
+}
+
+/// The db which the objects are stored into.
+enum DbStore<'a> {
+    Sqlite(SqliteStore<'a>),
+    #[cfg(feature = "postgres")]
+    Postgres(Box<PgStore>),
+}
+
+impl DbStore<'_> {
+    fn store(&mut self) -> &mut dyn Store {
+        match self {
+            DbStore::Sqlite(store) => store,
+            #[cfg(feature = "postgres")]
+            DbStore::Postgres(store) => store.as_mut(),
+        }
+    }
+
+    /// The SQLite db, for the features only supported by SQLite.
+    fn sqlite(&mut self) -> Option<&mut SqliteConnection> {
+        match self {
+            DbStore::Sqlite(store) => Some(store.db()),
+            #[cfg(feature = "postgres")]
+            DbStore::Postgres(_) => None,
+        }
+    }
+}
+
+/// Open the db of `--db-url`, or the SQLite db of `--db`, which is kept in
+/// `sqlite`.
+async fn open_store<'a>(
+    cli: &Cli,
+    sqlite: &'a mut Option<SqliteConnection>,
+) -> Result<DbStore<'a>> {
+    let path = match cli.db_url.as_deref() {
+        None => cli.db.as_str(),
+        Some(url) if url.starts_with("sqlite:") => {
+            let path = url.trim_start_matches("sqlite:");
+            path.strip_prefix("//").unwrap_or(path)
+        }
+        #[cfg(feature = "postgres")]
+        Some(url) if url.starts_with("postgres:") || url.starts_with("postgresql:") => {
+            return Ok(DbStore::Postgres(Box::new(
+                PgStore::connect(url, cli.max_field_bytes).await?,
+            )));
+        }
+        Some(url) if url.starts_with("postgres:") || url.starts_with("postgresql:") => {
+            return Err("Postgres needs a build with the `postgres` feature".into());
+        }
+        Some(url) => return Err(format!("unsupported db URL: {url}").into()),
+    };
+    let db = sqlite.insert(init_db(path).await?);
+    Ok(DbStore::Sqlite(SqliteStore::new(db, cli.max_field_bytes)))
+}
+
 impl Cli {
-    async fn run(&self, db: &mut SqliteConnection) -> Result<()> {
+    async fn run(&self, store: DbStore<'_>) -> Result<()> {
+        let mut writer = DbWriter {
+            store,
+            block_types: &self.block_types,
+            text_index: self.index_text.then(TextIndex::default),
+            provenance: false,
+            parent_order: self.ordered.then(ParentOrder::default),
+            transforms: self.transforms.iter().map(|x| x.transform()).collect(),
+            renumber_child_indexes: self.child_index_conflicts == ChildIndexConflicts::Renumber,
+            sinks: Vec::new(),
+            downloader: None,
+        };
+        if self.index_text || self.child_index_conflicts != ChildIndexConflicts::Keep {
+            // fail before syncing rather than after
+            writer.db()?;
+        }
+        let stores_blocks = matches!(
+            self.command,
+            Commands::Sync { .. } | Commands::SyncChanged { .. } | Commands::Import { .. }
+        );
+        if self.child_index_conflicts == ChildIndexConflicts::Replace
+            && matches!(self.command, Commands::Sync { .. })
+        {
+            create_unique_child_index(writer.db()?).await?;
+        } else if let Some(db) = writer.store.sqlite().filter(|_| stores_blocks) {
+            // left by a sync in the replace mode, which would make storing a
+            // block silently replace another one at its index
+            drop_unique_child_index(db).await?;
+        }
+
         match &self.command {
-            Commands::Sync { page } => {
-                let page = match page {
-                    Some(id) => id.to_owned(),
-                    None => {
-                        let Ok(id) = env::var(NOTION_ROOT_PAGE) else {
-                            return Err(
-                                format!("Neither --id nor env {NOTION_ROOT_PAGE} is set.").into()
-                            );
-                        };
-                        id
-                    }
+            Commands::Sync {
+                pages,
+                provenance,
+                dump_json,
+                dump_json_compressed,
+                webhook,
+                webhook_batch,
+                webhook_dead_letter,
+                max_depth,
+                on_error,
+                users,
+                prune,
+                download_files,
+                follow_mentions,
+                concurrency,
+            } => {
+                let pages = if pages.is_empty() {
+                    let Ok(id) = env::var(NOTION_ROOT_PAGE) else {
+                        return Err(
+                            format!("Neither --id nor env {NOTION_ROOT_PAGE} is set.").into()
+                        );
+                    };
+                    vec![id]
+                } else {
+                    pages.clone()
                 };
+                let page_ids = pages
+                    .into_iter()
+                    .map(parse_page_id)
+                    .collect::<Result<Vec<_>>>()?;
 
-                let page_id = if page.starts_with("https://") {
-                    match page.parse::<Uri>() {
-                        Ok(uri) => {
-                            let p = uri.path();
-                            let p = path::Path::new(p).file_name();
-                            if let Some(last) = p.and_then(|x| x.to_str()) {
-                                if let Some((_, id)) = last.rsplit_once("-") {
-                                    id.to_owned()
-                                } else {
-                                    last.to_owned()
-                                }
-                            } else {
-                                return Err(format!(
-                                    "Can't extract ID from NOTION_ROOT_PAGE, which value is {page}"
-                                )
-                                .into());
-                            }
-                        }
-                        Err(_) => page,
-                    }
+                if *provenance || download_files.is_some() {
+                    writer.db()?;
+                }
+                if let Some(dir) = download_files {
+                    let api = Api::new(&self.get_token()?);
+                    writer.downloader = Some(FileDownloader::new(dir, api));
+                }
+                writer.provenance = *provenance;
+                if let Some(url) = webhook {
+                    writer.sinks.push(Box::new(WebhookSink::new(
+                        url,
+                        *webhook_batch,
+                        webhook_dead_letter,
+                    )));
+                }
+                if self.ordered {
+                    writer.parent_order = Some(ParentOrder::new(&page_ids));
+                }
+                if *prune && !self.block_types.is_empty() {
+                    // the blocks of the other types would be pruned
+                    return Err("--prune can't be used with --block-types".into());
+                }
+                let token = self.get_token()?;
+                let mut fetcher =
+                    sync_fetcher(&token, *max_depth, (*on_error).into(), *follow_mentions);
+                if let Some(n) = concurrency {
+                    fetcher = fetcher.with_concurrency(*n as usize);
+                }
+                // the clock of `synced_at`, which may differ from the local one
+                let started = writer.store.store().now().await?;
+                let mut failed_roots = run_sync(
+                    fetcher,
+                    &page_ids,
+                    &mut writer,
+                    dump_json.as_deref(),
+                    *dump_json_compressed,
+                )
+                .await?;
+                if !failed_roots.is_empty() {
+                    // the roots share the fetched objects (see `sync_fetcher`), so
+                    // an error under a root may be reported by another one
+                    failed_roots.extend(page_ids.iter().cloned());
+                }
+                reconcile_deletions(
+                    writer.store.store(),
+                    &page_ids,
+                    started,
+                    &failed_roots,
+                    *prune,
+                )
+                .await?;
+                if *users {
+                    run_sync_users(&self.get_token()?, &mut writer).await?;
+                }
+            }
+            Commands::SyncChanged { since } => {
+                run_sync_changed(&self.get_token()?, *since, &mut writer).await?
+            }
+            Commands::Import { file } => run_import(file, &mut writer).await?,
+            Commands::Databases { root } => {
+                run_databases(&self.get_token()?, &parse_page_id(root.clone())?).await?
+            }
+            Commands::Export {
+                page,
+                out_dir,
+                format,
+            } => {
+                run_export(
+                    &self.get_token()?,
+                    &parse_page_id(page.clone())?,
+                    out_dir,
+                    *format,
+                    writer.store.sqlite(),
+                )
+                .await?
+            }
+            Commands::Graph { out, max_nodes } => {
+                let graph = Graph::load(writer.db()?, *max_nodes).await?;
+                let content = if out.ends_with(".graphml") {
+                    graph.to_graphml()
                 } else {
-                    page
+                    graph.to_dot()
                 };
-
-                run_sync(&self.get_token()?, &page_id, db).await;
+                tokio::fs::write(out, content).await?;
+            }
+            Commands::ExportHtml { page, out, css } => {
+                let css = match css {
+                    Some(css) => Some(tokio::fs::read_to_string(css).await?),
+                    None => None,
+                };
+                let page_id = parse_page_id(page.clone())?;
+                let Some(html) = export_html(writer.db()?, &page_id, css.as_deref()).await? else {
+                    return Err(format!("Page {page_id} isn't synced").into());
+                };
+                tokio::fs::write(out, html).await?;
+            }
+            Commands::Backlinks { id } => {
+                for key in get_backlinks(writer.db()?, &parse_page_id(id.clone())?).await? {
+                    println!("{} {}", key.object_type, key.id);
+                }
+            }
+            Commands::Verify { roots } => {
+                let roots = roots
+                    .iter()
+                    .map(|x| parse_page_id(x.clone()))
+                    .collect::<Result<Vec<_>>>()?;
+                let report = verify(writer.db()?, &roots).await?;
+                for violation in report.violations.iter() {
+                    println!("{violation}");
+                }
+                if !report.users_checked {
+                    println!("ℹ️ Users aren't synced by --users, skipped checking them");
+                }
+                if !report.violations.is_empty() {
+                    return Err(format!("{} violations found", report.violations.len()).into());
+                }
+                println!("✅ No violations found");
             }
         };
+        writer.finish().await?;
         Ok(())
     }
 
@@ -116,53 +636,817 @@ impl Cli {
     }
 }
 
-async fn run_sync(token: &str, page_id: &str, db: &mut SqliteConnection) {
+/// Extract the page ID if `page` is a link.
+fn parse_page_id(page: String) -> Result<String> {
+    let page_id = if page.starts_with("https://") {
+        match page.parse::<Uri>() {
+            Ok(uri) => {
+                let p = uri.path();
+                let p = path::Path::new(p).file_name();
+                if let Some(last) = p.and_then(|x| x.to_str()) {
+                    if let Some((_, id)) = last.rsplit_once("-") {
+                        id.to_owned()
+                    } else {
+                        last.to_owned()
+                    }
+                } else {
+                    return Err(format!(
+                        "Can't extract ID from NOTION_ROOT_PAGE, which value is {page}"
+                    )
+                    .into());
+                }
+            }
+            Err(_) => page,
+        }
+    } else {
+        page
+    };
+    Ok(page_id)
+}
+
+/// Find the objects under the roots which aren't synced since `started`, and
+/// delete them if `prune`, except under the roots with errors.
+async fn reconcile_deletions(
+    store: &mut dyn Store,
+    roots: &[String],
+    started: DateTime<Utc>,
+    failed_roots: &HashSet<String>,
+    prune: bool,
+) -> Result<()> {
+    for root in roots {
+        if failed_roots.contains(root) {
+            eprintln!("⚠️ {root} isn't reconciled since it's partially synced");
+            continue;
+        }
+        let stale = store.stale_descendants(root, started).await?;
+        for key in stale.iter() {
+            println!(
+                "🗑  {:8} {} {}",
+                key.object_type,
+                key.id,
+                if prune { "deleted" } else { "stale" }
+            );
+        }
+        if prune {
+            store.delete_objects(&stale).await?;
+        } else if !stale.is_empty() {
+            eprintln!(
+                "ℹ️ {} objects under {root} aren't found, run with --prune to delete them",
+                stale.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Fail fast if the token is rejected, rather than failing every request of a
+/// sync.
+async fn check_token(api: &Api) -> Result<()> {
+    match api.me().await {
+        Ok(_) => Ok(()),
+        Err(e) if e.api_error_code() == Some(&ApiErrorCode::Unauthorized) => {
+            Err(format!("the token is rejected, check --token or env {NOTION_TOKEN}").into())
+        }
+        Err(e) => Err(format!("failed to verify the token: {e}").into()),
+    }
+}
+
+/// The fetcher of `sync`.
+fn sync_fetcher(
+    token: &str,
+    max_depth: Option<usize>,
+    error_mode: ErrorMode,
+    follow_mentions: bool,
+) -> Fetcher {
+    let mut fetcher = Fetcher::new(token)
+        .with_options(FetcherOptions {
+            heartbeat: Some(HEARTBEAT_INTERVAL),
+            // skip the malformed elements of lists unless aborting on errors
+            lenient_lists: error_mode != ErrorMode::Abort,
+            follow_mentions,
+            // an object under nested roots is synced once
+            dedup_across_fetches: true,
+            ..Default::default()
+        })
+        .with_error_mode(error_mode);
+    if let Some(depth) = max_depth {
+        fetcher = fetcher.with_max_depth(depth);
+    }
+    fetcher
+}
+
+async fn run_sync(
+    fetcher: Fetcher,
+    page_ids: &[String],
+    writer: &mut DbWriter<'_>,
+    dump_json: Option<&str>,
+    compressed: bool,
+) -> Result<HashSet<String>> {
+    check_token(fetcher.api()).await?;
+    let mut dump = match dump_json {
+        Some(path) => Some(create_dump(path, compressed).await?),
+        None => None,
+    };
+    let mut runs = HashMap::new();
+    let mut streams = Vec::new();
+    let mut stats = HashMap::new();
+    for page_id in page_ids {
+        let run_id = writer.store.store().start_sync_run(page_id).await?;
+        runs.insert(page_id.clone(), run_id);
+        let root = page_id.clone();
+        let (stream, root_stats) = fetcher.fetch_events_with_stats(page_id).await;
+        streams.push(stream.map(move |x| (root.clone(), x)));
+        stats.insert(page_id.clone(), root_stats);
+    }
+    let mut rx = futures::stream::select_all(streams);
+    // the roots with any error
+    let mut failed_roots = HashSet::new();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    while let Some((root, event)) = rx.next().await {
+        match event {
+            SyncEvent::Object(fetched) => {
+                *counts.entry(root.clone()).or_default() += 1;
+                let obj = fetched.object;
+                if writer.provenance {
+                    insert_provenance(writer.db()?, &obj.key(), &root).await?;
+                }
+
+                if let Some(dump) = dump.as_mut() {
+                    let mut line = serde_json::to_vec(&obj)?;
+                    line.push(b'\n');
+                    dump.write_all(&line).await?;
+                }
+
+                writer.store(obj).await;
+            }
+            SyncEvent::Error(e) => {
+                eprintln!("❌ error {e}");
+                failed_roots.insert(root);
+            }
+            SyncEvent::VersionMismatch {
+                requested,
+                responded,
+            } => {
+                eprintln!("⚠️ API version mismatch, requested {requested}, responded {responded}");
+            }
+            SyncEvent::TitleMismatch {
+                id,
+                block_title,
+                title,
+            } => {
+                eprintln!("ℹ️ stale title of block {id}: {block_title:?}, renamed to {title:?}");
+            }
+            SyncEvent::Skipped { key, reason } => {
+                eprintln!("⚠️ skipped {key}: {reason}");
+            }
+            SyncEvent::InvalidElement {
+                parent_id,
+                index,
+                error,
+            } => {
+                eprintln!("⚠️ skipped the invalid element {index} of {parent_id}: {error}");
+                // not pruned, as it's only partially synced
+                failed_roots.insert(root);
+            }
+            SyncEvent::Heartbeat { pending_tasks } => {
+                let stats = format_stats(&stats[&root].borrow());
+                eprintln!("⏳ syncing {root}: {stats}, {pending_tasks} pending requests");
+            }
+        }
+    }
+    for root in page_ids {
+        eprintln!("✅ synced {root}: {}", format_stats(&stats[root].borrow()));
+    }
+
+    if let Some(mut dump) = dump {
+        // also finishes the compressed stream
+        dump.shutdown().await?;
+    }
+    if writer.provenance {
+        // the objects under a nested root are only emitted under one of the
+        // roots containing them
+        for root in page_ids {
+            insert_subtree_provenance(writer.db()?, root).await?;
+        }
+    }
+    for (root, run_id) in runs {
+        let count = counts.get(&root).copied().unwrap_or_default();
+        let failed = failed_roots.contains(&root);
+        writer
+            .store
+            .store()
+            .finish_sync_run(run_id, count, failed)
+            .await?;
+    }
+    Ok(failed_roots)
+}
+
+/// The counts of a sync as one line for the progress.
+fn format_stats(stats: &SyncStats) -> String {
+    format!(
+        "{} pages, {} databases, {} blocks, {} comments, {} requests ({} retries), {} errors",
+        stats.pages,
+        stats.databases,
+        stats.blocks,
+        stats.comments,
+        stats.requests,
+        stats.retries,
+        stats.errors,
+    )
+}
+
+async fn run_sync_users(token: &str, writer: &mut DbWriter<'_>) -> Result<()> {
+    for user in Fetcher::new(token).fetch_users().await? {
+        writer.store(AnyObject::User(user)).await;
+    }
+    Ok(())
+}
+
+async fn run_sync_changed(
+    token: &str,
+    since: DateTime<Utc>,
+    writer: &mut DbWriter<'_>,
+) -> Result<()> {
     let fetcher = Fetcher::new(token);
-    let mut rx = fetcher.fetch(page_id).await;
-    let mut objects = HashMap::<String, ()>::new();
+    check_token(fetcher.api()).await?;
+    let mut rx = fetcher.fetch_changed(since).await?;
+    while let Some(obj) = rx.next().await {
+        match obj {
+            Ok(obj) => writer.store(obj).await,
+            Err(e) => eprintln!("❌ error {e}"),
+        }
+    }
+    Ok(())
+}
+
+async fn run_databases(token: &str, root: &str) -> Result<()> {
+    let fetcher = Fetcher::new(token).with_options(FetcherOptions {
+        descend_into_databases: false,
+        object_types: vec![ObjectType::Database],
+        ..Default::default()
+    });
+    let mut rx = fetcher.fetch(root).await;
     while let Some(obj) = rx.next().await {
         match obj {
-            Ok(obj) => {
-                if let std::collections::hash_map::Entry::Vacant(e) =
-                    objects.entry(format!("{}-{}", obj.id(), obj.object_type()))
-                {
-                    e.insert(());
-                    // println!("⏬ {} {}", obj.object_type(), obj.id());
-                    // save_object(&obj, "testdata").await?;
+            Ok(AnyObject::Database(database)) => {
+                let description = database.plain_description().replace('\n', " ");
+                if description.is_empty() {
+                    println!("{} {}", database.id(), database.plain_title());
                 } else {
-                    eprintln!("➡️ 🔁 repeated {} {}", obj.object_type(), obj.id());
+                    println!(
+                        "{} {} - {description}",
+                        database.id(),
+                        database.plain_title()
+                    );
                 }
-
-                match obj {
-                    notion_async_api::AnyObject::Block(block) => {
-                        println!(
-                            "✔   {:8} {} {}",
-                            block.object_type(),
-                            block.id(),
-                            block.block_type
-                        );
-                        insert_or_update_block(db, block).await.unwrap();
-                    }
-                    notion_async_api::AnyObject::Page(page) => {
-                        println!("✔ 📃 {:8} {}", page.object_type(), page.id());
-                        insert_or_update_page(db, page).await.unwrap();
-                    }
-                    notion_async_api::AnyObject::Database(database) => {
-                        println!("✔   {:8} {}", database.object_type(), database.id());
-                        insert_or_update_database(db, database).await.unwrap();
-                    }
-                    notion_async_api::AnyObject::User(user) => {
-                        println!("✔️ 👤 {:8} {}", user.object_type(), user.id());
-                    }
-                    notion_async_api::AnyObject::Comment(comment) => {
-                        println!("✔   {:8} {}", comment.object_type(), comment.id(),);
-                        insert_or_update_comment(db, comment).await.unwrap();
-                    }
-                };
             }
+            Ok(_) => (),
+            Err(e) => eprintln!("❌ error {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Export the objects under `root` into `out_dir`, the markdown export
+/// resolves the mentions with the synced objects in `db` if set.
+async fn run_export(
+    token: &str,
+    root: &str,
+    out_dir: &str,
+    format: ExportFormat,
+    db: Option<&mut SqliteConnection>,
+) -> Result<()> {
+    tokio::fs::create_dir_all(out_dir).await?;
+    if let ExportFormat::Markdown = format {
+        return run_export_markdown(token, root, out_dir, db).await;
+    }
+    let mut rx = Fetcher::new(token).fetch(root).await;
+    // each object is written as soon as it's fetched
+    while let Some(obj) = rx.next().await {
+        let obj = match obj {
+            Ok(obj) => obj,
             Err(e) => {
                 eprintln!("❌ error {e}");
+                continue;
+            }
+        };
+        let data = serde_json::to_vec_pretty(&obj)?;
+        let name = format!("{}-{}.json", obj.object_type(), obj.id());
+        tokio::fs::write(path::Path::new(out_dir).join(name), data).await?;
+        println!("✔   {:8} {}", obj.object_type(), obj.id());
+    }
+    Ok(())
+}
+
+async fn run_export_markdown(
+    token: &str,
+    root: &str,
+    out_dir: &str,
+    db: Option<&mut SqliteConnection>,
+) -> Result<()> {
+    let fetcher = Fetcher::new(token).with_options(FetcherOptions {
+        descend_into_databases: false,
+        object_types: vec![ObjectType::Page, ObjectType::Block],
+        ..Default::default()
+    });
+    let mut rx = fetcher.fetch(root).await;
+    let mut page = None;
+    let mut objects = Vec::new();
+    while let Some(obj) = rx.next().await {
+        match obj {
+            Ok(AnyObject::Page(x)) if x.id().replace('-', "") == root.replace('-', "") => {
+                page = Some(x)
+            }
+            Ok(obj) => objects.push(obj),
+            Err(e) => eprintln!("❌ error {e}"),
+        }
+    }
+    let Some(page) = page else {
+        return Err(format!("{root} isn't a page").into());
+    };
+
+    // the mentioned pages which aren't fetched may be synced into db
+    let mut titles = AnyObject::titles(&objects);
+    titles.insert(page.key(), page.title());
+    let resolver = match db {
+        Some(db) => DbMentionResolver::load(db).await?,
+        None => DbMentionResolver::default(),
+    }
+    .with_titles(titles);
+
+    // only the blocks in the page, rather than in the rows of its databases
+    let tree = BlockTree::from_objects(objects);
+    let mut blocks = Vec::new();
+    let mut parents = vec![page.id().to_owned()];
+    while let Some(parent) = parents.pop() {
+        for block in tree.children(&parent) {
+            parents.push(block.id().to_owned());
+            blocks.push(block.clone());
+        }
+    }
+
+    let title = page.title();
+    let mut doc = format!(
+        "---\ntitle: {}\nid: {}\ncreated_time: {}\nurl: {}\n---\n\n",
+        serde_json::to_string(&title)?,
+        page.id(),
+        page.obj.created_time.to_rfc3339(),
+        page.url,
+    );
+    doc.push_str(&BlockTree::from_blocks(blocks).to_markdown_resolved(&resolver));
+
+    let path = markdown_path(out_dir, &title, page.id()).await?;
+    tokio::fs::write(&path, doc).await?;
+    println!("✔ 📃 {}", path.display());
+    Ok(())
+}
+
+/// The path of the Markdown file of a page, i.e. `{title}.md`, or `{title}
+/// {short ID}.md` if the former is taken by another page (by the `id` in its
+/// front matter). A re-export overwrites the file of the page.
+async fn markdown_path(out_dir: &str, title: &str, id: &str) -> Result<path::PathBuf> {
+    let name = sanitize_file_name(title);
+    let path = path::Path::new(out_dir).join(format!("{name}.md"));
+    match tokio::fs::read_to_string(&path).await {
+        Ok(doc) if front_matter_id(&doc) != Some(id) => {
+            let short_id: String = id.replace('-', "").chars().take(8).collect();
+            Ok(path::Path::new(out_dir).join(format!("{name} {short_id}.md")))
+        }
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(path),
+    }
+}
+
+/// The `id` in the front matter of an exported Markdown document.
+fn front_matter_id(doc: &str) -> Option<&str> {
+    let (front_matter, _) = doc.strip_prefix("---\n")?.split_once("\n---\n")?;
+    front_matter.lines().find_map(|x| x.strip_prefix("id: "))
+}
+
+/// A file name from a title, i.e. without path separators & the other chars
+/// invalid on some platforms, at most 100 chars.
+fn sanitize_file_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(100)
+        .collect();
+    // e.g. `.` and `..`, or the trailing dots & spaces dropped by Windows
+    let name = name.trim().trim_matches('.').trim();
+    if name.is_empty() {
+        "Untitled".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+async fn create_dump(path: &str, compressed: bool) -> Result<Box<dyn AsyncWrite + Unpin + Send>> {
+    let file = BufWriter::new(File::create(path).await?);
+    let dump: Box<dyn AsyncWrite + Unpin + Send> = if !compressed {
+        Box::new(file)
+    } else if path.ends_with(".zst") {
+        Box::new(ZstdEncoder::new(file))
+    } else {
+        Box::new(GzipEncoder::new(file))
+    };
+    Ok(dump)
+}
+
+/// Open a dump, decompressed by the magic bytes at its start rather than
+/// the extension of `path`.
+async fn open_dump(path: &str) -> Result<Box<dyn AsyncBufRead + Unpin + Send>> {
+    let mut file = BufReader::new(File::open(path).await?);
+    let magic = file.fill_buf().await?;
+    let dump: Box<dyn AsyncBufRead + Unpin + Send> = if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(BufReader::new(GzipDecoder::new(file)))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(BufReader::new(ZstdDecoder::new(file)))
+    } else {
+        Box::new(file)
+    };
+    Ok(dump)
+}
+
+async fn run_import(path: &str, writer: &mut DbWriter<'_>) -> Result<()> {
+    let mut lines = open_dump(path).await?.lines();
+    let mut line_no = 0;
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<AnyObject>(&line) {
+            Ok(obj) => writer.store(obj).await,
+            Err(e) => eprintln!("❌ invalid object at line {line_no}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Stores the synced/imported objects into db.
+struct DbWriter<'a> {
+    store: DbStore<'a>,
+    // store all blocks if empty
+    block_types: &'a [BlockType],
+    text_index: Option<TextIndex>,
+    // record which root each object is reached from
+    provenance: bool,
+    // store every object after its parent if set
+    parent_order: Option<ParentOrder>,
+    transforms: Vec<Box<dyn Transform>>,
+    // renumber the duplicate `child_index` values when finished
+    renumber_child_indexes: bool,
+    // where the objects are also sent to
+    sinks: Vec<Box<dyn ObjectSink>>,
+    // downloads the files of the objects before they're transformed
+    downloader: Option<FileDownloader>,
+}
+
+impl DbWriter<'_> {
+    /// The SQLite db, for the features only supported by SQLite.
+    fn db(&mut self) -> Result<&mut SqliteConnection> {
+        self.store
+            .sqlite()
+            .ok_or_else(|| "only supported by a SQLite db".into())
+    }
+
+    async fn store(&mut self, obj: AnyObject) {
+        if let (Some(downloader), Some(db)) = (self.downloader.as_mut(), self.store.sqlite()) {
+            if is_stored(self.block_types, &obj) {
+                if let Err(e) = downloader.enqueue(db, &obj).await {
+                    eprintln!("❌ failed to download the files of {}: {e}", obj.id());
+                }
+            }
+            print_downloads(&downloader.record_finished(db).await.unwrap());
+        }
+        let obj = apply_transforms(&self.transforms, obj);
+        for sink in self.sinks.iter_mut() {
+            if let Err(e) = sink.send(&obj).await {
+                eprintln!("❌ sink error {e}");
+            }
+            for warning in sink.take_warnings() {
+                eprintln!("⚠️ {warning}");
             }
         }
+        let objects = match self.parent_order.as_mut() {
+            Some(order) => order.push(obj),
+            None => vec![obj],
+        };
+        for obj in objects {
+            self.write(obj).await;
+        }
+    }
+
+    async fn write(&mut self, obj: AnyObject) {
+        if let Some(index) = self.text_index.as_mut() {
+            match &obj {
+                AnyObject::Page(page) => index.add_page(page.id()),
+                AnyObject::Block(block) => index.add_block(block),
+                _ => (),
+            }
+        }
+
+        if !is_stored(self.block_types, &obj) {
+            return;
+        }
+
+        let store = self.store.store();
+
+        let value = serde_json::to_value(&obj).unwrap();
+        let views = ViewRef::find_all(&value);
+        store.upsert_views(obj.id(), &views).await.unwrap();
+        store
+            .upsert_links(&obj.key(), &LinkRef::find_all(&value))
+            .await
+            .unwrap();
+        for user in obj.users() {
+            store.upsert_user(user).await.unwrap();
+        }
+
+        match obj {
+            AnyObject::Block(block) => {
+                println!(
+                    "✔   {:8} {} {}",
+                    block.object_type(),
+                    block.id(),
+                    block.block_type
+                );
+                if self.text_index.is_some() {
+                    store
+                        .upsert_block_rich_text(block.id(), &block.rich_text().unwrap_or_default())
+                        .await
+                        .unwrap();
+                }
+                store.upsert_block(block).await.unwrap();
+            }
+            AnyObject::Page(page) => {
+                println!("✔ 📃 {:8} {}", page.object_type(), page.id());
+                store.upsert_page(page).await.unwrap();
+            }
+            AnyObject::Database(database) => {
+                println!("✔   {:8} {}", database.object_type(), database.id());
+                store.upsert_database(database).await.unwrap();
+            }
+            AnyObject::User(user) => {
+                println!("✔️ 👤 {:8} {}", user.object_type(), user.id());
+                store.upsert_user(&user).await.unwrap();
+            }
+            AnyObject::Comment(comment) => {
+                println!("✔   {:8} {}", comment.object_type(), comment.id(),);
+                store.upsert_comment(comment).await.unwrap();
+            }
+        };
+        for x in store.take_truncated() {
+            eprintln!("✂️ truncated {} of {}: {} bytes", x.field, x.id, x.bytes);
+        }
+    }
+
+    /// Write the data collected during the sync.
+    async fn finish(mut self) -> Result<()> {
+        if let Some(downloader) = self.downloader.take() {
+            print_downloads(&downloader.finish(self.db()?).await?);
+        }
+        for sink in self.sinks.iter_mut() {
+            let flushed = sink.flush().await;
+            for warning in sink.take_warnings() {
+                eprintln!("⚠️ {warning}");
+            }
+            flushed?;
+        }
+        if let Some(order) = self.parent_order.take() {
+            let orphans = order.finish();
+            if !orphans.is_empty() {
+                eprintln!("⚠️ {} objects whose parents aren't synced", orphans.len());
+            }
+            for obj in orphans {
+                self.write(obj).await;
+            }
+        }
+        if let Some(mut index) = self.text_index.take() {
+            for (page_id, text) in index.page_texts() {
+                update_page_text(self.db()?, &page_id, &text).await?;
+            }
+        }
+        if self.renumber_child_indexes {
+            let n = renumber_duplicate_child_indexes(self.db()?).await?;
+            if n > 0 {
+                eprintln!("⚠️ Renumbered the blocks under {n} parents with duplicate child_index");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn print_downloads(downloaded: &[Downloaded]) {
+    for x in downloaded {
+        match &x.result {
+            Ok(path) => println!(
+                "⬇   {:8} {} {}",
+                x.key.object_type,
+                x.key.id,
+                path.display()
+            ),
+            Err(e) => eprintln!("❌ failed to download a file of {}: {e}", x.key.id),
+        }
+    }
+}
+
+/// Whether `obj` is stored, i.e. it's not a block of the types other than
+/// `block_types` (all the blocks are stored if empty).
+fn is_stored(block_types: &[BlockType], obj: &AnyObject) -> bool {
+    match obj {
+        AnyObject::Block(block) => {
+            block_types.is_empty() || block_types.contains(&block.block_type)
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod fixtures;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use clap::Parser;
+    use futures::{future::BoxFuture, FutureExt};
+    use notion_async::{init_db, insert_or_update_page, stale_objects, SqliteStore};
+    use notion_async_api::{Api, Fetcher, HttpExecutor};
+    use serde_json::json;
+    use sqlx::{types::chrono::Utc, SqliteConnection};
+    use tokio::io::AsyncWriteExt;
+
+    use crate::fixtures::page_json;
+
+    use super::{
+        check_token, create_dump, markdown_path, reconcile_deletions, run_import,
+        sanitize_file_name, Cli, DbStore, DbWriter,
+    };
+
+    /// Rejects the token of every request.
+    struct Unauthorized;
+
+    impl HttpExecutor for Unauthorized {
+        fn execute(
+            &self,
+            _request: reqwest::Request,
+        ) -> BoxFuture<'_, Result<reqwest::Response, reqwest::Error>> {
+            let body = json!({
+                "object": "error",
+                "status": 401,
+                "code": "unauthorized",
+                "message": "API token is invalid.",
+            });
+            let response = http::Response::builder()
+                .status(401)
+                .body(body.to_string())
+                .unwrap();
+            async move { Ok(response.into()) }.boxed()
+        }
+    }
+
+    fn writer(db: &mut SqliteConnection) -> DbWriter<'_> {
+        DbWriter {
+            store: DbStore::Sqlite(SqliteStore::new(db, None)),
+            block_types: &[],
+            text_index: None,
+            provenance: false,
+            parent_order: None,
+            transforms: Vec::new(),
+            renumber_child_indexes: false,
+            sinks: Vec::new(),
+            downloader: None,
+        }
+    }
+
+    #[test]
+    fn file_name_of_title() {
+        assert_eq!(sanitize_file_name("a/b\\c: d?"), "a_b_c_ d_");
+        assert_eq!(sanitize_file_name("line\nbreak"), "line_break");
+        assert_eq!(sanitize_file_name(" .. "), "Untitled");
+        assert_eq!(sanitize_file_name("notes. "), "notes");
+        assert_eq!(sanitize_file_name(&"x".repeat(200)).len(), 100);
+    }
+
+    #[tokio::test]
+    async fn markdown_path_of_reexport() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_str().unwrap();
+        let doc = |id: &str| format!("---\ntitle: \"Notes\"\nid: {id}\n---\n\ntext\n");
+
+        let path = markdown_path(out_dir, "Notes", "1234abcd-0000")
+            .await
+            .unwrap();
+        assert_eq!(path, dir.path().join("Notes.md"));
+        tokio::fs::write(&path, doc("1234abcd-0000")).await.unwrap();
+
+        // a re-export of the page overwrites its file
+        let path = markdown_path(out_dir, "Notes", "1234abcd-0000")
+            .await
+            .unwrap();
+        assert_eq!(path, dir.path().join("Notes.md"));
+
+        // another page with the same title
+        let path = markdown_path(out_dir, "Notes", "5678efgh-1111")
+            .await
+            .unwrap();
+        assert_eq!(path, dir.path().join("Notes 5678efgh.md"));
+    }
+
+    #[tokio::test]
+    async fn reconcile_deletions_of_roots() {
+        let mut db = init_db(":memory:").await.unwrap();
+        // r1 & r2 are synced, r3 is another root which isn't
+        for (id, parent_id) in [
+            ("r1", "workspace"),
+            ("r2", "workspace"),
+            ("r3", "workspace"),
+            ("p1", "r1"),
+            ("p2", "r2"),
+            ("p3", "r3"),
+        ] {
+            let page = serde_json::from_value(page_json(id, parent_id)).unwrap();
+            insert_or_update_page(&mut db, page, None).await.unwrap();
+        }
+        sqlx::query(
+            "update pages set synced_at = '2024-01-01 00:00:00' where id in ('p1', 'p2', 'p3')",
+        )
+        .execute(&mut db)
+        .await
+        .unwrap();
+
+        // the sync of r2 failed, so its stale objects may be just not fetched
+        let started = Utc::now() - std::time::Duration::from_secs(60);
+        let roots = ["r1".to_owned(), "r2".to_owned()];
+        let failed_roots = HashSet::from(["r2".to_owned()]);
+        reconcile_deletions(
+            &mut SqliteStore::new(&mut db, None),
+            &roots,
+            started,
+            &failed_roots,
+            true,
+        )
+        .await
+        .unwrap();
+
+        let mut stale: Vec<_> = stale_objects(&mut db, started)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        stale.sort();
+        assert_eq!(stale, ["p2", "p3"]);
+    }
+
+    #[test]
+    fn concurrency_arg() {
+        let parse = |n: &str| Cli::try_parse_from(["notion-async", "sync", "--concurrency", n]);
+        assert!(parse("8").is_ok());
+        assert!(parse("0").is_err());
+        assert!(parse("-1").is_err());
+    }
+
+    #[tokio::test]
+    async fn check_token_of_fetcher() {
+        // the token is checked by the API of the fetcher, e.g. against its
+        // endpoint
+        let fetcher = Fetcher::with_api(Api::with_executor("token", Unauthorized));
+        let e = check_token(fetcher.api()).await.unwrap_err();
+        assert!(e.to_string().contains("the token is rejected"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn import_compressed_dump() {
+        let dir = tempfile::tempdir().unwrap();
+        // the compression is detected by the content, not the extension
+        for (name, compressed) in [
+            ("plain.jsonl", false),
+            ("gzip.jsonl", true),
+            ("zstd.zst", true),
+            ("plain.gz", false),
+        ] {
+            let path = dir.path().join(name);
+            let path = path.to_str().unwrap();
+            let mut dump = create_dump(path, compressed).await.unwrap();
+            for id in ["p1", "p2"] {
+                let line = format!("{}\n", page_json(id, "workspace"));
+                dump.write_all(line.as_bytes()).await.unwrap();
+            }
+            dump.shutdown().await.unwrap();
+
+            let mut db = init_db(":memory:").await.unwrap();
+            run_import(path, &mut writer(&mut db)).await.unwrap();
+            let ids: Vec<(String,)> = sqlx::query_as("select id from pages order by id")
+                .fetch_all(&mut db)
+                .await
+                .unwrap();
+            assert_eq!(ids, [("p1".into(),), ("p2".into(),)], "{name}");
+        }
     }
 }
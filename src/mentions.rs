@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use notion_async_api::{MentionResolver, ObjectKey, ObjectType};
+use sqlx::SqliteConnection;
+
+use crate::graph::{page_title, plain_text};
+
+/// The titles of the pages/databases and the names of the users in db, to
+/// render the mentions in rich text as readable labels rather than IDs, see
+/// [`RichText::mention_label`](notion_async_api::RichText::mention_label).
+#[derive(Debug, Default)]
+pub struct DbMentionResolver {
+    titles: HashMap<ObjectKey, String>,
+    user_names: HashMap<String, String>,
+}
+
+impl DbMentionResolver {
+    pub async fn load(db: &mut SqliteConnection) -> sqlx::Result<Self> {
+        let pages: Vec<(String, String)> = sqlx::query_as("select id, properties from pages")
+            .fetch_all(&mut *db)
+            .await?;
+        let databases: Vec<(String, String)> = sqlx::query_as("select id, title from databases")
+            .fetch_all(&mut *db)
+            .await?;
+        let user_names: Vec<(String, String)> =
+            sqlx::query_as("select id, name from users where name is not null")
+                .fetch_all(&mut *db)
+                .await?;
+
+        let mut titles = HashMap::new();
+        for (id, properties) in pages {
+            titles.insert(
+                ObjectKey::new(ObjectType::Page, id),
+                page_title(&properties),
+            );
+        }
+        for (id, title) in databases {
+            titles.insert(ObjectKey::new(ObjectType::Database, id), plain_text(&title));
+        }
+        Ok(Self {
+            titles,
+            user_names: user_names.into_iter().collect(),
+        })
+    }
+
+    /// Add the titles of the pages/databases which may not be in db, e.g. by
+    /// [`AnyObject::titles`](notion_async_api::AnyObject::titles), which
+    /// take precedence over the ones in db.
+    pub fn with_titles(mut self, titles: HashMap<ObjectKey, String>) -> Self {
+        self.titles.extend(titles);
+        self
+    }
+}
+
+impl MentionResolver for DbMentionResolver {
+    fn title(&self, key: &ObjectKey) -> Option<String> {
+        self.titles.get(key).cloned()
+    }
+
+    fn user_name(&self, id: &str) -> Option<String> {
+        self.user_names.get(id).cloned()
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{HashMap, HashSet};
+
+use notion_async_api::{AnyObject, Object, ObjectKey, ObjectType, ParentType};
+
+/// Reorders the synced objects so that every object comes after its parent
+/// and the users it references, e.g. to store them into a db which enforces
+/// foreign keys.
+///
+/// An object is released once its parent is released, preceded by the
+/// (partial) users of its `created_by`/`last_edited_by` which aren't
+/// released yet, see [`AnyObject::users`]. The objects whose parent is the
+/// workspace (or of a type unknown to this crate), and the roots of the
+/// sync, don't wait for any parent. The objects waiting for their parents
+/// are buffered in memory, which may be the most of a sync in the worst
+/// case, since the fetcher emits the objects concurrently.
+#[derive(Default)]
+pub struct ParentOrder {
+    // the roots, in the form of `normalize_id`
+    roots: HashSet<String>,
+    released: HashSet<ObjectKey>,
+    // parent => the objects waiting for it
+    waiting: HashMap<ObjectKey, Vec<AnyObject>>,
+}
+
+impl ParentOrder {
+    /// `roots` are the IDs of the sync roots (with or without dashes), whose
+    /// parents aren't synced.
+    pub fn new(roots: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        Self {
+            roots: roots
+                .into_iter()
+                .map(|x| normalize_id(x.as_ref()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Add a synced object, return the objects released by it in order,
+    /// i.e. itself if its parent has been released, followed by the objects
+    /// waiting for it, recursively.
+    pub fn push(&mut self, obj: AnyObject) -> Vec<AnyObject> {
+        match self.parent_key(&obj) {
+            Some(parent) if !self.released.contains(&parent) => {
+                self.waiting.entry(parent).or_default().push(obj);
+                Vec::new()
+            }
+            _ => self.release(obj),
+        }
+    }
+
+    /// The objects whose parents are never synced, e.g. the objects under a
+    /// root which isn't given to [`ParentOrder::new`], still in the order of
+    /// their parents among themselves. The topmost of them should be written
+    /// without foreign key checks.
+    pub fn finish(mut self) -> Vec<AnyObject> {
+        let waiting: HashSet<ObjectKey> =
+            self.waiting.values().flatten().map(|x| x.key()).collect();
+        let missing: Vec<ObjectKey> = self
+            .waiting
+            .keys()
+            .filter(|x| !waiting.contains(x))
+            .cloned()
+            .collect();
+
+        let mut released = Vec::new();
+        for parent in missing {
+            for obj in self.waiting.remove(&parent).unwrap_or_default() {
+                released.extend(self.release(obj));
+            }
+        }
+        // in case of cycles in broken data
+        released.extend(self.waiting.into_values().flatten());
+        released
+    }
+
+    fn release(&mut self, obj: AnyObject) -> Vec<AnyObject> {
+        let mut released = Vec::new();
+        let mut queue = vec![obj];
+        while let Some(obj) = queue.pop() {
+            let key = obj.key();
+            if let Some(children) = self.waiting.remove(&key) {
+                queue.extend(children.into_iter().rev());
+            }
+            for user in obj.users() {
+                let user = AnyObject::User(user.clone());
+                if self.released.insert(user.key()) {
+                    released.push(user);
+                }
+            }
+            self.released.insert(key);
+            released.push(obj);
+        }
+        released
+    }
+
+    /// The parent which `obj` waits for.
+    fn parent_key(&self, obj: &AnyObject) -> Option<ObjectKey> {
+        if self.roots.contains(&normalize_id(obj.id())) {
+            return None;
+        }
+        let parent = obj.parent()?;
+        let object_type = match parent.r#type() {
+            ParentType::BlockId => ObjectType::Block,
+            ParentType::PageId => ObjectType::Page,
+            ParentType::DatabaseId => ObjectType::Database,
+            ParentType::Workspace | ParentType::Unknown => return None,
+        };
+        Some(ObjectKey::new(object_type, parent.id()))
+    }
+}
+
+fn normalize_id(id: &str) -> String {
+    id.chars()
+        .filter(|x| *x != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use notion_async_api::{AnyObject, Object};
+    use serde_json::Value;
+
+    use crate::tests::{block_json, page_json};
+
+    use super::ParentOrder;
+
+    fn object(js: Value) -> AnyObject {
+        serde_json::from_value(js).unwrap()
+    }
+
+    fn ids(objects: &[AnyObject]) -> Vec<String> {
+        objects
+            .iter()
+            .map(|x| format!("{} {}", x.object_type(), x.id()))
+            .collect()
+    }
+
+    #[test]
+    fn parents_and_users_first() {
+        let mut order = ParentOrder::new(["r-1"]);
+        let mut b2 = block_json("b2", "b1", 0);
+        b2["last_edited_by"]["id"] = "u2".into();
+
+        // b2 waits for b1, which waits for the root
+        assert!(order.push(object(b2)).is_empty());
+        let mut b1 = block_json("b1", "r1", 0);
+        b1["parent"] = serde_json::json!({ "type": "page_id", "page_id": "r1" });
+        assert!(order.push(object(b1)).is_empty());
+        assert_eq!(
+            ids(&order.push(object(page_json("r1", "p0")))),
+            ["user user", "page r1", "block b1", "user u2", "block b2"]
+        );
+        // the workspace isn't waited for, and the users are released once
+        let mut page = page_json("p2", "workspace");
+        page["parent"] = serde_json::json!({ "type": "workspace", "workspace": true });
+        assert_eq!(ids(&order.push(object(page))), ["page p2"]);
+        assert!(order.finish().is_empty());
+    }
+
+    #[test]
+    fn orphans() {
+        let mut order = ParentOrder::new(["r1"]);
+        assert!(order.push(object(block_json("b2", "b1", 0))).is_empty());
+        assert!(order.push(object(block_json("b1", "p1", 0))).is_empty());
+        assert!(order.push(object(block_json("c1", "p2", 0))).is_empty());
+
+        // still in the order of their parents among themselves
+        let mut orphans = ids(&order.finish());
+        orphans.sort_by_key(|x| x != "user user");
+        assert_eq!(orphans[0], "user user");
+        let b1 = orphans.iter().position(|x| x == "block b1").unwrap();
+        let b2 = orphans.iter().position(|x| x == "block b2").unwrap();
+        assert!(b1 < b2);
+        assert_eq!(orphans.len(), 4);
+    }
+}
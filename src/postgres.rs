@@ -0,0 +1,593 @@
+use futures::{future::BoxFuture, FutureExt};
+use notion_async_api::{Block, Comment, Database, Object, ObjectKey, Page, User};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    Connection, PgConnection,
+};
+
+use crate::{
+    db::{json_field, TruncatedField, OBJECT_TABLES, SUBTREE},
+    store::{Store, StoreError},
+};
+
+/// Stores the objects into a Postgres db, in the tables of the SQLite db
+/// (see [`init_db`](crate::init_db)), with `timestamptz` & `jsonb` columns.
+/// The links, views, rich text of blocks & downloaded files aren't stored.
+pub struct PgStore {
+    db: PgConnection,
+    max_field_bytes: Option<usize>,
+    truncated: Vec<TruncatedField>,
+}
+
+impl PgStore {
+    /// Connect to the db of `url` (e.g. `postgres://user@localhost/notion`),
+    /// and create the tables if they don't exist. The JSON fields larger
+    /// than `max_field_bytes` are truncated like the SQLite db.
+    pub async fn connect(url: &str, max_field_bytes: Option<usize>) -> sqlx::Result<Self> {
+        let mut db = PgConnection::connect(url).await?;
+        sqlx::raw_sql(PG_SCHEMA).execute(&mut db).await?;
+        Ok(Self {
+            db,
+            max_field_bytes,
+            truncated: Vec::new(),
+        })
+    }
+
+    fn json(&mut self, json: serde_json::Result<String>, field: &'static str, id: &str) -> String {
+        json_field(
+            json.unwrap(),
+            self.max_field_bytes,
+            field,
+            id,
+            &mut self.truncated,
+        )
+    }
+}
+
+/// `insert ... on conflict (id) do update` of `columns`, which are bound in
+/// order, the `jsonb` ones are cast from text. `synced_at` is set to now.
+fn upsert_sql(table: &str, columns: &[(&str, bool)]) -> String {
+    let names: Vec<_> = columns.iter().map(|(name, _)| *name).collect();
+    let values: Vec<_> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (_, jsonb))| {
+            if *jsonb {
+                format!("${}::jsonb", i + 1)
+            } else {
+                format!("${}", i + 1)
+            }
+        })
+        .collect();
+    let updates: Vec<_> = names
+        .iter()
+        .skip(1)
+        .chain(["synced_at"].iter())
+        .map(|name| format!("{name} = excluded.{name}"))
+        .collect();
+    format!(
+        "insert into {table} ({}, synced_at) values ({}, now()) \
+         on conflict (id) do update set {}",
+        names.join(", "),
+        values.join(", "),
+        updates.join(", "),
+    )
+}
+
+// the columns shared by blocks, pages & databases
+const COMMON_COLUMNS: [(&str, bool); 9] = [
+    ("id", false),
+    ("parent_type", false),
+    ("parent_id", false),
+    ("created_time", false),
+    ("created_by", false),
+    ("last_edited_time", false),
+    ("last_edited_by", false),
+    ("archived", false),
+    ("in_trash", false),
+];
+
+impl Store for PgStore {
+    fn upsert_block(&mut self, block: Block) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let id = block.id().to_owned();
+            let columns = [
+                &COMMON_COLUMNS[..],
+                &[
+                    ("child_index", false),
+                    ("has_children", false),
+                    ("block_type", false),
+                    ("type_data", true),
+                    ("checked", false),
+                    ("raw_json", true),
+                ],
+            ]
+            .concat();
+            let type_data = self.json(serde_json::to_string(&block.type_data), "type_data", &id);
+            let raw_json = self.json(serde_json::to_string(&block), "raw_json", &id);
+            sqlx::query(&upsert_sql("blocks", &columns))
+                .bind(&id)
+                .bind(block.obj.parent_type().to_string())
+                .bind(block.obj.parent.id())
+                .bind(block.obj.created_time)
+                .bind(block.obj.created_by.id())
+                .bind(block.obj.last_edited_time)
+                .bind(block.obj.last_edited_by.id())
+                .bind(block.obj.archived)
+                .bind(block.obj.in_trash)
+                .bind(block.child_index as i64)
+                .bind(block.has_children)
+                .bind(block.block_type.to_string())
+                .bind(type_data)
+                .bind(block.as_todo().map(|x| x.checked))
+                .bind(raw_json)
+                .execute(&mut self.db)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_page(&mut self, page: Page) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let id = page.id().to_owned();
+            let columns = [
+                &COMMON_COLUMNS[..],
+                &[
+                    ("properties", true),
+                    ("url", false),
+                    ("public_url", false),
+                    ("icon", false),
+                    ("cover", false),
+                    ("raw_json", true),
+                ],
+            ]
+            .concat();
+            let properties = self.json(serde_json::to_string(&page.properties), "properties", &id);
+            let raw_json = self.json(serde_json::to_string(&page), "raw_json", &id);
+            sqlx::query(&upsert_sql("pages", &columns))
+                .bind(&id)
+                .bind(page.obj.parent_type().to_string())
+                .bind(page.obj.parent.id())
+                .bind(page.obj.created_time)
+                .bind(page.obj.created_by.id())
+                .bind(page.obj.last_edited_time)
+                .bind(page.obj.last_edited_by.id())
+                .bind(page.obj.archived)
+                .bind(page.obj.in_trash)
+                .bind(properties)
+                .bind(page.url)
+                .bind(page.public_url)
+                .bind(page.icon.map(|x| x.to_string()))
+                .bind(page.cover.map(|x| x.to_string()))
+                .bind(raw_json)
+                .execute(&mut self.db)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_database(&mut self, database: Database) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let id = database.id().to_owned();
+            let columns = [
+                &COMMON_COLUMNS[..],
+                &[
+                    ("properties", true),
+                    ("url", false),
+                    ("public_url", false),
+                    ("icon", false),
+                    ("cover", false),
+                    ("is_inline", false),
+                    ("title", true),
+                    ("description", true),
+                    ("data_sources", true),
+                    ("raw_json", true),
+                ],
+            ]
+            .concat();
+            let properties = self.json(
+                serde_json::to_string(&database.properties),
+                "properties",
+                &id,
+            );
+            let title = self.json(serde_json::to_string(&database.title), "title", &id);
+            let description = self.json(
+                serde_json::to_string(&database.description),
+                "description",
+                &id,
+            );
+            let raw_json = self.json(serde_json::to_string(&database), "raw_json", &id);
+            sqlx::query(&upsert_sql("databases", &columns))
+                .bind(&id)
+                .bind(database.obj.parent_type().to_string())
+                .bind(database.obj.parent.id())
+                .bind(database.obj.created_time)
+                .bind(database.obj.created_by.id())
+                .bind(database.obj.last_edited_time)
+                .bind(database.obj.last_edited_by.id())
+                .bind(database.obj.archived)
+                .bind(database.obj.in_trash)
+                .bind(properties)
+                .bind(database.url)
+                .bind(database.public_url)
+                .bind(database.icon.map(|x| x.to_string()))
+                .bind(database.cover.map(|x| x.to_string()))
+                .bind(database.is_inline)
+                .bind(title)
+                .bind(description)
+                .bind(serde_json::to_string(&database.data_sources).unwrap())
+                .bind(raw_json)
+                .execute(&mut self.db)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_comment(&mut self, comment: Comment) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let id = comment.id().to_owned();
+            let columns = [
+                ("id", false),
+                ("parent_type", false),
+                ("parent_id", false),
+                ("created_time", false),
+                ("created_by", false),
+                ("last_edited_time", false),
+                ("discussion_id", false),
+                ("rich_text", true),
+                ("raw_json", true),
+            ];
+            // not truncated, like the SQLite db
+            let rich_text = serde_json::to_string(&comment.rich_text).unwrap();
+            let raw_json = self.json(serde_json::to_string(&comment), "raw_json", &id);
+            sqlx::query(&upsert_sql("comments", &columns))
+                .bind(&id)
+                .bind(comment.parent.r#type().to_string())
+                .bind(comment.parent.id())
+                .bind(comment.created_time)
+                .bind(comment.created_by.id())
+                .bind(comment.last_edited_time)
+                .bind(comment.discussion_id)
+                .bind(rich_text)
+                .bind(raw_json)
+                .execute(&mut self.db)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_user<'a>(&'a mut self, user: &'a User) -> BoxFuture<'a, Result<(), StoreError>> {
+        async move {
+            // an embedded user has only its ID, which never overwrites the
+            // known fields
+            sqlx::query(
+                "insert into users values ($1, $2, $3, $4, $5, now()) \
+                 on conflict (id) do update set \
+                   type = coalesce(excluded.type, users.type), \
+                   name = coalesce(excluded.name, users.name), \
+                   avatar_url = coalesce(excluded.avatar_url, users.avatar_url), \
+                   email = coalesce(excluded.email, users.email), \
+                   synced_at = excluded.synced_at",
+            )
+            .bind(user.id())
+            .bind(user.r#type.map(|x| x.to_string()))
+            .bind(&user.name)
+            .bind(&user.avatar_url)
+            .bind(user.email())
+            .execute(&mut self.db)
+            .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn now(&mut self) -> BoxFuture<'_, Result<DateTime<Utc>, StoreError>> {
+        async move {
+            let (now,): (DateTime<Utc>,) = sqlx::query_as("select now()")
+                .fetch_one(&mut self.db)
+                .await?;
+            Ok(now)
+        }
+        .boxed()
+    }
+
+    fn start_sync_run<'a>(
+        &'a mut self,
+        root_id: &'a str,
+    ) -> BoxFuture<'a, Result<i64, StoreError>> {
+        async move {
+            let (run_id,): (i64,) = sqlx::query_as(
+                "insert into sync_runs (root_id, started_at, status) \
+                 values ($1, now(), 'running') returning run_id",
+            )
+            .bind(root_id)
+            .fetch_one(&mut self.db)
+            .await?;
+            Ok(run_id)
+        }
+        .boxed()
+    }
+
+    fn finish_sync_run(
+        &mut self,
+        run_id: i64,
+        objects_count: usize,
+        failed: bool,
+    ) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            sqlx::query(
+                "update sync_runs \
+                 set finished_at = now(), objects_count = $2, status = $3 \
+                 where run_id = $1",
+            )
+            .bind(run_id)
+            .bind(objects_count as i64)
+            .bind(if failed { "error" } else { "ok" })
+            .execute(&mut self.db)
+            .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn stale_descendants<'a>(
+        &'a mut self,
+        root_id: &'a str,
+        since: DateTime<Utc>,
+    ) -> BoxFuture<'a, Result<Vec<ObjectKey>, StoreError>> {
+        async move {
+            let rows: Vec<(String, String)> = sqlx::query_as(&format!(
+                "{SUBTREE} \
+                 select object_type, id from tree \
+                 where synced_at is null or synced_at < $2 \
+                 order by object_type, id"
+            ))
+            .bind(root_id.replace('-', ""))
+            .bind(since)
+            .fetch_all(&mut self.db)
+            .await?;
+            let mut keys = Vec::new();
+            for (object_type, id) in rows {
+                let object_type = serde_json::from_value(serde_json::Value::String(object_type))?;
+                keys.push(ObjectKey::new(object_type, id));
+            }
+            Ok(keys)
+        }
+        .boxed()
+    }
+
+    fn take_truncated(&mut self) -> Vec<TruncatedField> {
+        std::mem::take(&mut self.truncated)
+    }
+
+    fn delete_objects<'a>(
+        &'a mut self,
+        keys: &'a [ObjectKey],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async move {
+            let mut tx = self.db.begin().await?;
+            for key in keys {
+                let Some((_, table)) = OBJECT_TABLES.iter().find(|(x, _)| *x == key.object_type)
+                else {
+                    continue;
+                };
+                sqlx::query(&format!("delete from {table} where id = $1"))
+                    .bind(&key.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+// The tables of the SQLite db with all of its migrations applied, keep them in
+// sync with `SQL_SCHEMA` & `MIGRATIONS` in db.rs.
+const PG_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS blocks (
+    id TEXT not null primary key,
+
+    parent_type TEXT not null,
+    parent_id TEXT not null,
+
+    created_time TIMESTAMPTZ not null,
+    created_by TEXT not null,
+    last_edited_time TIMESTAMPTZ not null,
+    last_edited_by TEXT not null,
+
+    archived BOOLEAN not null,
+    in_trash BOOLEAN not null,
+
+    -------------------------------
+    -- index in parent
+    child_index BIGINT not null,
+
+    has_children BOOLEAN not null,
+
+    -- child_page, child_database, paragraph, etc.
+    block_type TEXT not null,
+    type_data JSONB not null,
+    -- the checked state of to-do blocks, null for the other blocks
+    checked BOOLEAN,
+
+    raw_json JSONB,
+    synced_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS blocks_parent ON blocks (parent_id, child_index);
+
+CREATE TABLE IF NOT EXISTS pages (
+    id TEXT not null primary key,
+
+    parent_type TEXT not null,
+    parent_id TEXT not null,
+
+    created_time TIMESTAMPTZ not null,
+    created_by TEXT not null,
+    last_edited_time TIMESTAMPTZ not null,
+    last_edited_by TEXT not null,
+
+    archived BOOLEAN not null,
+    in_trash BOOLEAN not null,
+
+    -------------------------------
+    properties JSONB not null,
+    url TEXT not null,
+
+    public_url TEXT,
+    icon TEXT,
+    cover TEXT,
+
+    raw_json JSONB,
+    synced_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS pages_parent ON pages (parent_id);
+
+CREATE TABLE IF NOT EXISTS databases (
+    id TEXT not null primary key,
+
+    parent_type TEXT not null,
+    parent_id TEXT not null,
+
+    created_time TIMESTAMPTZ not null,
+    created_by TEXT not null,
+    last_edited_time TIMESTAMPTZ not null,
+    last_edited_by TEXT not null,
+
+    archived BOOLEAN not null,
+    in_trash BOOLEAN not null,
+
+    -------------------------------
+    properties JSONB not null,
+    url TEXT not null,
+
+    public_url TEXT,
+    icon TEXT,
+    cover TEXT,
+
+    is_inline BOOLEAN not null,
+    title JSONB not null,
+    description JSONB not null,
+    -- array of `{id, name}`
+    data_sources JSONB,
+
+    raw_json JSONB,
+    synced_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS databases_parent ON databases (parent_id);
+
+CREATE TABLE IF NOT EXISTS comments (
+    id TEXT not null primary key,
+
+    parent_type TEXT not null,
+    parent_id TEXT not null,
+
+    created_time TIMESTAMPTZ not null,
+    created_by TEXT not null,
+    last_edited_time TIMESTAMPTZ not null,
+
+    discussion_id TEXT not null,
+    rich_text JSONB not null,
+
+    raw_json JSONB,
+    synced_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS comments_parent ON comments (parent_id, discussion_id, created_time);
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT not null primary key,
+    -- person, bot
+    type TEXT,
+    name TEXT,
+    avatar_url TEXT,
+    email TEXT,
+    synced_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS sync_runs (
+    run_id BIGSERIAL primary key,
+    root_id TEXT not null,
+    started_at TIMESTAMPTZ not null,
+    -- null if the sync is still running or interrupted
+    finished_at TIMESTAMPTZ,
+    objects_count BIGINT,
+    -- running, ok, error
+    status TEXT not null
+);
+"#;
+
+#[cfg(test)]
+mod tests {
+    //! Run with a disposable db, e.g. `NOTION_ASYNC_TEST_PG_URL=postgres://localhost/test
+    //! cargo test --features postgres -- --ignored`.
+
+    use notion_async_api::{ObjectKey, ObjectType};
+    use sqlx::types::chrono::Utc;
+
+    use crate::{
+        store::Store,
+        tests::{block_json, page_json},
+    };
+
+    use super::PgStore;
+
+    async fn pg_store() -> PgStore {
+        let url = std::env::var("NOTION_ASYNC_TEST_PG_URL").expect("NOTION_ASYNC_TEST_PG_URL");
+        PgStore::connect(&url, None).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres db"]
+    async fn sync_and_prune() {
+        let mut store = pg_store().await;
+        // unique among the runs sharing the db
+        let root = format!("pg-root-{}", Utc::now().timestamp_micros());
+        let child = format!("{root}-b1");
+
+        let run_id = store.start_sync_run(&root).await.unwrap();
+        let mut page = page_json(&root, "workspace");
+        page["parent"] = serde_json::json!({ "type": "workspace", "workspace": true });
+        store
+            .upsert_page(serde_json::from_value(page).unwrap())
+            .await
+            .unwrap();
+        let mut block = block_json(&child, &root, 0);
+        block["parent"] = serde_json::json!({ "type": "page_id", "page_id": root });
+        store
+            .upsert_block(serde_json::from_value(block).unwrap())
+            .await
+            .unwrap();
+        store.finish_sync_run(run_id, 2, false).await.unwrap();
+
+        let since = store.now().await.unwrap() - std::time::Duration::from_secs(60);
+        assert!(store
+            .stale_descendants(&root, since)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let now = store.now().await.unwrap();
+        let stale = store
+            .stale_descendants(&root.replace('-', ""), now)
+            .await
+            .unwrap();
+        assert_eq!(
+            stale,
+            [
+                ObjectKey::new(ObjectType::Block, child),
+                ObjectKey::new(ObjectType::Page, root.clone()),
+            ]
+        );
+        store.delete_objects(&stale).await.unwrap();
+        assert!(store
+            .stale_descendants(&root, Utc::now())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+use std::time::Duration;
+
+use futures::{future::BoxFuture, FutureExt};
+use notion_async_api::AnyObject;
+use reqwest::{Client, StatusCode};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::mpsc, task::JoinHandle};
+
+// the batches waiting for the webhook, before `send` waits
+const QUEUE_SIZE: usize = 16;
+
+/// A destination of the synced objects, e.g. a webhook. The sinks compose,
+/// i.e. each object is sent to all the sinks of a sync.
+pub trait ObjectSink: Send {
+    /// Send a synced object, which may be buffered until [`flush`].
+    ///
+    /// [`flush`]: ObjectSink::flush
+    fn send<'a>(&'a mut self, obj: &'a AnyObject) -> BoxFuture<'a, std::io::Result<()>>;
+
+    /// Send the buffered objects, called when the sync is finished.
+    fn flush(&mut self) -> BoxFuture<'_, std::io::Result<()>>;
+
+    /// Take the problems met so far which didn't fail [`send`] or
+    /// [`flush`], e.g. the retried requests, to log them.
+    ///
+    /// [`send`]: ObjectSink::send
+    /// [`flush`]: ObjectSink::flush
+    fn take_warnings(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// POSTs the synced objects to a webhook URL, in batches of JSON arrays of
+/// objects (in the format of `sync --dump-json`).
+///
+/// The batches are posted in the background, so that the sync goes on while
+/// the webhook is slow or retried. A failed batch is retried with
+/// exponential backoff, then appended to the dead-letter file as newline
+/// delimited JSON, which can be imported or replayed later.
+pub struct WebhookSink {
+    batch_size: usize,
+    batch: Vec<serde_json::Value>,
+    // `None` once flushed
+    queue: Option<mpsc::Sender<Vec<serde_json::Value>>>,
+    worker: Option<JoinHandle<std::io::Result<()>>>,
+    warnings: mpsc::UnboundedReceiver<String>,
+}
+
+impl WebhookSink {
+    pub fn new(url: impl Into<String>, batch_size: usize, dead_letter: impl Into<String>) -> Self {
+        Self::with_retry_delay(url, batch_size, dead_letter, Duration::from_secs(1))
+    }
+
+    /// Like [`WebhookSink::new`], but the first retry of a batch waits
+    /// `delay` rather than a second.
+    pub fn with_retry_delay(
+        url: impl Into<String>,
+        batch_size: usize,
+        dead_letter: impl Into<String>,
+        delay: Duration,
+    ) -> Self {
+        let (queue, mut rx) = mpsc::channel(QUEUE_SIZE);
+        let (warnings_tx, warnings) = mpsc::unbounded_channel();
+        let poster = Poster {
+            client: Client::new(),
+            url: url.into(),
+            max_retries: 3,
+            delay,
+            dead_letter: dead_letter.into(),
+            warnings: warnings_tx,
+        };
+        let worker = tokio::spawn(async move {
+            while let Some(batch) = rx.recv().await {
+                poster.post(batch).await?;
+            }
+            Ok(())
+        });
+        Self {
+            batch_size: batch_size.max(1),
+            batch: Vec::new(),
+            queue: Some(queue),
+            worker: Some(worker),
+            warnings,
+        }
+    }
+
+    /// Queue the buffered batch, waiting only if the queue is full.
+    async fn queue_batch(&mut self) -> std::io::Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut self.batch);
+        let Some(queue) = self.queue.as_ref() else {
+            return Err(std::io::Error::other("the webhook sink is flushed"));
+        };
+        if queue.send(batch).await.is_err() {
+            // the worker has stopped on an error, which is returned by `flush`
+            return Err(std::io::Error::other("the webhook worker has stopped"));
+        }
+        Ok(())
+    }
+}
+
+impl ObjectSink for WebhookSink {
+    fn send<'a>(&'a mut self, obj: &'a AnyObject) -> BoxFuture<'a, std::io::Result<()>> {
+        async move {
+            self.batch.push(serde_json::to_value(obj)?);
+            if self.batch.len() >= self.batch_size {
+                self.queue_batch().await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    /// Post the last batch, and wait for all the queued ones.
+    fn flush(&mut self) -> BoxFuture<'_, std::io::Result<()>> {
+        async move {
+            let queued = self.queue_batch().await;
+            self.queue = None;
+            if let Some(worker) = self.worker.take() {
+                worker.await.map_err(std::io::Error::other)??;
+            }
+            queued
+        }
+        .boxed()
+    }
+
+    fn take_warnings(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        while let Ok(x) = self.warnings.try_recv() {
+            warnings.push(x);
+        }
+        warnings
+    }
+}
+
+/// Posts the queued batches of a [`WebhookSink`] in the background.
+struct Poster {
+    client: Client,
+    url: String,
+    max_retries: u32,
+    // of the first retry, doubled for each of the next ones
+    delay: Duration,
+    dead_letter: String,
+    warnings: mpsc::UnboundedSender<String>,
+}
+
+impl Poster {
+    async fn post(&self, batch: Vec<serde_json::Value>) -> std::io::Result<()> {
+        let mut delay = self.delay;
+        for retry in 0..=self.max_retries {
+            if retry > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            let error = match self.client.post(&self.url).json(&batch).send().await {
+                Ok(res) if res.status().is_success() => return Ok(()),
+                Ok(res) => {
+                    let status = res.status();
+                    if !is_retryable(status) {
+                        self.warn(format!("webhook responded {status}"));
+                        break;
+                    }
+                    status.to_string()
+                }
+                Err(e) => e.to_string(),
+            };
+            self.warn(format!("webhook failed ({error}), {retry} retries"));
+        }
+
+        self.warn(format!(
+            "{} objects written to the dead-letter file {}",
+            batch.len(),
+            self.dead_letter
+        ));
+        let mut lines = Vec::new();
+        for obj in batch {
+            serde_json::to_writer(&mut lines, &obj)?;
+            lines.push(b'\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.dead_letter)
+            .await?;
+        file.write_all(&lines).await
+    }
+
+    fn warn(&self, warning: String) {
+        // the sink is dropped without reading the warnings
+        let _ = self.warnings.send(warning);
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use notion_async_api::AnyObject;
+    use serde_json::Value;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::tests::block_json;
+
+    use super::{ObjectSink, WebhookSink};
+
+    /// A webhook replying the `statuses` in order, then 200. Return its URL
+    /// and the batches it receives.
+    async fn webhook(statuses: &[u16]) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let mut statuses: VecDeque<u16> = statuses.iter().copied().collect();
+        let received = batches.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let body_start = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    if let Some(i) = request.windows(4).position(|x| x == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                };
+                let headers = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                let len: usize = headers
+                    .lines()
+                    .find_map(|x| x.strip_prefix("content-length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                while request.len() < body_start + len {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let body = serde_json::from_slice(&request[body_start..]).unwrap();
+                received.lock().unwrap().push(body);
+
+                let status = statuses.pop_front().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, batches)
+    }
+
+    fn blocks(n: usize) -> Vec<AnyObject> {
+        (0..n)
+            .map(|i| serde_json::from_value(block_json(&format!("b{i}"), "p1", i)).unwrap())
+            .collect()
+    }
+
+    // of an object in the format of `sync --dump-json`
+    fn block_id(obj: &Value) -> &str {
+        obj["Block"]["id"].as_str().unwrap()
+    }
+
+    fn batch_ids(batches: &Mutex<Vec<Value>>) -> Vec<Vec<String>> {
+        let batches = batches.lock().unwrap();
+        batches
+            .iter()
+            .map(|x| {
+                let objects = x.as_array().unwrap();
+                objects.iter().map(|x| block_id(x).to_owned()).collect()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn post_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter = dir.path().join("dead.jsonl");
+        let (url, batches) = webhook(&[]).await;
+        let mut sink = WebhookSink::new(url, 2, dead_letter.to_str().unwrap());
+        for obj in blocks(3) {
+            sink.send(&obj).await.unwrap();
+        }
+        sink.flush().await.unwrap();
+
+        assert_eq!(batch_ids(&batches), [vec!["b0", "b1"], vec!["b2"]]);
+        assert!(sink.take_warnings().is_empty());
+        assert!(!dead_letter.exists());
+    }
+
+    #[tokio::test]
+    async fn retry_and_dead_letter() {
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter = dir.path().join("dead.jsonl");
+        // the first batch succeeds on the second retry, the second one is
+        // rejected
+        let (url, batches) = webhook(&[503, 429, 200, 400]).await;
+        let mut sink = WebhookSink::with_retry_delay(
+            url,
+            2,
+            dead_letter.to_str().unwrap(),
+            Duration::from_millis(1),
+        );
+        for obj in blocks(3) {
+            sink.send(&obj).await.unwrap();
+        }
+        sink.flush().await.unwrap();
+
+        assert_eq!(batch_ids(&batches).len(), 4);
+        assert_eq!(
+            sink.take_warnings(),
+            [
+                "webhook failed (503 Service Unavailable), 0 retries",
+                "webhook failed (429 Too Many Requests), 1 retries",
+                "webhook responded 400 Bad Request",
+                &format!(
+                    "1 objects written to the dead-letter file {}",
+                    dead_letter.display()
+                ),
+            ]
+        );
+        let lines = tokio::fs::read_to_string(&dead_letter).await.unwrap();
+        let ids: Vec<_> = lines
+            .lines()
+            .map(|x| block_id(&serde_json::from_str(x).unwrap()).to_owned())
+            .collect();
+        assert_eq!(ids, ["b2"]);
+    }
+
+    #[tokio::test]
+    async fn send_during_backoff() {
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter = dir.path().join("dead.jsonl");
+        let (url, batches) = webhook(&[503]).await;
+        let mut sink = WebhookSink::with_retry_delay(
+            url,
+            1,
+            dead_letter.to_str().unwrap(),
+            Duration::from_secs(3600),
+        );
+        let objects = blocks(3);
+        sink.send(&objects[0]).await.unwrap();
+        // the first batch is being retried in an hour
+        while batches.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        for obj in &objects[1..] {
+            tokio::time::timeout(Duration::from_secs(10), sink.send(obj))
+                .await
+                .expect("send waits for the backoff")
+                .unwrap();
+        }
+        assert_eq!(batch_ids(&batches).len(), 1);
+    }
+}
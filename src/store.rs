@@ -0,0 +1,267 @@
+use std::fmt::Display;
+
+use futures::{future::BoxFuture, FutureExt};
+use notion_async_api::{
+    Block, Comment, Database, LinkRef, ObjectKey, Page, RichText, User, ViewRef,
+};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    SqliteConnection,
+};
+
+use crate::db::{
+    current_time, delete_objects, finish_sync_run, insert_or_update_block,
+    insert_or_update_comment, insert_or_update_database, insert_or_update_page,
+    insert_or_update_user, insert_or_update_views, stale_descendants, start_sync_run,
+    update_block_rich_text, update_links, TruncatedField,
+};
+
+pub type StoreError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The error of an operation which a [`Store`] doesn't support, e.g. the
+/// sync runs of a store which keeps only the objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported(pub &'static str);
+
+impl Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} isn't supported by this db", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+/// Where the synced objects are stored, e.g. [`SqliteStore`], so that the
+/// sync isn't tied to one kind of database.
+///
+/// The data derived from the objects (links, views & the rich text of
+/// blocks) is optional, it's dropped by default. The sync runs & the
+/// deletion of stale objects fail with [`Unsupported`] by default.
+pub trait Store: Send {
+    fn upsert_block(&mut self, block: Block) -> BoxFuture<'_, Result<(), StoreError>>;
+
+    fn upsert_page(&mut self, page: Page) -> BoxFuture<'_, Result<(), StoreError>>;
+
+    fn upsert_database(&mut self, database: Database) -> BoxFuture<'_, Result<(), StoreError>>;
+
+    fn upsert_comment(&mut self, comment: Comment) -> BoxFuture<'_, Result<(), StoreError>>;
+
+    /// Upsert a user, which may have only its ID if it's embedded in another
+    /// object, e.g. `created_by`.
+    fn upsert_user<'a>(&'a mut self, user: &'a User) -> BoxFuture<'a, Result<(), StoreError>>;
+
+    /// Replace the links from the object `source`.
+    fn upsert_links<'a>(
+        &'a mut self,
+        _source: &'a ObjectKey,
+        _links: &'a [LinkRef],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async { Ok(()) }.boxed()
+    }
+
+    /// Record the database views referenced by the object `source_id`.
+    fn upsert_views<'a>(
+        &'a mut self,
+        _source_id: &'a str,
+        _views: &'a [ViewRef],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async { Ok(()) }.boxed()
+    }
+
+    /// Replace the rich text of the block.
+    fn upsert_block_rich_text<'a>(
+        &'a mut self,
+        _block_id: &'a str,
+        _rich_text: &'a [RichText],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async { Ok(()) }.boxed()
+    }
+
+    /// The current time of the db, which sets the `synced_at` of the
+    /// objects, so that the `since` of [`Store::stale_descendants`] isn't
+    /// skewed by the local clock. It's the local time by default.
+    fn now(&mut self) -> BoxFuture<'_, Result<DateTime<Utc>, StoreError>> {
+        async { Ok(Utc::now()) }.boxed()
+    }
+
+    /// Record the start of syncing the root `root_id`, return the ID of the
+    /// sync run, see [`Store::finish_sync_run`].
+    fn start_sync_run<'a>(
+        &'a mut self,
+        _root_id: &'a str,
+    ) -> BoxFuture<'a, Result<i64, StoreError>> {
+        async { Err(Unsupported("recording sync runs").into()) }.boxed()
+    }
+
+    /// Record the end of the sync run `run_id`, with the number of the synced
+    /// objects, and whether there was any error.
+    fn finish_sync_run(
+        &mut self,
+        _run_id: i64,
+        _objects_count: usize,
+        _failed: bool,
+    ) -> BoxFuture<'_, Result<(), StoreError>> {
+        async { Err(Unsupported("recording sync runs").into()) }.boxed()
+    }
+
+    /// Find the objects under the root `root_id` (including itself) which
+    /// haven't been synced since `since`, see [`stale_descendants`].
+    fn stale_descendants<'a>(
+        &'a mut self,
+        _root_id: &'a str,
+        _since: DateTime<Utc>,
+    ) -> BoxFuture<'a, Result<Vec<ObjectKey>, StoreError>> {
+        async { Err(Unsupported("finding stale objects").into()) }.boxed()
+    }
+
+    /// Take the JSON fields truncated by the upserts so far, e.g. to log
+    /// them.
+    fn take_truncated(&mut self) -> Vec<TruncatedField> {
+        Vec::new()
+    }
+
+    /// Delete the objects along with the data derived from them.
+    fn delete_objects<'a>(
+        &'a mut self,
+        _keys: &'a [ObjectKey],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async { Err(Unsupported("deleting objects").into()) }.boxed()
+    }
+}
+
+/// Stores the objects into the SQLite db initialized by
+/// [`init_db`](crate::init_db).
+pub struct SqliteStore<'a> {
+    db: &'a mut SqliteConnection,
+    max_field_bytes: Option<usize>,
+    truncated: Vec<TruncatedField>,
+}
+
+impl<'a> SqliteStore<'a> {
+    /// The JSON fields larger than `max_field_bytes` are truncated, see
+    /// [`insert_or_update_block`].
+    pub fn new(db: &'a mut SqliteConnection, max_field_bytes: Option<usize>) -> Self {
+        Self {
+            db,
+            max_field_bytes,
+            truncated: Vec::new(),
+        }
+    }
+
+    /// The SQLite db, for the features only supported by SQLite, e.g. the
+    /// full-text index.
+    pub fn db(&mut self) -> &mut SqliteConnection {
+        self.db
+    }
+}
+
+impl Store for SqliteStore<'_> {
+    fn upsert_block(&mut self, block: Block) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let truncated = insert_or_update_block(self.db, block, self.max_field_bytes).await?;
+            self.truncated.extend(truncated);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_page(&mut self, page: Page) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let truncated = insert_or_update_page(self.db, page, self.max_field_bytes).await?;
+            self.truncated.extend(truncated);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_database(&mut self, database: Database) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let truncated =
+                insert_or_update_database(self.db, database, self.max_field_bytes).await?;
+            self.truncated.extend(truncated);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_comment(&mut self, comment: Comment) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move {
+            let truncated =
+                insert_or_update_comment(self.db, comment, self.max_field_bytes).await?;
+            self.truncated.extend(truncated);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_user<'a>(&'a mut self, user: &'a User) -> BoxFuture<'a, Result<(), StoreError>> {
+        async move {
+            insert_or_update_user(self.db, user).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn upsert_links<'a>(
+        &'a mut self,
+        source: &'a ObjectKey,
+        links: &'a [LinkRef],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async move { Ok(update_links(self.db, source, links).await?) }.boxed()
+    }
+
+    fn upsert_views<'a>(
+        &'a mut self,
+        source_id: &'a str,
+        views: &'a [ViewRef],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async move { Ok(insert_or_update_views(self.db, source_id, views).await?) }.boxed()
+    }
+
+    fn upsert_block_rich_text<'a>(
+        &'a mut self,
+        block_id: &'a str,
+        rich_text: &'a [RichText],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async move { Ok(update_block_rich_text(self.db, block_id, rich_text).await?) }.boxed()
+    }
+
+    fn now(&mut self) -> BoxFuture<'_, Result<DateTime<Utc>, StoreError>> {
+        async move { Ok(current_time(self.db).await?) }.boxed()
+    }
+
+    fn start_sync_run<'a>(
+        &'a mut self,
+        root_id: &'a str,
+    ) -> BoxFuture<'a, Result<i64, StoreError>> {
+        async move { Ok(start_sync_run(self.db, root_id).await?) }.boxed()
+    }
+
+    fn finish_sync_run(
+        &mut self,
+        run_id: i64,
+        objects_count: usize,
+        failed: bool,
+    ) -> BoxFuture<'_, Result<(), StoreError>> {
+        async move { Ok(finish_sync_run(self.db, run_id, objects_count, failed).await?) }.boxed()
+    }
+
+    fn stale_descendants<'a>(
+        &'a mut self,
+        root_id: &'a str,
+        since: DateTime<Utc>,
+    ) -> BoxFuture<'a, Result<Vec<ObjectKey>, StoreError>> {
+        async move { Ok(stale_descendants(self.db, root_id, since).await?) }.boxed()
+    }
+
+    fn take_truncated(&mut self) -> Vec<TruncatedField> {
+        std::mem::take(&mut self.truncated)
+    }
+
+    fn delete_objects<'a>(
+        &'a mut self,
+        keys: &'a [ObjectKey],
+    ) -> BoxFuture<'a, Result<(), StoreError>> {
+        async move { Ok(delete_objects(self.db, keys).await?) }.boxed()
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use notion_async_api::{Block, BlockType, Object};
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Collects the plain text of the blocks while syncing, to build the
+/// full-text index of each page's content once all blocks are known.
+#[derive(Default)]
+pub struct TextIndex {
+    pages: Vec<String>,
+    // parent id => children
+    children: HashMap<String, Vec<IndexedBlock>>,
+}
+
+struct IndexedBlock {
+    id: String,
+    child_index: usize,
+    created_time: DateTime<Utc>,
+    text: String,
+    // false for child pages/databases, whose content belongs to themselves
+    descend: bool,
+}
+
+impl TextIndex {
+    pub fn add_page(&mut self, page_id: &str) {
+        self.pages.push(page_id.to_owned());
+    }
+
+    pub fn add_block(&mut self, block: &Block) {
+        self.children
+            .entry(block.obj.parent.id().to_owned())
+            .or_default()
+            .push(IndexedBlock {
+                id: block.id().to_owned(),
+                child_index: block.child_index,
+                created_time: block.obj.created_time,
+                text: block.plain_text(),
+                descend: !matches!(
+                    block.block_type,
+                    BlockType::ChildPage | BlockType::ChildDatabase
+                ),
+            });
+    }
+
+    /// The text of each page, i.e. the text of its blocks in document order,
+    /// one block per line.
+    pub fn page_texts(&mut self) -> Vec<(String, String)> {
+        for children in self.children.values_mut() {
+            // the same order as `Block::sibling_order`
+            children.sort_by(|a, b| {
+                (a.child_index, a.created_time, &a.id).cmp(&(b.child_index, b.created_time, &b.id))
+            });
+        }
+
+        self.pages
+            .iter()
+            .map(|page_id| {
+                let mut lines = Vec::new();
+                self.collect_text(page_id, &mut lines);
+                (page_id.clone(), lines.join("\n"))
+            })
+            .collect()
+    }
+
+    fn collect_text<'a>(&'a self, parent_id: &str, lines: &mut Vec<&'a str>) {
+        for block in self.children.get(parent_id).into_iter().flatten() {
+            if !block.text.is_empty() {
+                lines.push(&block.text);
+            }
+            if block.descend {
+                self.collect_text(&block.id, lines);
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt::Display;
+
+use sqlx::SqliteConnection;
+
+// the tables with `parent_type`/`parent_id`, and the columns referencing users
+const PARENT_TABLES: [(&str, &[&str]); 4] = [
+    ("blocks", &["created_by", "last_edited_by"]),
+    ("pages", &["created_by", "last_edited_by"]),
+    ("databases", &["created_by", "last_edited_by"]),
+    ("comments", &["created_by"]),
+];
+
+/// A violation of the integrity of a synced db, see [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The parent of a row isn't in db.
+    DanglingParent {
+        table: &'static str,
+        id: String,
+        parent_type: String,
+        parent_id: String,
+    },
+    /// The user who created/edited a row isn't among the synced users.
+    DanglingUser {
+        table: &'static str,
+        id: String,
+        column: &'static str,
+        user_id: String,
+    },
+    /// The `child_index` values of the blocks under a parent aren't
+    /// `0..count`.
+    ChildIndexGap {
+        parent_id: String,
+        count: i64,
+        min: i64,
+        max: i64,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::DanglingParent {
+                table,
+                id,
+                parent_type,
+                parent_id,
+            } => write!(
+                f,
+                "{table} {id}: parent {parent_type} {parent_id} not found"
+            ),
+            Violation::DanglingUser {
+                table,
+                id,
+                column,
+                user_id,
+            } => write!(
+                f,
+                "{table} {id}: {column} user {user_id} not found in the synced users"
+            ),
+            Violation::ChildIndexGap {
+                parent_id,
+                count,
+                min,
+                max,
+            } => write!(
+                f,
+                "blocks under {parent_id}: {count} blocks with child_index {min}..={max}"
+            ),
+        }
+    }
+}
+
+/// The result of [`verify`].
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub violations: Vec<Violation>,
+    /// Whether the users are checked, i.e. the users are synced by `sync
+    /// --users`. The users only referenced by the objects are stored with
+    /// their IDs alone, which don't count.
+    pub users_checked: bool,
+}
+
+/// Check the integrity of a synced db, e.g. after a large sync, to catch
+/// partial syncs and bugs in traversal:
+///
+/// - the parent of every row (except the workspace) is in db, the parents of
+///   `roots` (the IDs of the sync roots) and of the roots recorded in the
+///   `provenance` table are expected to be missing, so they're skipped
+/// - the users referenced by `created_by`/`last_edited_by` are among the
+///   synced users, if the users are synced
+/// - the `child_index` values of the blocks are contiguous per parent, note
+///   that syncing with `--block-types` leaves gaps
+pub async fn verify(db: &mut SqliteConnection, roots: &[String]) -> sqlx::Result<VerifyReport> {
+    let roots: Vec<String> = roots.iter().map(|x| x.replace('-', "")).collect();
+    let roots = serde_json::to_string(&roots).unwrap();
+    let mut report = VerifyReport::default();
+
+    for (table, _) in PARENT_TABLES {
+        let rows: Vec<(String, String, String)> = sqlx::query_as(&format!(
+            "select t.id, t.parent_type, t.parent_id from {table} t \
+             where ( \
+               (t.parent_type = 'page_id' \
+                and not exists (select 1 from pages x where x.id = t.parent_id)) \
+               or (t.parent_type = 'database_id' \
+                and not exists (select 1 from databases x where x.id = t.parent_id)) \
+               or (t.parent_type = 'block_id' \
+                and not exists (select 1 from blocks x where x.id = t.parent_id)) \
+             ) \
+             and replace(t.id, '-', '') not in (select value from json_each($1)) \
+             and not exists (select 1 from provenance p \
+               where p.object_id = t.id and replace(p.root_id, '-', '') = replace(t.id, '-', '')) \
+             order by t.id"
+        ))
+        .bind(&roots)
+        .fetch_all(&mut *db)
+        .await?;
+        report
+            .violations
+            .extend(rows.into_iter().map(|(id, parent_type, parent_id)| {
+                Violation::DanglingParent {
+                    table,
+                    id,
+                    parent_type,
+                    parent_id,
+                }
+            }));
+    }
+
+    // the referenced users are stored too, but without their types
+    let users: Option<(String,)> =
+        sqlx::query_as("select id from users where type is not null limit 1")
+            .fetch_optional(&mut *db)
+            .await?;
+    report.users_checked = users.is_some();
+    if report.users_checked {
+        for (table, columns) in PARENT_TABLES {
+            for &column in columns {
+                let rows: Vec<(String, String)> = sqlx::query_as(&format!(
+                    "select t.id, t.{column} from {table} t \
+                     where not exists ( \
+                       select 1 from users u where u.id = t.{column} and u.type is not null \
+                     ) \
+                     order by t.id"
+                ))
+                .fetch_all(&mut *db)
+                .await?;
+                report
+                    .violations
+                    .extend(
+                        rows.into_iter()
+                            .map(|(id, user_id)| Violation::DanglingUser {
+                                table,
+                                id,
+                                column,
+                                user_id,
+                            }),
+                    );
+            }
+        }
+    }
+
+    let rows: Vec<(String, i64, i64, i64)> = sqlx::query_as(
+        "select parent_id, count(*), min(child_index), max(child_index) from blocks \
+         group by parent_id \
+         having min(child_index) != 0 or max(child_index) != count(*) - 1 \
+           or count(distinct child_index) != count(*) \
+         order by parent_id",
+    )
+    .fetch_all(&mut *db)
+    .await?;
+    report
+        .violations
+        .extend(
+            rows.into_iter()
+                .map(|(parent_id, count, min, max)| Violation::ChildIndexGap {
+                    parent_id,
+                    count,
+                    min,
+                    max,
+                }),
+        );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use notion_async_api::User;
+    use serde_json::json;
+    use sqlx::SqliteConnection;
+
+    use crate::{
+        insert_or_update_block, insert_or_update_page, insert_or_update_user,
+        tests::{block_json, memory_db, page_json, user_json},
+    };
+
+    use super::{verify, Violation};
+
+    async fn insert_user(db: &mut SqliteConnection, js: serde_json::Value) {
+        let user: User = serde_json::from_value(js).unwrap();
+        insert_or_update_user(db, &user).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn violations() {
+        let mut db = memory_db().await;
+        let mut p2 = page_json("p2", "p1");
+        p2["last_edited_by"] = user_json("ghost");
+        for page in [page_json("p1", "outside"), p2] {
+            let page = serde_json::from_value(page).unwrap();
+            insert_or_update_page(&mut db, page, None).await.unwrap();
+        }
+        // b1 is missing, and b9 is under a missing block
+        for (id, parent_id, child_index) in [("b0", "p1", 0), ("b2", "p1", 2), ("b9", "b8", 0)] {
+            let mut block = block_json(id, parent_id, child_index);
+            if parent_id == "p1" {
+                block["parent"] = json!({ "type": "page_id", "page_id": "p1" });
+            }
+            let block = serde_json::from_value(block).unwrap();
+            insert_or_update_block(&mut db, block, None).await.unwrap();
+        }
+        // the referenced users, as stored by a sync
+        for id in ["user", "ghost"] {
+            insert_user(&mut db, user_json(id)).await;
+        }
+        let violations = vec![
+            Violation::DanglingParent {
+                table: "blocks",
+                id: "b9".into(),
+                parent_type: "block_id".into(),
+                parent_id: "b8".into(),
+            },
+            Violation::ChildIndexGap {
+                parent_id: "p1".into(),
+                count: 2,
+                min: 0,
+                max: 2,
+            },
+        ];
+
+        let report = verify(&mut db, &["p1".to_owned()]).await.unwrap();
+        assert!(!report.users_checked);
+        assert_eq!(report.violations, violations);
+
+        // synced by `--users`, but "ghost" isn't among the users any more
+        let user = json!({ "object": "user", "id": "user", "type": "person", "person": {} });
+        insert_user(&mut db, user).await;
+        let report = verify(&mut db, &["p1".to_owned()]).await.unwrap();
+        assert!(report.users_checked);
+        let ghost = Violation::DanglingUser {
+            table: "pages",
+            id: "p2".into(),
+            column: "last_edited_by",
+            user_id: "ghost".into(),
+        };
+        assert_eq!(
+            report.violations,
+            [&violations[..1], &[ghost], &violations[1..]].concat()
+        );
+    }
+}